@@ -1,61 +1,155 @@
 use jito_protos::shredstream::{
     shredstream_proxy_client::ShredstreamProxyClient, SubscribeEntriesRequest, Entry,
 };
-use tonic::Streaming;
+use tonic::{Status, Streaming};
 use crate::config::Config;
-use std::time::Duration;
+use crate::transaction::PUMP_PROGRAM_ID;
+use solana_sdk::pubkey::Pubkey;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, error::TrySendError};
 use tokio::time::sleep;
 
+// Delay before resubscribing after the subscription could not be established
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+// Minimum time between two logs of dropped frames while processing is behind
+const OVERFLOW_LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+// Endless stream of Shredstream entries that resubscribes whenever the subscription ends or fails.
+// Stream errors are yielded before resubscribing, so the consumer can log them.
+pub struct ShredstreamEntryStream {
+    inner: BoxStream<'static, Result<Entry, Status>>,
+    subscriptions: Arc<AtomicU64>, // Subscriptions established so far
+}
+
+impl ShredstreamEntryStream {
+    // Number of subscriptions established so far; changes when the stream resubscribed
+    pub fn subscription_count(&self) -> u64 {
+        self.subscriptions.load(Ordering::Relaxed)
+    }
+
+    // Frames with the subscription they were received on. Stream errors are logged and skipped,
+    // the stream resubscribes by itself.
+    pub fn frames(self) -> BoxStream<'static, (Entry, u64)> {
+        stream::unfold(self, |mut entries| async move {
+            loop {
+                match entries.next().await? {
+                    Ok(entry) => {
+                        let subscription = entries.subscription_count();
+                        return Some(((entry, subscription), entries));
+                    }
+                    Err(e) => println!("Failed to get message: {:?}", e),
+                }
+            }
+        })
+        .boxed()
+    }
+
+    // Read frames on a separate task into a bounded channel of `capacity` frames, so slow
+    // processing never delays reading the stream (and noticing that it died). While the channel
+    // is full, newly received frames are dropped and the overflow is logged.
+    pub fn spawn_frame_reader(self, capacity: usize) -> BoxStream<'static, (Entry, u64)> {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+
+        tokio::spawn(async move {
+            let mut frames = self.frames();
+            let mut dropped = 0u64;
+            let mut last_overflow_log: Option<Instant> = None;
+            while let Some(frame) = frames.next().await {
+                match sender.try_send(frame) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full((entry, _))) => {
+                        dropped += 1;
+                        if last_overflow_log.is_none_or(|logged| logged.elapsed() >= OVERFLOW_LOG_INTERVAL) {
+                            println!("Processing is behind the feed, dropped frame of slot {} ({} dropped so far, channel capacity {})",
+                                     entry.slot, dropped, capacity);
+                            last_overflow_log = Some(Instant::now());
+                        }
+                    }
+                    // The processing side is gone
+                    Err(TrySendError::Closed(_)) => break,
+                }
+            }
+        });
+
+        stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|frame| (frame, receiver))
+        })
+        .boxed()
+    }
+}
+
+impl Stream for ShredstreamEntryStream {
+    type Item = Result<Entry, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+// Whether a serialized frame can contain a transaction invoking one of the programs. Invoked
+// programs are always static account keys, never loaded from lookup tables, so a frame without
+// any of their pubkey bytes has no such transaction.
+fn frame_mentions_programs(entries: &[u8], programs: &[Pubkey]) -> bool {
+    entries.windows(32).any(|window| programs.iter().any(|program| window == program.as_ref()))
+}
+
 pub struct ShredstreamClient {
     client: ShredstreamProxyClient<tonic::transport::Channel>,
-    config: Config,
+    config: Arc<Config>,
+    last_subscribe: Option<Instant>, // Start of the last subscribe cycle, for the resubscribe floor
+    program_filter: Vec<Pubkey>,     // Programs frames are filtered on client-side (empty = all frames)
 }
 
 impl ShredstreamClient {
-    pub async fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
+    pub async fn new(config: Arc<Config>) -> Result<Self, Box<dyn std::error::Error>> {
         let client = Self::connect(&config).await?;
-        Ok(Self { client, config })
+        Ok(Self { client, config, last_subscribe: None, program_filter: Vec::new() })
     }
 
     async fn connect(config: &Config) -> Result<ShredstreamProxyClient<tonic::transport::Channel>, Box<dyn std::error::Error>> {
-        let mut retries = 0;
-        let max_retries = 5;
-        let base_delay = Duration::from_secs(1);
+        let client = config.retry_policy
+            .run("Connection", |_| ShredstreamProxyClient::connect(config.server_url.clone()))
+            .await?;
+        Ok(client)
+    }
 
-        loop {
-            match ShredstreamProxyClient::connect(config.server_url.clone()).await {
-                Ok(client) => return Ok(client),
-                Err(e) => {
-                    retries += 1;
-                    if retries >= max_retries {
-                        return Err(Box::new(e));
-                    }
-                    let delay = base_delay * retries;
-                    println!("Connection failed, retrying in {} seconds (attempt {})...", delay.as_secs(), retries);
-                    sleep(delay).await;
-                }
+    pub async fn subscribe_entries(&mut self) -> Result<Streaming<Entry>, Box<dyn std::error::Error>> {
+        // A server that accepts the subscription and closes it right away would otherwise make
+        // every cycle "succeed" and resubscribe in a tight loop
+        let min_interval = Duration::from_millis(self.config.min_resubscribe_interval_ms);
+        if let Some(last_subscribe) = self.last_subscribe {
+            let elapsed = last_subscribe.elapsed();
+            if elapsed < min_interval {
+                let wait = min_interval - elapsed;
+                println!("Last subscription was {}ms ago, waiting {}ms before resubscribing", elapsed.as_millis(), wait.as_millis());
+                sleep(wait).await;
             }
         }
-    }
+        self.last_subscribe = Some(Instant::now());
 
-    pub async fn subscribe_entries(&mut self) -> Result<Streaming<Entry>, Box<dyn std::error::Error>> {
-        let mut retries = 0;
-        let max_retries = 5;
-        let base_delay = Duration::from_secs(1);
+        // Reconnects between tries, which needs the client mutably, so the policy is applied inline
+        let retry_policy = self.config.retry_policy;
+        let mut attempt = 0;
 
         loop {
+            attempt += 1;
             match self.client
                 .subscribe_entries(SubscribeEntriesRequest {})
                 .await
             {
                 Ok(response) => return Ok(response.into_inner()),
                 Err(e) => {
-                    retries += 1;
-                    if retries >= max_retries {
-                        return Err(Box::new(e));
-                    }
-                    let delay = base_delay * retries;
-                    println!("Subscription failed, retrying in {} seconds (attempt {})...", delay.as_secs(), retries);
+                    let delay = match retry_policy.delay_after(attempt) {
+                        Some(delay) => delay,
+                        None => return Err(Box::new(e)),
+                    };
+                    println!("Subscription failed ({}), retrying in {}ms (attempt {}/{})...",
+                             e.message(), delay.as_millis(), attempt, retry_policy.max_attempts);
                     sleep(delay).await;
                     
                     // Attempt to reconnect
@@ -64,4 +158,71 @@ impl ShredstreamClient {
             }
         }
     }
+
+    // Subscribe to the entries of transactions involving the given programs only. This version of
+    // the ShredstreamProxy protocol has no filter fields in SubscribeEntriesRequest, so the server
+    // still sends every entry; frames not mentioning any of the programs are dropped client-side
+    // by the entry stream.
+    pub async fn subscribe_entries_filtered(&mut self, programs: &[Pubkey]) -> Result<Streaming<Entry>, Box<dyn std::error::Error>> {
+        self.program_filter = programs.to_vec();
+        self.subscribe_entries().await
+    }
+
+    // Turn the client into a stream of entries with automatic resubscription. With FILTER_ENTRIES,
+    // only frames that can contain Pump transactions are yielded.
+    pub fn into_entry_stream(self) -> ShredstreamEntryStream {
+        let programs = if self.config.filter_entries {
+            vec![self.config.token_creator_pubkey, PUMP_PROGRAM_ID]
+        } else {
+            Vec::new()
+        };
+        let subscriptions = Arc::new(AtomicU64::new(0));
+        let counter = subscriptions.clone();
+        let inner = stream::unfold((self, None::<Streaming<Entry>>), move |(mut client, mut current)| {
+            let counter = counter.clone();
+            let programs = programs.clone();
+            async move {
+                loop {
+                    let subscription = match current.as_mut() {
+                        Some(subscription) => subscription,
+                        None => {
+                            let subscription = if programs.is_empty() {
+                                client.subscribe_entries().await
+                            } else {
+                                client.subscribe_entries_filtered(&programs).await
+                            };
+                            match subscription.map_err(|e| e.to_string()) {
+                                Ok(subscription) => {
+                                    counter.fetch_add(1, Ordering::Relaxed);
+                                    current = Some(subscription);
+                                }
+                                Err(e) => {
+                                    println!("Connection lost: {e}");
+                                    println!("Reconnecting in {} seconds...", RESUBSCRIBE_DELAY.as_secs());
+                                    sleep(RESUBSCRIBE_DELAY).await;
+                                }
+                            }
+                            continue;
+                        }
+                    };
+
+                    match subscription.message().await {
+                        Ok(Some(entry)) => {
+                            if !client.program_filter.is_empty() && !frame_mentions_programs(&entry.entries, &client.program_filter) {
+                                continue;
+                            }
+                            return Some((Ok(entry), (client, current)));
+                        }
+                        Ok(None) => {
+                            println!("Shredstream subscription ended, resubscribing");
+                            current = None;
+                        }
+                        Err(status) => return Some((Err(status), (client, None))),
+                    }
+                }
+            }
+        });
+
+        ShredstreamEntryStream { inner: inner.boxed(), subscriptions }
+    }
 }
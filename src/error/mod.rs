@@ -0,0 +1,144 @@
+use redis::RedisError;
+use solana_rpc_client_api::client_error::Error as ClientError;
+use solana_sdk::transaction::TransactionError;
+use std::error::Error;
+use std::fmt;
+
+// Errors returned by the trading paths
+#[derive(Debug)]
+pub enum SniperError {
+    // The RPC reported the transaction as already processed, i.e. a copy of it landed
+    DuplicateSignature(String),
+    // An account of the transaction was write-locked by another transaction (hot bonding curve);
+    // transient, unlike slippage or insufficient funds
+    AccountInUse(String),
+    // A Jito bundle was not confirmed as landed in time
+    BundleTimeout(String),
+    // RPC request failed
+    Rpc(ClientError),
+    // Transaction simulation returned an error
+    Simulation(String),
+    // The configured private key could not be parsed
+    InvalidKey(String),
+    // Redis request failed
+    Redis(RedisError),
+    // SQLite store request failed
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+    // Any other failure, with a description
+    Other(String),
+}
+
+impl SniperError {
+    pub fn is_duplicate_signature(&self) -> bool {
+        matches!(self, SniperError::DuplicateSignature(_))
+    }
+
+    pub fn is_account_in_use(&self) -> bool {
+        matches!(self, SniperError::AccountInUse(_))
+    }
+}
+
+impl fmt::Display for SniperError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SniperError::DuplicateSignature(message) => write!(f, "Transaction already processed: {}", message),
+            SniperError::AccountInUse(message) => write!(f, "Account in use: {}", message),
+            SniperError::BundleTimeout(bundle_id) => write!(f, "Bundle {} not confirmed in time", bundle_id),
+            SniperError::Rpc(e) => write!(f, "RPC error: {}", e),
+            SniperError::Simulation(message) => write!(f, "Simulation failed: {}", message),
+            SniperError::InvalidKey(message) => write!(f, "Invalid private key: {}", message),
+            SniperError::Redis(e) => write!(f, "Redis error: {}", e),
+            #[cfg(feature = "sqlite")]
+            SniperError::Sqlite(e) => write!(f, "SQLite error: {}", e),
+            SniperError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for SniperError {}
+
+// Write-lock contention: another transaction locked the account, or the per-account compute
+// limit of the block was reached by the transactions hitting it
+pub fn is_write_lock_contention(error: &TransactionError) -> bool {
+    matches!(error, TransactionError::AccountInUse | TransactionError::WouldExceedMaxAccountCostLimit)
+}
+
+impl From<ClientError> for SniperError {
+    fn from(e: ClientError) -> Self {
+        let transaction_error = e.get_transaction_error();
+
+        // Sending the same signed transaction twice (multi-send, retries) is rejected as a duplicate
+        let already_processed = transaction_error == Some(TransactionError::AlreadyProcessed)
+            || e.to_string().contains("already been processed");
+
+        let account_in_use = transaction_error.as_ref().is_some_and(is_write_lock_contention)
+            || e.to_string().contains("Account in use");
+
+        if already_processed {
+            SniperError::DuplicateSignature(e.to_string())
+        } else if account_in_use {
+            SniperError::AccountInUse(e.to_string())
+        } else {
+            SniperError::Rpc(e)
+        }
+    }
+}
+
+impl From<RedisError> for SniperError {
+    fn from(e: RedisError) -> Self {
+        SniperError::Redis(e)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for SniperError {
+    fn from(e: rusqlite::Error) -> Self {
+        SniperError::Sqlite(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_rpc_client_api::client_error::ErrorKind;
+    use solana_rpc_client_api::request::{RpcError, RpcResponseErrorData};
+    use solana_rpc_client_api::response::RpcSimulateTransactionResult;
+    use solana_sdk::instruction::InstructionError;
+
+    // Send error of a transaction rejected by the node's simulation with the given error
+    fn preflight_failure(err: TransactionError) -> ClientError {
+        let result = RpcSimulateTransactionResult {
+            err: Some(err.clone()),
+            logs: None,
+            accounts: None,
+            units_consumed: None,
+            return_data: None,
+            inner_instructions: None,
+        };
+        ErrorKind::RpcError(RpcError::RpcResponseError {
+            code: -32002,
+            message: format!("Transaction simulation failed: {}", err),
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+        })
+        .into()
+    }
+
+    // The retry of a buy is decided from the error its send returned
+    #[test]
+    fn write_lock_contention_in_send_errors_is_account_in_use() {
+        for err in [TransactionError::AccountInUse, TransactionError::WouldExceedMaxAccountCostLimit] {
+            assert!(SniperError::from(preflight_failure(err.clone())).is_account_in_use());
+            assert!(SniperError::from(ClientError::from(ErrorKind::TransactionError(err))).is_account_in_use());
+        }
+
+        // Nodes that only report the message
+        let message_only: ClientError = ErrorKind::Custom("Account in use".to_string()).into();
+        assert!(SniperError::from(message_only).is_account_in_use());
+
+        // Slippage and insufficient funds are permanent
+        let slippage = TransactionError::InstructionError(1, InstructionError::Custom(6002));
+        assert!(!SniperError::from(preflight_failure(slippage)).is_account_in_use());
+        assert!(!SniperError::from(preflight_failure(TransactionError::InsufficientFundsForFee)).is_account_in_use());
+    }
+}
@@ -1,6 +1,8 @@
 use solana_sdk::pubkey::Pubkey;
 use std::error::Error;
 use borsh::BorshDeserialize;
+use serde::{Deserialize, Serialize};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 
 // Define CreateEvent arguments structure for Borsh deserialization
 #[derive(BorshDeserialize, Debug)]
@@ -10,6 +12,18 @@ struct CreateArgs {
     uri: String,
 }
 
+// Fields of the CreateEvent the program emits on create, in event order. Later program versions
+// append more fields, which are ignored.
+#[derive(BorshDeserialize, Debug)]
+struct CreateEventArgs {
+    name: String,
+    symbol: String,
+    uri: String,
+    mint: [u8; 32],
+    bonding_curve: [u8; 32],
+    user: [u8; 32],
+}
+
 // Define Buy arguments structure for Borsh deserialization
 #[derive(BorshDeserialize, Debug)]
 struct BuyArgs {
@@ -17,7 +31,14 @@ struct BuyArgs {
     max_sol_cost: u64,
 }
 
-#[derive(Debug)]
+// Define Sell arguments structure for Borsh deserialization
+#[derive(BorshDeserialize, Debug)]
+struct SellArgs {
+    amount: u64,
+    min_sol_output: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateEventInstruction {
     pub name: String,
     pub symbol: String,
@@ -25,18 +46,72 @@ pub struct CreateEventInstruction {
     pub user: Pubkey,
 }
 
-#[derive(Debug)]
+// CreateEvent emitted by the program, read from the "Program data:" lines of a create's logs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateEvent {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub user: Pubkey,
+}
+
+impl From<CreateEvent> for CreateEventInstruction {
+    fn from(event: CreateEvent) -> Self {
+        CreateEventInstruction { name: event.name, symbol: event.symbol, uri: event.uri, user: event.user }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuyInstruction {
     pub amount: u64,
     pub max_sol_cost: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SellInstruction {
+    pub amount: u64,         // Tokens sold
+    pub min_sol_output: u64, // Least SOL the seller accepts (lamports)
+}
+
+// A parsed Pump instruction or event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PumpInstruction {
+    Create(CreateEventInstruction),
+    CreateEvent(CreateEvent),
+    Buy(BuyInstruction),
+}
+
 // Instruction discriminator bytes for CreateEvent
-const CREATE_EVENT_DISCRIMINATOR: [u8; 8] = [0x18, 0x1e, 0xc8, 0x28, 0x05, 0x1c, 0x07, 0x77];
+pub(crate) const CREATE_EVENT_DISCRIMINATOR: [u8; 8] = [0x18, 0x1e, 0xc8, 0x28, 0x05, 0x1c, 0x07, 0x77];
 // Instruction discriminator bytes for BuyEvent
-const BUY_EVENT_DISCRIMINATOR: [u8; 8] = [0x66, 0x06, 0x3d, 0x12, 0x01, 0xda, 0xeb, 0xea];
+pub(crate) const BUY_EVENT_DISCRIMINATOR: [u8; 8] = [0x66, 0x06, 0x3d, 0x12, 0x01, 0xda, 0xeb, 0xea];
+// Instruction discriminator bytes for Sell
+const SELL_DISCRIMINATOR: [u8; 8] = [0x33, 0xe6, 0x85, 0xa4, 0x01, 0x7f, 0x83, 0xad];
+// Event discriminator bytes of the emitted CreateEvent, sha256("event:CreateEvent")[..8]
+pub(crate) const CREATE_EVENT_LOG_DISCRIMINATOR: [u8; 8] = [0x1b, 0x72, 0xa9, 0x4d, 0xde, 0xeb, 0x63, 0x76];
+
+// Classify an instruction by its discriminator alone, without allocating, so instructions that
+// parse_instruction_data cannot handle are skipped before the full parse. Accepts the same
+// discriminators as parse_instruction_data, including its first-byte fallbacks.
+pub fn estimate_instruction_type(data: &[u8]) -> Option<&'static str> {
+    let discriminator: [u8; 8] = data.get(..8)?.try_into().ok()?;
+
+    match discriminator {
+        CREATE_EVENT_DISCRIMINATOR => Some("Create"),
+        BUY_EVENT_DISCRIMINATOR => Some("Buy"),
+        SELL_DISCRIMINATOR => Some("Sell"),
+        [24, ..] => Some("Create"),
+        [102, ..] => Some("Buy"),
+        _ => None,
+    }
+}
+
+// Instruction type name and the decoded create or buy instruction
+pub type ParsedInstruction = (String, Option<CreateEventInstruction>, Option<BuyInstruction>);
 
-pub fn parse_instruction_data(data: &[u8]) -> Result<(String, Option<CreateEventInstruction>, Option<BuyInstruction>), Box<dyn Error>> {
+pub fn parse_instruction_data(data: &[u8]) -> Result<ParsedInstruction, Box<dyn Error>> {
     if data.len() < 8 {
         return Err("Instruction data too short".into());
     }
@@ -207,3 +282,124 @@ pub fn parse_instruction_data(data: &[u8]) -> Result<(String, Option<CreateEvent
         _ => Err("Unknown instruction data".into()),
     }
 }
+
+// Parse a sell instruction; kept apart from parse_instruction_data, which only yields creates and buys
+pub fn parse_sell_instruction(data: &[u8]) -> Option<SellInstruction> {
+    if data.get(..8)? != SELL_DISCRIMINATOR {
+        return None;
+    }
+    let args = SellArgs::deserialize(&mut &data[8..]).ok()?;
+    Some(SellInstruction { amount: args.amount, min_sol_output: args.min_sol_output })
+}
+
+// Parse the CreateEvent emitted by a create; None for other events
+pub fn parse_create_event(data: &[u8]) -> Option<CreateEvent> {
+    if data.get(..8)? != CREATE_EVENT_LOG_DISCRIMINATOR {
+        return None;
+    }
+    let args = CreateEventArgs::deserialize(&mut &data[8..]).ok()?;
+    Some(CreateEvent {
+        name: args.name,
+        symbol: args.symbol,
+        uri: args.uri,
+        mint: Pubkey::new_from_array(args.mint),
+        bonding_curve: Pubkey::new_from_array(args.bonding_curve),
+        user: Pubkey::new_from_array(args.user),
+    })
+}
+
+// Prefixes of program log lines that carry base64 encoded instruction or event data
+const LOG_DATA_PREFIXES: [&str; 2] = ["Program log: ", "Program data: "];
+
+// Parse a create or buy from a program log line (simulation or confirmed transaction logs).
+// Fallback for when the instruction data itself cannot be parsed but the emitted data is intact.
+// Anchor events are emitted as "Program data:" lines and start with their event discriminator.
+pub fn parse_instruction_from_log(log_line: &str) -> Option<PumpInstruction> {
+    let payload = LOG_DATA_PREFIXES
+        .iter()
+        .find_map(|prefix| log_line.strip_prefix(prefix))?;
+    let data = BASE64.decode(payload.trim()).ok()?;
+
+    if let Some(event) = parse_create_event(&data) {
+        return Some(PumpInstruction::CreateEvent(event));
+    }
+    match parse_instruction_data(&data).ok()? {
+        (_, Some(create), _) => Some(PumpInstruction::Create(create)),
+        (_, _, Some(buy)) => Some(PumpInstruction::Buy(buy)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Anchor derives instruction discriminators as sha256("global:{instruction_name}")[..8]
+    fn anchor_discriminator(instruction_name: &str) -> [u8; 8] {
+        let hash = solana_sdk::hash::hashv(&[b"global:", instruction_name.as_bytes()]);
+        let mut discriminator = [0u8; 8];
+        discriminator.copy_from_slice(&hash.to_bytes()[..8]);
+        discriminator
+    }
+
+    // A stale constant would make every instruction fall through to "Unknown instruction data"
+    #[test]
+    fn verify_discriminators() {
+        assert_eq!(CREATE_EVENT_DISCRIMINATOR, anchor_discriminator("create"));
+        assert_eq!(BUY_EVENT_DISCRIMINATOR, anchor_discriminator("buy"));
+        assert_eq!(SELL_DISCRIMINATOR, anchor_discriminator("sell"));
+
+        // Events use the "event:" namespace and the event name instead
+        let hash = solana_sdk::hash::hashv(&[b"event:", b"CreateEvent"]);
+        assert_eq!(CREATE_EVENT_LOG_DISCRIMINATOR, hash.to_bytes()[..8]);
+    }
+
+    // CreateEvent as a create emits it on mainnet: the event discriminator, name, symbol, URI, mint,
+    // bonding curve and user, followed by the fields later program versions append (creator,
+    // timestamp, virtual and real reserves, total supply)
+    fn create_event_data(name: &str, symbol: &str, uri: &str, mint: Pubkey, bonding_curve: Pubkey, user: Pubkey) -> Vec<u8> {
+        let mut data = CREATE_EVENT_LOG_DISCRIMINATOR.to_vec();
+        for field in [name, symbol, uri] {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field.as_bytes());
+        }
+        for key in [mint, bonding_curve, user, user] {
+            data.extend_from_slice(key.as_ref());
+        }
+        for value in [1_718_000_000u64, 1_073_000_000_000_000, 30_000_000_000, 793_100_000_000_000, 1_000_000_000_000_000] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn create_events_are_parsed_from_program_data_lines() {
+        let (mint, bonding_curve, user) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let data = create_event_data("Token", "TKN", "https://ipfs.io/ipfs/token.json", mint, bonding_curve, user);
+        let logs = [
+            "Program 6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P invoke [1]".to_string(),
+            "Program log: Instruction: Create".to_string(),
+            format!("Program data: {}", BASE64.encode(&data)),
+            "Program 6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P success".to_string(),
+        ];
+
+        let events: Vec<CreateEvent> = logs.iter().filter_map(|line| match parse_instruction_from_log(line) {
+            Some(PumpInstruction::CreateEvent(event)) => Some(event),
+            _ => None,
+        }).collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].name.as_str(), events[0].symbol.as_str()), ("Token", "TKN"));
+        assert_eq!(events[0].uri, "https://ipfs.io/ipfs/token.json");
+        assert_eq!((events[0].mint, events[0].bonding_curve, events[0].user), (mint, bonding_curve, user));
+
+        // Other events and truncated data are not creates
+        let mut other_event = data.clone();
+        other_event[0] ^= 0xff;
+        assert!(parse_create_event(&other_event).is_none());
+        assert!(parse_create_event(&data[..40]).is_none());
+
+        // Plain log text and lines of other programs are not events
+        assert!(parse_instruction_from_log("Program log: Instruction: Create").is_none());
+        assert!(parse_instruction_from_log(&format!("Program return: {}", BASE64.encode(&data))).is_none());
+    }
+}
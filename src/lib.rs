@@ -1,4 +1,5 @@
 pub mod config;
+pub mod error;
 pub mod client;
 pub mod processor;
 pub mod utils;
@@ -1,31 +1,250 @@
-mod config;
-mod client;
-mod processor;
-mod utils;
-mod instruction;
-mod transaction;
-
-use config::Config;
+use jito_shredstream_client::{client, config, processor, transaction, utils};
+use config::{Config, TradingMode};
 use client::ShredstreamClient;
-use processor::TransactionProcessor;
-use utils::deserialize_entries;
+use processor::{FeedSlot, ProcessorMetrics, TransactionProcessor};
+use utils::{deserialize_entries, deserialize_entries_resilient};
 use utils::redis::RedisClient;
-use utils::auto_trader::AutoTrader;
-use std::error::Error;
+use utils::auto_trader::{AutoTrader, AutoTraderStats, PositionInventory, TraderControl};
+use utils::bonding_curve::spawn_repricing_task;
+use utils::blockhash_cache::BlockhashCache;
+use utils::slot_gaps::SlotGapTracker;
+use utils::telemetry;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use std::env;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, info_span, Instrument};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, signer::Signer};
 use dotenvy::dotenv;
+use clap::Parser;
+use futures::StreamExt;
+use std::path::{Path, PathBuf};
+use utils::replay::{read_recorded_frames, FrameCapture};
+
+// Maximum time to wait for in-flight snipes on shutdown
+const SHUTDOWN_SNIPE_TIMEOUT_MS: u64 = 10_000;
+
+#[derive(Parser)]
+#[command(about = "Jito Shredstream Pump.fun sniper")]
+struct Cli {
+    /// Process a recorded entry file offline with paper trading instead of connecting to Shredstream
+    #[arg(long, value_name = "FILE")]
+    dry_run_replay: Option<PathBuf>,
+
+    /// Submit and confirm a 0-lamport self-transfer through the buy send path before trading
+    #[arg(long)]
+    selftest: bool,
+
+    /// Print all recognised environment variables with their values, defaults and descriptions, then exit
+    #[arg(long)]
+    print_env: bool,
+}
+
+// Feed a recording through the processor with paper trading and print the results
+async fn run_replay(config: Config, path: &Path) {
+    let frames = match read_recorded_frames(path) {
+        Ok(frames) => frames,
+        Err(e) => {
+            println!("Failed to read recording {}: {:?}", path.display(), e);
+            return;
+        }
+    };
+    println!("Replaying {} frames from {}", frames.len(), path.display());
+
+    // Nothing may touch the chain during a replay
+    let config = Config {
+        simulate_initial_reserves: false,
+        calibrate_compute_units: false,
+        create_commitment: None,
+        ..config
+    };
+
+    let mut processor = TransactionProcessor::new(Arc::new(config));
+    processor.enable_paper_trading();
+
+    for frame in frames {
+        match deserialize_entries(&frame.entries) {
+            Ok(entries) => {
+                if let Err(e) = processor.process_entries_async(entries, frame.slot).await {
+                    println!("Failed to process entries: {:?}", e);
+                }
+            },
+            Err(e) => println!("Deserialization failed at slot {}: {e}", frame.slot),
+        }
+    }
+
+    processor.print_paper_report();
+}
+
+// Genesis hash of mainnet-beta
+const MAINNET_GENESIS_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+
+// Whether the RPC node serves mainnet; an unreachable node counts as mainnet, so the safe mode
+// gate does not open by accident
+async fn is_mainnet(rpc_url: &str) -> bool {
+    match RpcClient::new(rpc_url.to_string()).get_genesis_hash().await {
+        Ok(genesis_hash) => genesis_hash.to_string() == MAINNET_GENESIS_HASH,
+        Err(e) => {
+            println!("Failed to read the cluster genesis hash, assuming mainnet: {}", e);
+            true
+        }
+    }
+}
+
+// SIGUSR1 pauses new snipes (sells continue) and dumps the captured frames, to stop entering and
+// capture an incident noticed by the operator; SIGUSR2 resumes snipes
+#[cfg(unix)]
+fn spawn_control_signals(control: Arc<TraderControl>, frame_capture: Option<FrameCapture>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::user_defined1()) {
+        Ok(mut signals) => {
+            let control = control.clone();
+            tokio::spawn(async move {
+                while signals.recv().await.is_some() {
+                    println!("SIGUSR1 received, pausing snipes");
+                    control.halt();
+                    if let Some(frame_capture) = &frame_capture {
+                        frame_capture.dump("SIGUSR1");
+                    }
+                }
+            });
+        }
+        Err(e) => println!("Failed to install SIGUSR1 handler: {}", e),
+    }
+
+    match signal(SignalKind::user_defined2()) {
+        Ok(mut signals) => {
+            tokio::spawn(async move {
+                while signals.recv().await.is_some() {
+                    println!("SIGUSR2 received, resuming snipes");
+                    control.resume();
+                }
+            });
+        }
+        Err(e) => println!("Failed to install SIGUSR2 handler: {}", e),
+    }
+}
+
+#[cfg(not(unix))]
+fn spawn_control_signals(_control: Arc<TraderControl>, _frame_capture: Option<FrameCapture>) {}
+
+// Every SUMMARY_INTERVAL_MS, log one grep-able line with the feed, trading and Redis state.
+// Session PnL is the wallet balance change since startup, so open positions count at their cost;
+// like the lag and blockhash hit rate, it is "?" when the balance could not be read.
+// Fields are space-separated key=value pairs in a fixed order; new fields are only appended.
+fn spawn_health_log(
+    config: Arc<Config>,
+    redis_client: Arc<RedisClient>,
+    feed_slot: FeedSlot,
+    processor_metrics: ProcessorMetrics,
+    trader_stats: Arc<AutoTraderStats>,
+    inventory: Arc<RwLock<PositionInventory>>,
+    blockhash_cache: Arc<BlockhashCache>,
+) {
+    tokio::spawn(async move {
+        let rpc_client = RpcClient::new(config.rpc_url.clone());
+        let wallet = utils::keypair::parse_keypair(&config.private_key).ok().map(|keypair| keypair.pubkey());
+        let starting_balance = match &wallet {
+            Some(wallet) => rpc_client.get_balance(wallet).await.ok(),
+            None => None,
+        };
+
+        let mut interval = tokio::time::interval(Duration::from_millis(config.summary_interval_ms));
+        interval.tick().await;
+        let mut last_entries = processor_metrics.entries_processed();
+        let mut last_tick = Instant::now();
+
+        loop {
+            interval.tick().await;
+
+            let entries = processor_metrics.entries_processed();
+            let entries_per_sec = (entries - last_entries) as f64 / last_tick.elapsed().as_secs_f64();
+            last_entries = entries;
+            last_tick = Instant::now();
+
+            let slot = feed_slot.get().unwrap_or(0);
+            let lag = match rpc_client.get_slot_with_commitment(CommitmentConfig::processed()).await {
+                Ok(chain_slot) => chain_slot.saturating_sub(slot).to_string(),
+                Err(_) => "?".to_string(),
+            };
+            let session_pnl = match (&wallet, starting_balance) {
+                (Some(wallet), Some(starting_balance)) => match rpc_client.get_balance(wallet).await {
+                    Ok(balance) => format!("{:.4}", (balance as f64 - starting_balance as f64) / 1_000_000_000.0),
+                    Err(_) => "?".to_string(),
+                },
+                _ => "?".to_string(),
+            };
+            let positions = inventory.read().await.count_positions();
+            let health = redis_client.health_status().await;
+            let blockhash_hit_rate = match blockhash_cache.hit_rate() {
+                Some(hit_rate) => format!("{:.2}", hit_rate),
+                None => "?".to_string(),
+            };
+
+            info!("[HEALTH] slot={} lag={}slots entries/s={:.0} snipes={} positions={} session_pnl={}SOL redis_ping={}ms entries={} creates={} triggers={} snipes_attempted={} sells={} blockhash_hit_rate={}",
+                  slot, lag, entries_per_sec, trader_stats.snipes(), positions, session_pnl, health.ping_latency_ms,
+                  entries, processor_metrics.creates_seen(), processor_metrics.snipe_triggers(), trader_stats.attempts(), trader_stats.sells(), blockhash_hit_rate);
+        }
+    });
+}
 
 #[tokio::main]
 async fn main() {
     // Load environment variables
     dotenv().ok();
 
+    let cli = Cli::parse();
+
+    // Reference of the configuration, also usable as a starting .env file
+    if cli.print_env {
+        config::print_env();
+        return;
+    }
+
     // Get configuration
-    let config = Config::new();
+    let mut config = Config::new();
+
+    // Warnings and other tracing events go to stdout next to the regular logs; spans are also
+    // exported when an OTLP endpoint is configured
+    telemetry::init_tracing(config.otlp_endpoint.as_deref());
+
+    utils::set_account_key_logging(config.log_account_keys);
+
+    transaction::set_curve_address_cache_size(config.curve_address_cache_size);
+
+    // Buy/sell instruction data layouts, for following a program change without recompiling
+    if let Some(path) = &config.instruction_layout_path {
+        match transaction::layout::load_instruction_layouts(path) {
+            Ok(()) => println!("Loaded instruction layouts from {}", path),
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        }
+    }
+
+    if let Some(path) = cli.dry_run_replay {
+        run_replay(config, &path).await;
+        return;
+    }
+
+    // Safe mode: no autonomous spending on mainnet until the operator acknowledged it
+    if config.mode == TradingMode::Normal && !config.real_trading_acknowledged && is_mainnet(&config.rpc_url).await {
+        println!("WARNING: ==================================================================");
+        println!("WARNING: REAL TRADING IS NOT ACKNOWLEDGED, SNIPES ARE DISABLED");
+        println!("WARNING: This bot spends the SOL of the configured wallet on mainnet on its own,");
+        println!("WARNING: with every qualifying token it sees. Review the configuration, fund the");
+        println!("WARNING: wallet only with what you are prepared to lose, then set");
+        println!("WARNING: I_UNDERSTAND_REAL_TRADING=true to enable snipes. Running as sell_only.");
+        println!("WARNING: ==================================================================");
+        config.mode = TradingMode::SellOnly;
+    }
+
+    let config = Arc::new(config);
+
     let client_result = ShredstreamClient::new(config.clone()).await;
-    let mut client = match client_result {
+    let client = match client_result {
         Ok(client) => client,
         Err(e) => {
             println!("Failed to create client: {:?}", e);
@@ -33,25 +252,18 @@ async fn main() {
         }
     };
 
-    let mut processor = TransactionProcessor::new(config.token_creator_pubkey);
-
-    // Get Redis configuration
-    let redis_url = env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
-    println!("Connecting to Redis: {}", redis_url);
-
-    // Get RPC and private key
-    let rpc_url = env::var("RPC_URL").unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string());
-    let private_key = match env::var("PRIVATE_KEY") {
-        Ok(key) => key,
-        Err(_) => {
-            println!("PRIVATE_KEY environment variable must be set");
-            return;
-        }
-    };
+    let mut processor = TransactionProcessor::new(config.clone());
 
     // Initialize Redis client
-    let redis_client_result = RedisClient::new(&redis_url).await;
+    println!("Connecting to Redis: {}", config.redis_url);
+    let redis_client_result = RedisClient::connect(&config.redis_url, config.redis_db)
+        .await
+        .map(|client| client.with_key_prefix(&config.redis_key_prefix));
     let redis_client = match redis_client_result {
+        Ok(client) if config.redis_latency_report_ms > 0 => {
+            println!("Redis connection successful, latency metrics enabled");
+            Arc::new(client.with_latency_metrics())
+        },
         Ok(client) => {
             println!("Redis connection successful");
             Arc::new(client)
@@ -62,81 +274,240 @@ async fn main() {
         }
     };
 
+    if config.redis_latency_report_ms > 0 {
+        redis_client.spawn_latency_report(config.redis_latency_report_ms);
+    }
+
+    // Keep the prices of open positions in line with the chain
+    if config.reprice_interval_ms > 0 {
+        spawn_repricing_task(
+            config.clone(),
+            redis_client.clone(),
+            processor.reserve_updates(),
+        );
+    }
+
     // Initialize AutoTrader
-    let auto_trader = AutoTrader::new(
-        redis_client.clone(),
-        rpc_url.clone(),
-        private_key.clone()
-    ).await;
-
-    // Read sniping price range from environment variables
-    let min_sol_str = env::var("MIN_SOL_PRICE").unwrap_or_else(|_| "0.5".to_string());
-    let max_sol_str = env::var("MAX_SOL_PRICE").unwrap_or_else(|_| "3.0".to_string());
-    let buy_sol_str = env::var("BUY_SOL_AMOUNT").unwrap_or_else(|_| "0.1".to_string());
-    let sell_delay_ms = env::var("SELL_DELAY_MS").unwrap_or_else(|_| "5000".to_string());
-
-    // Convert floating-point SOL values to integer lamports
-    let min_sol = (min_sol_str.parse::<f64>().unwrap_or(0.5) * 1_000_000_000.0) as u64;
-    let max_sol = (max_sol_str.parse::<f64>().unwrap_or(3.0) * 1_000_000_000.0) as u64;
-    let buy_sol = (buy_sol_str.parse::<f64>().unwrap_or(0.1) * 1_000_000_000.0) as u64;
-    let sell_delay = sell_delay_ms.parse::<u64>().unwrap_or(5000);
+    let mut auto_trader = AutoTrader::new(redis_client.clone(), config.clone()).await;
+
+    if cli.selftest {
+        println!("Running send path self-test...");
+        match auto_trader.self_test().await {
+            Ok(elapsed) => println!("Self-test transaction landed in {}ms", elapsed.as_millis()),
+            Err(e) => {
+                println!("Self-test failed: {}, not starting", e);
+                return;
+            }
+        }
+    }
+
+    match auto_trader.check_wallet_health().await {
+        Ok(health) => {
+            println!("Wallet: {:.4} SOL, {} token accounts with a balance",
+                     health.sol_balance_lamports as f64 / 1_000_000_000.0, health.token_accounts.len());
+            for warning in &health.warnings {
+                println!("WARNING: {}", warning);
+            }
+        }
+        Err(e) => println!("Wallet health check failed: {}", e),
+    }
+
+    auto_trader.set_feed_slot(processor.feed_slot());
+
+    if let Some(addr) = &config.health_bind_addr {
+        let sources = utils::health::HealthSources {
+            redis_client: redis_client.clone(),
+            feed_slot: processor.feed_slot(),
+            blockhash_cache: auto_trader.blockhash_cache(),
+            feed_stall_ms: config.health_feed_stall_ms,
+            blockhash_max_age_ms: config.health_blockhash_max_age_ms,
+            control: auto_trader.control(),
+        };
+        if let Err(e) = utils::health::spawn_health_server(addr, sources).await {
+            println!("Failed to start health endpoint on {}: {:?}", addr, e);
+        }
+    }
+    processor.set_trade_limits(auto_trader.trade_limits());
+    processor.set_position_inventory(auto_trader.inventory());
+
+    // Shared creator allowlist, maintained in Redis
+    if config.creator_allowlist_redis {
+        match redis_client.get_creator_allowlist().await {
+            Ok(creators) => {
+                let creators = processor::parse_creators(creators.iter().map(String::as_str));
+                processor.extend_creator_allowlist(creators);
+            }
+            Err(e) => {
+                println!("Failed to load the creator allowlist from Redis: {:?}", e);
+                processor.extend_creator_allowlist(Vec::new());
+            }
+        }
+    }
+
+    // Keep the USD denominated amounts in line with the SOL price
+    if config.uses_usd_amounts() {
+        utils::sol_price::spawn_sol_price_refresh(config.clone(), auto_trader.trade_limits().sol_usd_price()).await;
+    }
+
+    // Buy priority fee from the external fee API, when configured
+    utils::priority_fee::spawn_priority_fee_refresh(config.clone(), auto_trader.priority_fee());
+
+    // Pause snipes while the network is congested
+    if config.congestion_fee_threshold > 0 {
+        utils::congestion::spawn_congestion_monitor(config.clone(), auto_trader.congestion());
+    }
+    let snipe_tasks = auto_trader.snipe_tasks();
+    processor.set_snipe_tasks(snipe_tasks.clone());
+
+    // Optional durable journal of positions, trades and reserve snapshots
+    if let Some(path) = &config.sqlite_path {
+        #[cfg(feature = "sqlite")]
+        match utils::sqlite_store::SqliteStore::open(path) {
+            Ok(store) => auto_trader.set_position_journal(Arc::new(store)).await,
+            Err(e) => println!("Failed to open SQLite store {}: {}", path, e),
+        }
+        #[cfg(not(feature = "sqlite"))]
+        println!("SQLITE_PATH={} ignored, build with --features sqlite to enable the SQLite store", path);
+    }
+
+    // Forensic capture of the raw frames around parse errors and failed snipes
+    let frame_capture = (config.frame_capture_size > 0)
+        .then(|| FrameCapture::new(config.frame_capture_size, &config.frame_capture_dir));
+    if let Some(frame_capture) = &frame_capture {
+        auto_trader.set_frame_capture(frame_capture.clone());
+    }
+    spawn_control_signals(auto_trader.control(), frame_capture.clone());
+
+    // Feed gap detection across reconnects and restarts
+    let mut slot_gaps = (config.slot_history_size > 0)
+        .then(|| SlotGapTracker::new(redis_client.clone(), config.slot_history_size));
+
+    if let Some(url) = &config.pushgateway_url {
+        let sources = utils::metrics::MetricsSources {
+            redis_client: redis_client.clone(),
+            feed_slot: processor.feed_slot(),
+            inventory: auto_trader.inventory(),
+            slot_gaps: slot_gaps.as_ref().map(SlotGapTracker::stats),
+            landing: auto_trader.landing_stats(),
+            fees: auto_trader.fee_history(),
+        };
+        if let Err(e) = utils::metrics::spawn_pushgateway(url, &config.pushgateway_job, config.pushgateway_interval_ms, sources) {
+            println!("Failed to start metrics push: {}", e);
+        }
+    }
+
+    if config.summary_interval_ms > 0 {
+        spawn_health_log(
+            config.clone(),
+            redis_client.clone(),
+            processor.feed_slot(),
+            processor.metrics(),
+            auto_trader.stats(),
+            auto_trader.inventory(),
+            auto_trader.blockhash_cache(),
+        );
+    }
+
+    let slot_stats = auto_trader.slot_stats();
 
     // Create a mutex for the AutoTrader
     let auto_trader = Arc::new(Mutex::new(auto_trader));
 
-    // Set trader parameters and start
-    {
-        let mut trader = auto_trader.lock().await;
-        trader.set_price_range(min_sol, max_sol).await;
-        trader.set_buy_amount(buy_sol).await;
-        trader.set_sell_delay(sell_delay).await;
-        trader.start();
-    }
+    // Start the trader
+    auto_trader.lock().await.start();
 
     // Set the AutoTrader for the processor
     processor.set_auto_trader(Arc::clone(&auto_trader));
 
+    config.print_summary();
+
     println!("Starting to listen for Jito Shredstream data...");
-    println!("Will automatically snipe new tokens with a price between {} - {} SOL", min_sol_str, max_sol_str);
-    println!("Will invest {} SOL for each purchase", buy_sol_str);
-    println!("Will automatically sell after {}ms", sell_delay);
+    if config.mode == TradingMode::SellOnly {
+        println!("Sell-only mode: no new positions will be opened, existing positions will be sold");
+    }
+    if config.uses_usd_amounts() {
+        // The configured SOL values only apply until a SOL/USD price is known
+        let trade_limits = auto_trader.lock().await.trade_limits();
+        println!("USD amounts at the current SOL/USD price: snipe range {} - {} SOL, {} SOL per purchase",
+                 trade_limits.min_sol_price() as f64 / 1_000_000_000.0,
+                 trade_limits.max_sol_price() as f64 / 1_000_000_000.0,
+                 trade_limits.buy_sol_amount() as f64 / 1_000_000_000.0);
+    }
     println!("---------------------------");
 
-    // Main loop - continuously listen for Shredstream data
-    loop {
-        match client.subscribe_entries().await {
-            Ok(mut stream) => {
-                let process_result = async {
-                    while let Some(entry) = match stream.message().await {
-                        Ok(entry) => entry,
-                        Err(e) => {
-                            println!("Failed to get message: {:?}", e);
-                            return Ok(());
-                        }
-                    } {
-                        match deserialize_entries(&entry.entries) {
-                            Ok(entries) => {
-                                if let Err(e) = processor.process_entries(entries, entry.slot) {
-                                    println!("Failed to process entries: {:?}", e);
-                                }
-                            },
-                            Err(e) => {
-                                println!("Deserialization failed: {e}");
+    // Main loop - continuously listen for Shredstream data, resubscribing as needed
+    let stream = client.into_entry_stream();
+    let mut frames = if config.feed_channel_capacity > 0 {
+        stream.spawn_frame_reader(config.feed_channel_capacity)
+    } else {
+        stream.frames()
+    };
+    let feed = async {
+        let mut last_slot = 0;
+        while let Some((entry, subscription)) = frames.next().await {
+
+            // A slot's entries can span several frames, it is complete once a later slot arrives
+            if entry.slot > last_slot {
+                if last_slot > 0 {
+                    slot_stats.on_slot_complete(last_slot);
+                }
+                last_slot = entry.slot;
+            }
+
+            if let Some(frame_capture) = &frame_capture {
+                frame_capture.push(entry.slot, &entry.entries);
+            }
+
+            // Root span of everything done for this batch, including the snipes it triggers
+            let batch_span = info_span!("entry_batch", slot = entry.slot);
+
+            let decoded = batch_span.in_scope(|| info_span!("deserialize").in_scope(|| {
+                if config.resilient_entry_decoding {
+                    deserialize_entries_resilient(&entry.entries).map(|partial| {
+                        if partial.truncated {
+                            println!("Truncated entry frame at slot {}, kept {} entries", entry.slot, partial.entries.len());
+                            if let Some(frame_capture) = &frame_capture {
+                                frame_capture.dump("truncated frame");
                             }
                         }
-                    }
-                    Ok::<(), ()>(())
-                }.await;
+                        partial.entries
+                    })
+                } else {
+                    deserialize_entries(&entry.entries)
+                }
+            }));
 
-                if let Err(_) = process_result {
-                    println!("Fatal error occurred in message processing loop");
+            match decoded {
+                Ok(entries) => {
+                    if let Err(e) = processor.process_entries_async(entries, entry.slot).instrument(batch_span).await {
+                        println!("Failed to process entries: {:?}", e);
+                        if let Some(frame_capture) = &frame_capture {
+                            frame_capture.dump("processing error");
+                        }
+                    }
+                    if let Some(slot_gaps) = &mut slot_gaps {
+                        slot_gaps.on_batch(entry.slot, subscription).await;
+                    }
+                },
+                Err(e) => {
+                    println!("Deserialization failed: {e}");
+                    if let Some(frame_capture) = &frame_capture {
+                        frame_capture.dump("deserialization error");
+                    }
                 }
             }
-            Err(e) => {
-                println!("Connection lost: {e}");
-                println!("Reconnecting in 5 seconds...");
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-            }
+        }
+    };
+
+    // Stop reading the feed on Ctrl-C, but let in-flight snipes finish first
+    tokio::select! {
+        _ = feed => {}
+        _ = tokio::signal::ctrl_c() => {
+            println!("Shutdown requested, waiting for in-flight snipes...");
+            let completed = snipe_tasks.wait(Duration::from_millis(SHUTDOWN_SNIPE_TIMEOUT_MS)).await;
+            println!("{} in-flight snipes completed, exiting", completed);
         }
     }
+
+    telemetry::shutdown_tracing();
 }
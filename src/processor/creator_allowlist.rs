@@ -0,0 +1,66 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use solana_sdk::pubkey::Pubkey;
+
+// How long a mint of an allowed creator stays eligible after its create
+const ALLOWED_MINT_RETENTION: Duration = Duration::from_secs(3600);
+
+// Creator allowlist: only mints created by a listed creator are sniped. Mints whose create was
+// not seen have no known creator and are denied as well.
+pub struct CreatorAllowlist {
+    creators: HashSet<Pubkey>,
+    allowed_mints: HashMap<String, Instant>, // Mints of allowed creators -> created at
+}
+
+impl CreatorAllowlist {
+    pub fn new(creators: impl IntoIterator<Item = Pubkey>) -> Self {
+        Self {
+            creators: creators.into_iter().collect(),
+            allowed_mints: HashMap::new(),
+        }
+    }
+
+    // Add creators, e.g. the ones loaded from Redis after startup
+    pub fn extend(&mut self, creators: impl IntoIterator<Item = Pubkey>) {
+        self.creators.extend(creators);
+    }
+
+    pub fn creator_count(&self) -> usize {
+        self.creators.len()
+    }
+
+    pub fn on_token_created(&mut self, mint: &str, creator: Pubkey) {
+        let now = Instant::now();
+        self.allowed_mints.retain(|_, created_at| now.duration_since(*created_at) < ALLOWED_MINT_RETENTION);
+        if self.creators.contains(&creator) {
+            self.allowed_mints.insert(mint.to_string(), now);
+        }
+    }
+
+    pub fn is_allowed(&self, mint: &str) -> bool {
+        self.allowed_mints.contains_key(mint)
+    }
+}
+
+// Parse creator pubkeys, skipping invalid entries with a warning
+pub fn parse_creators<'a>(entries: impl IntoIterator<Item = &'a str>) -> Vec<Pubkey> {
+    entries
+        .into_iter()
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty() && !entry.starts_with('#'))
+        .filter_map(|entry| match Pubkey::from_str(entry) {
+            Ok(creator) => Some(creator),
+            Err(e) => {
+                println!("WARNING: Ignoring invalid creator {} in the allowlist: {}", entry, e);
+                None
+            }
+        })
+        .collect()
+}
+
+// Creators listed in a file, one pubkey per line, # starts a comment line
+pub fn load_creator_file(path: &str) -> Result<Vec<Pubkey>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read creator allowlist {}: {}", path, e))?;
+    Ok(parse_creators(contents.lines()))
+}
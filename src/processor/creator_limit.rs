@@ -0,0 +1,63 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use solana_sdk::pubkey::Pubkey;
+
+// How long the creator of a mint is remembered after its create
+const MINT_CREATOR_RETENTION: Duration = Duration::from_secs(3600);
+
+// Per-creator snipe cap: at most max_snipes distinct mints of the same creator are sniped within
+// the window, bounding the exposure to a single deployer's launch spam
+pub struct CreatorLimiter {
+    max_snipes: usize,
+    window: Duration,
+    creators: HashMap<String, (Pubkey, Instant)>,          // Mint -> (creator, created at)
+    snipes: HashMap<Pubkey, VecDeque<(Instant, String)>>, // Creator -> sniped mints within the window
+}
+
+impl CreatorLimiter {
+    pub fn new(max_snipes: usize, window_ms: u64) -> Self {
+        Self {
+            max_snipes,
+            window: Duration::from_millis(window_ms),
+            creators: HashMap::new(),
+            snipes: HashMap::new(),
+        }
+    }
+
+    pub fn on_token_created(&mut self, mint: &str, creator: Pubkey) {
+        let now = Instant::now();
+        self.creators.retain(|_, (_, created_at)| now.duration_since(*created_at) < MINT_CREATOR_RETENTION);
+        self.creators.insert(mint.to_string(), (creator, now));
+    }
+
+    // Whether a snipe of the mint is allowed, counting it against its creator if so. Mints whose
+    // create was not seen are not limited; repeated snipes of a counted mint are not counted again.
+    pub fn allow_snipe(&mut self, mint: &str) -> bool {
+        let creator = match self.creators.get(mint) {
+            Some((creator, _)) => *creator,
+            None => return true,
+        };
+
+        let now = Instant::now();
+        let window = self.window;
+        self.snipes.retain(|_, sniped| {
+            while sniped.front().is_some_and(|(sniped_at, _)| now.duration_since(*sniped_at) >= window) {
+                sniped.pop_front();
+            }
+            !sniped.is_empty()
+        });
+
+        let sniped = self.snipes.entry(creator).or_default();
+        if sniped.iter().any(|(_, sniped_mint)| sniped_mint == mint) {
+            return true;
+        }
+        if sniped.len() >= self.max_snipes {
+            println!("Skipping {}: creator {} already sniped {} times in the last {}ms",
+                     mint, creator, sniped.len(), window.as_millis());
+            return false;
+        }
+
+        sniped.push_back((now, mint.to_string()));
+        true
+    }
+}
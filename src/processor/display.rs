@@ -0,0 +1,64 @@
+// Human-readable amounts for the logs. Raw amounts are formatted exactly from their integer
+// value and decimals, so large values are not rounded and small ones do not collapse to 0.000000.
+
+pub const SOL_DECIMALS: u8 = 9;
+
+// Significant digits shown for prices and amounts below 1
+const SIGNIFICANT_DIGITS: usize = 4;
+
+// Raw token or lamport amount with its decimals, e.g. 1234567890123 @ 6 -> "1,234,567.890123"
+pub fn format_amount(raw: u64, decimals: u8) -> String {
+    let scale = 10u128.pow(decimals as u32);
+    let whole = raw as u128 / scale;
+    let fraction = raw as u128 % scale;
+
+    let whole = group_thousands(whole);
+    if fraction == 0 {
+        return whole;
+    }
+
+    let fraction = format!("{:0width$}", fraction, width = decimals as usize);
+    let fraction = fraction.trim_end_matches('0');
+    if whole != "0" {
+        return format!("{}.{}", whole, fraction);
+    }
+
+    // Below 1, keep the leading zeros and a few significant digits
+    let leading_zeros = fraction.len() - fraction.trim_start_matches('0').len();
+    let end = fraction.len().min(leading_zeros + SIGNIFICANT_DIGITS);
+    format!("0.{}", &fraction[..end])
+}
+
+// Signed raw amount, e.g. a PnL in lamports: "-" followed by the formatted magnitude if negative
+pub fn format_signed_amount(raw: i128, decimals: u8) -> String {
+    let magnitude = format_amount(u64::try_from(raw.unsigned_abs()).unwrap_or(u64::MAX), decimals);
+    if raw < 0 {
+        format!("-{}", magnitude)
+    } else {
+        magnitude
+    }
+}
+
+// Price in SOL per token, with enough decimals to show its significant digits
+pub fn format_price(price: f64) -> String {
+    if !price.is_finite() || price <= 0.0 {
+        return format!("{}", price);
+    }
+    if price >= 1.0 {
+        return format!("{:.4}", price);
+    }
+    let leading_zeros = (-price.log10()).floor() as usize;
+    format!("{:.*}", leading_zeros + SIGNIFICANT_DIGITS, price)
+}
+
+fn group_thousands(value: u128) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
@@ -1,33 +1,392 @@
 use chrono::Local;
-use solana_sdk::{message::VersionedMessage, pubkey::Pubkey, transaction::VersionedTransaction};
+use tracing::{debug, info_span, warn, Instrument};
+use solana_sdk::{commitment_config::CommitmentConfig, instruction::CompiledInstruction, message::VersionedMessage, pubkey::Pubkey, signature::Signature, transaction::VersionedTransaction};
 use solana_entry::entry::Entry;
-use crate::instruction::parse_instruction_data;
+use crate::instruction::{estimate_instruction_type, parse_instruction_data, parse_instruction_from_log, parse_sell_instruction, BuyInstruction, CreateEventInstruction, PumpInstruction};
 use std::error::Error;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroUsize;
+use lru::LruCache;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use crate::utils::auto_trader::AutoTrader;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::{Mutex, RwLock};
+use crate::utils::auto_trader::{AutoTrader, BuyConfirmer, PipelineStart, PositionInventory, SkipReason, SnipeDecision, SnipeOutcome, SnipeTarget, SnipeTasks, UnconfirmedBuy};
+use crate::config::{Config, TradingMode};
+use crate::error::SniperError;
+use crate::utils::{account_key_logging_enabled, log_account_keys, unix_time_ms};
+use crate::utils::sol_price::TradeLimits;
+use crate::transaction::{calibrate_compute_units, fetch_transaction_logs, simulate_initial_reserves, wait_for_commitment, ConfirmationStatus, PUMP_PROGRAM_ID};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+
+mod creator_allowlist;
+mod creator_limit;
+mod display;
+mod paper;
+mod pending;
+mod qualifying_buys;
+mod sell_exit;
+mod shadow;
+mod strategy;
+mod symbol_filter;
+use creator_allowlist::{load_creator_file, CreatorAllowlist};
+use creator_limit::CreatorLimiter;
+use display::{format_amount, format_price, SOL_DECIMALS};
+use paper::SLOT_DURATION_MS;
+use pending::{PendingCheck, SnipeWaits};
+use qualifying_buys::QualifyingBuyTracker;
+use sell_exit::LargeSellExit;
+use shadow::ShadowRunner;
+use strategy::{BuyObservation, PriceRangeStrategy, Strategy};
+use symbol_filter::SymbolFilter;
+pub use creator_allowlist::parse_creators;
+pub use symbol_filter::SymbolMatch;
+
+// Common view over v0 and legacy messages so both are handled by the same code
+trait MessageAdapter {
+    fn account_keys(&self) -> &[Pubkey];
+    fn instructions(&self) -> impl Iterator<Item = &CompiledInstruction>;
+    fn num_required_signatures(&self) -> usize;
+
+    // Accounts that signed the transaction, the fee payer first; always static keys
+    fn signers(&self) -> &[Pubkey] {
+        let account_keys = self.account_keys();
+        &account_keys[..self.num_required_signatures().min(account_keys.len())]
+    }
+}
+
+impl MessageAdapter for solana_sdk::message::v0::Message {
+    fn account_keys(&self) -> &[Pubkey] {
+        &self.account_keys
+    }
+
+    fn instructions(&self) -> impl Iterator<Item = &CompiledInstruction> {
+        self.instructions.iter()
+    }
+
+    fn num_required_signatures(&self) -> usize {
+        self.header.num_required_signatures as usize
+    }
+}
+
+impl MessageAdapter for solana_sdk::message::Message {
+    fn account_keys(&self) -> &[Pubkey] {
+        &self.account_keys
+    }
+
+    fn instructions(&self) -> impl Iterator<Item = &CompiledInstruction> {
+        self.instructions.iter()
+    }
+
+    fn num_required_signatures(&self) -> usize {
+        self.header.num_required_signatures as usize
+    }
+}
+
+// Position of the mint account in the accounts of a pump buy instruction
+const BUY_MINT_ACCOUNT_INDEX: usize = 2;
+// Position of the user account in the accounts of a pump buy instruction
+const BUY_USER_ACCOUNT_INDEX: usize = 6;
+// Position of the mint account in the accounts of a pump sell instruction
+const SELL_MINT_ACCOUNT_INDEX: usize = 2;
+
+// The wallet buying in a buy instruction. With several signers it need not be the fee payer
+// (e.g. a relayer paying fees for the buyer), so the instruction's user account is used when it
+// is one of the signers.
+fn buyer_of<M: MessageAdapter>(message: &M, instruction: &CompiledInstruction) -> Pubkey {
+    let signers = message.signers();
+    instruction
+        .accounts
+        .get(BUY_USER_ACCOUNT_INDEX)
+        .and_then(|index| signers.get(*index as usize))
+        .or_else(|| signers.first())
+        .copied()
+        .unwrap_or_default()
+}
+
+// Oldest tracked mints checked for one without a position when MAX_TRACKED_MINTS is reached
+const MAX_TRACKED_EVICTION_SCAN: usize = 64;
+
+// Initial virtual reserve values - adjusted based on transaction records for more accurate values
+const INITIAL_VIRTUAL_SOL_RESERVES: u64 = 30_000_000_000;            // 30 SOL (lamports)
+const INITIAL_VIRTUAL_TOKEN_RESERVES: u64 = 1_073_000_000_000_000;   // Approximately 1.073 billion tokens (6 decimal precision)
 
 // Used to store virtual reserve information for tokens
-struct TokenReserves {
-    virtual_sol_reserves: u64,    // Virtual SOL reserves
-    virtual_token_reserves: u64,  // Virtual token reserves
+#[derive(Clone, Copy, Debug)]
+pub struct TokenReserves {
+    pub virtual_sol_reserves: u64,    // Virtual SOL reserves
+    pub virtual_token_reserves: u64,  // Virtual token reserves
+}
+
+impl TokenReserves {
+    // Price in SOL per whole token of a mint with the given decimals
+    pub fn price(&self, token_decimals: u8) -> f64 {
+        let virtual_sol = self.virtual_sol_reserves as f64 / 1_000_000_000.0;
+        let virtual_token = self.virtual_token_reserves as f64 / 10f64.powi(token_decimals as i32);
+        virtual_sol / virtual_token
+    }
+}
+
+// Reserve corrections produced by background tasks, applied by the processor before each batch
+#[derive(Clone, Default)]
+pub struct ReserveUpdates(Arc<std::sync::Mutex<HashMap<String, TokenReserves>>>);
+
+impl ReserveUpdates {
+    // Queue a correction, replacing any pending correction for the same mint
+    pub fn push(&self, mint: String, reserves: TokenReserves) {
+        if let Ok(mut pending) = self.0.lock() {
+            pending.insert(mint, reserves);
+        }
+    }
+
+    fn drain(&self) -> Vec<(String, TokenReserves)> {
+        match self.0.lock() {
+            Ok(mut pending) => pending.drain().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+// Window of the per-token buy volume
+const BUY_VOLUME_WINDOW: Duration = Duration::from_secs(60);
+
+// Latest slot seen on the feed, a current-slot source for slot-based logic without RPC calls
+#[derive(Clone, Default)]
+pub struct FeedSlot {
+    slot: Arc<AtomicU64>,
+    updated_at_ms: Arc<AtomicU64>, // Unix time of the last processed batch, 0 before the first
+}
+
+impl FeedSlot {
+    // Current slot, None until the first batch has been processed
+    pub fn get(&self) -> Option<u64> {
+        match self.slot.load(Ordering::Relaxed) {
+            0 => None,
+            slot => Some(slot),
+        }
+    }
+
+    // Time since the feed last delivered a batch, None until the first batch
+    pub fn since_last_batch(&self) -> Option<Duration> {
+        match self.updated_at_ms.load(Ordering::Relaxed) {
+            0 => None,
+            updated_at_ms => Some(Duration::from_millis(unix_time_ms().saturating_sub(updated_at_ms))),
+        }
+    }
+
+    // Frames may arrive out of order, the slot never moves backwards
+    fn advance(&self, slot: u64) {
+        self.slot.fetch_max(slot, Ordering::Relaxed);
+        self.updated_at_ms.store(unix_time_ms(), Ordering::Relaxed);
+    }
+}
+
+// Processing counters, readable from other tasks
+#[derive(Clone, Default)]
+pub struct ProcessorMetrics {
+    entries: Arc<AtomicU64>,
+    creates: Arc<AtomicU64>,
+    snipe_triggers: Arc<AtomicU64>,
+}
+
+impl ProcessorMetrics {
+    // Entries processed since startup
+    pub fn entries_processed(&self) -> u64 {
+        self.entries.load(Ordering::Relaxed)
+    }
+
+    // Token creates seen since startup
+    pub fn creates_seen(&self) -> u64 {
+        self.creates.load(Ordering::Relaxed)
+    }
+
+    // Buys that passed the snipe filters since startup
+    pub fn snipe_triggers(&self) -> u64 {
+        self.snipe_triggers.load(Ordering::Relaxed)
+    }
+}
+
+// Shredstream entries carry no execution status, so failed transactions cannot be identified
+// directly; this catches transactions without signatures or with an all-zero first signature
+// (simulation-only entries), which must not update reserves or trigger snipes
+fn is_unusable_transaction(transaction: &VersionedTransaction) -> bool {
+    match transaction.signatures.first() {
+        Some(signature) => *signature == Signature::default(),
+        None => true,
+    }
+}
+
+// Confirm sent buys (CONFIRM_BUYS) without the trader lock, taking it again only to record each
+// position, so a slow confirmation does not stall other snipes, sells and slot summaries
+async fn confirm_sent_buys(trader: &Mutex<AutoTrader>, confirmer: BuyConfirmer, sent: Vec<UnconfirmedBuy>) {
+    let confirmer = &confirmer;
+    futures::future::join_all(sent.into_iter().map(|buy| async move {
+        let confirmation = info_span!("confirmation", signature = %buy.signature);
+        let token_amount = confirmer.confirm(&buy).instrument(confirmation).await;
+        if let Err(e) = trader.lock().await.record_confirmed_buy(buy, token_amount).await {
+            println!("Snipe failed: {:?}", e);
+        }
+    })).await;
+}
+
+// A buy event that may trigger a snipe once the batch is complete
+struct PendingSnipe {
+    target: SnipeTarget,
+    sol_amount: u64,
+    detected_at: std::time::Instant,
+    waits: SnipeWaits,
 }
 
 pub struct TransactionProcessor {
+    config: Arc<Config>,
+    // Used to check create signatures against the commitment gate
+    rpc_client: Arc<RpcClient>,
+    // Commitment gates of recent creates, awaited by the snipes of their mint
+    commitment_gates: HashMap<String, PendingCheck<bool>>,
+    // Initial reserve simulations of recent creates, awaited by the snipes of their mint
+    reserve_simulations: HashMap<String, PendingCheck<Option<TokenReserves>>>,
+    // Creates whose instruction data could not be parsed: mint -> fee payer of the create and its
+    // event, read from the logs of the confirmed transaction
+    create_log_fallbacks: HashMap<Pubkey, (Pubkey, PendingCheck<Option<CreateEventInstruction>>)>,
     token_creator_pubkey: Pubkey,
-    // Use HashMap to track virtual reserve states for various tokens
-    token_reserves: HashMap<String, TokenReserves>,
+    // Track virtual reserve states for various tokens, bounded by evicting the least recently traded
+    token_reserves: LruCache<String, TokenReserves>,
+    // Timestamped buy sizes of the last minute per token, bounded like token_reserves
+    buy_volumes: LruCache<String, VecDeque<(Instant, u64)>>,
+    // Creators of mints whose first buy by anyone else triggers the snipe, with IGNORE_DEV_BUY and
+    // no qualifying buy threshold; bounded like token_reserves
+    awaiting_third_party_buy: LruCache<String, Pubkey>,
+    // Corrections to token_reserves from background tasks
+    reserve_updates: ReserveUpdates,
+    // Whether buy compute units still need to be measured on the next create (cleared once run)
+    compute_unit_calibration_pending: bool,
     // Auto trader
     auto_trader: Option<Arc<Mutex<AutoTrader>>>,
+    // Record hypothetical trades only, never snipe
+    paper_trading: bool,
+    // Strategies evaluated with hypothetical trades on the observed events
+    shadow: Option<ShadowRunner>,
+    // Slot of the last processed batch
+    last_slot: u64,
+    // Latest slot seen on the feed, shared with the auto trader
+    feed_slot: FeedSlot,
+    metrics: ProcessorMetrics,
+    // Live snipe range, shared with the trader
+    trade_limits: TradeLimits,
+    // Spawned snipe tasks, awaited on shutdown
+    snipe_tasks: SnipeTasks,
+    // Distinct qualifying buyers per mint, None when a single qualifying buy triggers a snipe
+    qualifying_buys: Option<QualifyingBuyTracker>,
+    // Skips mints reusing a recently seen symbol, None when disabled
+    symbol_filter: Option<SymbolFilter>,
+    // Caps the snipes of mints of the same creator, None when unlimited
+    creator_limiter: Option<CreatorLimiter>,
+    // Only creators on the allowlist are sniped, None when every creator is
+    creator_allowlist: Option<CreatorAllowlist>,
+    // Qualifying buys of the current batch, sniped together when buy batching is enabled
+    pending_snipes: Vec<PendingSnipe>,
+    // Open positions of the trader, never evicted by the MAX_TRACKED_MINTS cap
+    positions: Option<Arc<RwLock<PositionInventory>>>,
+    // Sells held positions on large observed sells, None when disabled
+    large_sell_exit: Option<LargeSellExit>,
 }
 
 impl TransactionProcessor {
-    pub fn new(token_creator_pubkey: Pubkey) -> Self {
+    pub fn new(config: Arc<Config>) -> Self {
+        if config.simulate_initial_reserves {
+            println!("Initial reserve simulation enabled");
+        }
+        if config.calibrate_compute_units {
+            println!("Compute unit calibration will run on the next token creation");
+        }
+
+        if let Some(commitment) = config.create_commitment {
+            println!("Creates must reach {:?} commitment before being acted on", commitment.commitment);
+        }
+
+        // Shadow mode compares the configured strategy with a candidate on the live feed
+        let shadow = if config.shadow_mode {
+            println!("Shadow mode enabled, comparing the primary and candidate strategies");
+            let strategies: Vec<Box<dyn Strategy>> = vec![
+                Box::new(PriceRangeStrategy::primary(&config)),
+                Box::new(PriceRangeStrategy::candidate(&config)),
+            ];
+            Some(ShadowRunner::new(strategies, config.shadow_report_interval_ms / SLOT_DURATION_MS, config.token_decimals))
+        } else {
+            None
+        };
+
+        let symbol_filter = if config.symbol_dedup_window_ms > 0 {
+            println!("Skipping mints reusing a symbol seen in the last {}ms ({:?} match)", config.symbol_dedup_window_ms, config.symbol_dedup_match);
+            Some(SymbolFilter::new(config.symbol_dedup_window_ms, config.symbol_dedup_match))
+        } else {
+            None
+        };
+
+        let creator_limiter = if config.max_snipes_per_creator > 0 {
+            println!("Sniping at most {} mints per creator within {}ms", config.max_snipes_per_creator, config.creator_snipe_window_ms);
+            Some(CreatorLimiter::new(config.max_snipes_per_creator, config.creator_snipe_window_ms))
+        } else {
+            None
+        };
+
+        // Allowlisted creators from the environment and the file; Redis entries are added later
+        let creator_allowlist = if !config.creator_allowlist.is_empty() || config.creator_allowlist_file.is_some() {
+            let mut creators = parse_creators(config.creator_allowlist.iter().map(String::as_str));
+            if let Some(path) = &config.creator_allowlist_file {
+                match load_creator_file(path) {
+                    Ok(listed) => creators.extend(listed),
+                    Err(e) => println!("WARNING: {}", e),
+                }
+            }
+            let allowlist = CreatorAllowlist::new(creators);
+            println!("Sniping only mints of {} allowlisted creators", allowlist.creator_count());
+            Some(allowlist)
+        } else {
+            None
+        };
+
+        let large_sell_exit = if config.large_sell_exit_sol > 0 {
+            println!("Exiting positions on observed sells of at least {} SOL", format_amount(config.large_sell_exit_sol, SOL_DECIMALS));
+            Some(LargeSellExit::new(config.large_sell_exit_sol))
+        } else {
+            None
+        };
+
+        let qualifying_buys = if config.min_qualifying_buys > 1 {
+            println!("Sniping after {} distinct qualifying buyers within {}ms", config.min_qualifying_buys, config.qualifying_buy_window_ms);
+            Some(QualifyingBuyTracker::new(config.min_qualifying_buys, config.qualifying_buy_window_ms))
+        } else {
+            None
+        };
+
         Self { 
-            token_creator_pubkey,
-            token_reserves: HashMap::new(),
+            rpc_client: Arc::new(RpcClient::new(config.rpc_url.clone())),
+            commitment_gates: HashMap::new(),
+            reserve_simulations: HashMap::new(),
+            create_log_fallbacks: HashMap::new(),
+            token_creator_pubkey: config.token_creator_pubkey,
+            token_reserves: LruCache::new(NonZeroUsize::new(config.token_reserves_cache_size).unwrap_or(NonZeroUsize::MIN)),
+            buy_volumes: LruCache::new(NonZeroUsize::new(config.token_reserves_cache_size).unwrap_or(NonZeroUsize::MIN)),
+            awaiting_third_party_buy: LruCache::new(NonZeroUsize::new(config.token_reserves_cache_size).unwrap_or(NonZeroUsize::MIN)),
+            reserve_updates: ReserveUpdates::default(),
+            compute_unit_calibration_pending: config.calibrate_compute_units,
             auto_trader: None,
+            paper_trading: false,
+            shadow,
+            last_slot: 0,
+            feed_slot: FeedSlot::default(),
+            metrics: ProcessorMetrics::default(),
+            trade_limits: TradeLimits::new(config.clone()),
+            snipe_tasks: SnipeTasks::default(),
+            pending_snipes: Vec::new(),
+            positions: None,
+            large_sell_exit,
+            symbol_filter,
+            creator_limiter,
+            creator_allowlist,
+            qualifying_buys,
+            config,
         }
     }
     
@@ -37,321 +396,1126 @@ impl TransactionProcessor {
         println!("Auto trader has been set up");
     }
 
-    pub fn process_entries(&mut self, entries: Vec<Entry>, slot: u64) -> Result<(), Box<dyn Error>> {
+    // Record hypothetical trades of the configured strategy instead of sniping
+    pub fn enable_paper_trading(&mut self) {
+        self.paper_trading = true;
+        if self.shadow.is_none() {
+            let strategies: Vec<Box<dyn Strategy>> = vec![Box::new(PriceRangeStrategy::primary(&self.config))];
+            self.shadow = Some(ShadowRunner::new(strategies, 0, self.config.token_decimals));
+        }
+        println!("Paper trading enabled, no transactions will be sent");
+    }
+
+    // Close the remaining hypothetical positions and print the totals per strategy
+    pub fn print_paper_report(&mut self) {
+        if let Some(shadow) = &mut self.shadow {
+            shadow.finish(self.last_slot, &self.token_reserves);
+        }
+    }
+
+    // Trader to snipe with, none when not set, when running sell-only or when paper trading
+    fn snipe_trader(&self) -> Option<&Arc<Mutex<AutoTrader>>> {
+        if self.paper_trading {
+            return None;
+        }
+        match self.config.mode {
+            TradingMode::SellOnly => None,
+            TradingMode::Normal => self.auto_trader.as_ref(),
+        }
+    }
+
+    // Check in the background that a create transaction reaches the configured commitment. Only
+    // the snipes of its mint wait for the result, the feed keeps being processed meanwhile.
+    fn spawn_commitment_gate(&mut self, mint: &str, signature: Signature) {
+        let commitment = match self.config.create_commitment {
+            Some(commitment) => commitment,
+            None => return,
+        };
+        let rpc_client = Arc::clone(&self.rpc_client);
+        let timeout_ms = self.config.create_commitment_timeout_ms;
+
+        let gate = PendingCheck::spawn(async move {
+            match wait_for_commitment(&rpc_client, &signature, commitment, timeout_ms).await {
+                ConfirmationStatus::Confirmed => true,
+                status => {
+                    println!("Discarding create {}: {:?} at {:?} commitment", signature, status, commitment.commitment);
+                    false
+                }
+            }
+        });
+        self.commitment_gates.insert(mint.to_string(), gate);
+    }
+
+    // Drop finished commitment gates; mints of creates that did not land are no longer tracked
+    fn settle_commitment_gates(&mut self) {
+        let (token_reserves, buy_volumes) = (&mut self.token_reserves, &mut self.buy_volumes);
+        self.commitment_gates.retain(|mint, gate| {
+            if !gate.is_done() {
+                return true;
+            }
+            if gate.result() != Some(true) {
+                token_reserves.pop(mint);
+                buy_volumes.pop(mint);
+            }
+            false
+        });
+    }
+
+    // Read the event of a create whose instruction data could not be parsed from the program logs
+    // of its transaction, once confirmed. The create is tracked when the event is found.
+    fn spawn_create_log_fallback(&mut self, mint: Pubkey, fee_payer: Pubkey, signature: Signature) {
+        if self.create_log_fallbacks.contains_key(&mint) {
+            return;
+        }
+        let rpc_client = Arc::clone(&self.rpc_client);
+        let timeout_ms = self.config.create_commitment_timeout_ms;
+
+        let event = PendingCheck::spawn(async move {
+            let status = wait_for_commitment(&rpc_client, &signature, CommitmentConfig::confirmed(), timeout_ms).await;
+            if !matches!(status, ConfirmationStatus::Confirmed) {
+                return None;
+            }
+            let logs = match fetch_transaction_logs(&rpc_client, &signature).await {
+                Ok(logs) => logs,
+                Err(e) => {
+                    println!("Failed to read the logs of create {}: {}", signature, e);
+                    return None;
+                }
+            };
+            logs.iter().find_map(|line| match parse_instruction_from_log(line) {
+                Some(PumpInstruction::CreateEvent(event)) if event.mint == mint => Some(event.into()),
+                Some(PumpInstruction::Create(event)) => Some(event),
+                _ => None,
+            })
+        });
+        self.create_log_fallbacks.insert(mint, (fee_payer, event));
+    }
+
+    // Track the creates whose event was found in their logs
+    fn settle_create_log_fallbacks(&mut self) {
+        let finished: Vec<Pubkey> = self.create_log_fallbacks
+            .iter()
+            .filter(|(_, (_, event))| event.is_done())
+            .map(|(mint, _)| *mint)
+            .collect();
+        for mint in finished {
+            if let Some((fee_payer, event)) = self.create_log_fallbacks.remove(&mint) {
+                match event.result().flatten() {
+                    Some(event) => {
+                        println!("\nCreate of {} parsed from its program logs", mint);
+                        self.handle_create(mint, fee_payer, event);
+                    }
+                    None => println!("No create event in the logs of {}", mint),
+                }
+            }
+        }
+    }
+
+    // Checks the snipes of a mint wait for, cloned into their task
+    fn snipe_waits(&self, mint: &str) -> SnipeWaits {
+        SnipeWaits {
+            gate: self.commitment_gates.get(mint).cloned(),
+            simulation: self.reserve_simulations.get(mint).cloned(),
+            simulation_wait: Duration::from_millis(self.config.reserve_simulation_wait_ms),
+            token_decimals: self.config.token_decimals,
+        }
+    }
+
+    // Register spawned snipes with the trader's task list
+    pub fn set_snipe_tasks(&mut self, snipe_tasks: SnipeTasks) {
+        self.snipe_tasks = snipe_tasks;
+    }
+
+    // Handle for reading the latest slot seen on the feed
+    pub fn feed_slot(&self) -> FeedSlot {
+        self.feed_slot.clone()
+    }
+
+    pub fn metrics(&self) -> ProcessorMetrics {
+        self.metrics.clone()
+    }
+
+    // Record a buy and drop the ones older than the volume window
+    fn record_buy_volume(&mut self, mint: &str, sol_amount: u64) {
+        let now = Instant::now();
+        let buys = self.buy_volumes.get_or_insert_mut(mint.to_string(), VecDeque::new);
+        while buys.front().is_some_and(|(bought_at, _)| now.duration_since(*bought_at) >= BUY_VOLUME_WINDOW) {
+            buys.pop_front();
+        }
+        buys.push_back((now, sol_amount));
+    }
+
+    // Buy volume of a token over the last minute (lamports)
+    pub fn buy_volume_1m(&self, mint: &str) -> u64 {
+        let now = Instant::now();
+        self.buy_volumes.peek(mint).map_or(0, |buys| {
+            buys.iter()
+                .filter(|(bought_at, _)| now.duration_since(*bought_at) < BUY_VOLUME_WINDOW)
+                .map(|(_, sol_amount)| sol_amount)
+                .sum()
+        })
+    }
+
+    // Use the trader's limits, so both convert USD amounts at the same SOL/USD price
+    pub fn set_trade_limits(&mut self, trade_limits: TradeLimits) {
+        self.trade_limits = trade_limits;
+    }
+
+    // Add allowlisted creators, enabling the allowlist if it was not configured otherwise
+    pub fn extend_creator_allowlist(&mut self, creators: Vec<Pubkey>) {
+        match &mut self.creator_allowlist {
+            Some(allowlist) => allowlist.extend(creators),
+            None => self.creator_allowlist = Some(CreatorAllowlist::new(creators)),
+        }
+        if let Some(allowlist) = &self.creator_allowlist {
+            println!("Sniping only mints of {} allowlisted creators", allowlist.creator_count());
+        }
+    }
+
+    // Positions the MAX_TRACKED_MINTS cap must keep tracking
+    pub fn set_position_inventory(&mut self, positions: Arc<RwLock<PositionInventory>>) {
+        self.positions = Some(positions);
+    }
+
+    // Make room for a new mint under MAX_TRACKED_MINTS by evicting the least recently traded mint
+    // without an open position. Only the MAX_TRACKED_EVICTION_SCAN oldest mints are considered, so
+    // the work per create stays bounded; returns false if none of them can be evicted, in which
+    // case the new mint is not tracked.
+    fn make_room_for_mint(&mut self) -> bool {
+        let max_tracked = self.config.max_tracked_mints;
+        if max_tracked == 0 || self.token_reserves.len() < max_tracked {
+            return true;
+        }
+
+        // A position cannot be told apart while the inventory is being written, keep everything then
+        let positions = match &self.positions {
+            Some(positions) => match positions.try_read() {
+                Ok(positions) => Some(positions),
+                Err(_) => return false,
+            },
+            None => None,
+        };
+        let evictable = self.token_reserves
+            .iter()
+            .rev()
+            .take(MAX_TRACKED_EVICTION_SCAN)
+            .map(|(mint, _)| mint)
+            .find(|mint| positions.as_ref().is_none_or(|positions| positions.get_position(mint).is_none()))
+            .cloned();
+        drop(positions);
+
+        match evictable {
+            Some(mint) => {
+                self.token_reserves.pop(&mint);
+                self.buy_volumes.pop(&mint);
+                debug!("Evicted reserves of {} to stay within MAX_TRACKED_MINTS", mint);
+                true
+            }
+            None => {
+                debug!("MAX_TRACKED_MINTS reached and no mint without a position to evict, not tracking the new mint");
+                false
+            }
+        }
+    }
+
+    // Handle for background tasks to push reserve corrections
+    pub fn reserve_updates(&self) -> ReserveUpdates {
+        self.reserve_updates.clone()
+    }
+
+    // Overwrite tracked reserves with the finished simulations and the corrections queued since
+    // the last batch
+    fn apply_reserve_updates(&mut self) {
+        let token_reserves = &mut self.token_reserves;
+        self.reserve_simulations.retain(|mint, simulation| {
+            if !simulation.is_done() {
+                return true;
+            }
+            if let (Some(Some(simulated)), Some(existing)) = (simulation.result(), token_reserves.peek_mut(mint)) {
+                *existing = simulated;
+            }
+            false
+        });
+
+        for (mint, reserves) in self.reserve_updates.drain() {
+            if let Some(existing) = self.token_reserves.peek_mut(&mint) {
+                *existing = reserves;
+            }
+        }
+    }
+
+    // Simulate a nominal buy against the new curve in the background. The first snipes of the
+    // mint wait for the result up to RESERVE_SIMULATION_WAIT_MS, the tracked reserves take it
+    // on the next batch.
+    fn spawn_reserve_simulation(&mut self, mint: Pubkey) {
+        if self.config.simulate_initial_reserves {
+            let config = self.config.clone();
+
+            let simulation = PendingCheck::spawn(async move {
+                match simulate_initial_reserves(&config.rpc_url, &config.private_key, mint).await {
+                    Ok((virtual_sol_reserves, virtual_token_reserves)) => {
+                        println!("Simulated initial reserves for {}: {} SOL / {} tokens",
+                                 mint,
+                                 format_amount(virtual_sol_reserves, SOL_DECIMALS),
+                                 format_amount(virtual_token_reserves, config.token_decimals));
+                        Some(TokenReserves {
+                            virtual_sol_reserves,
+                            virtual_token_reserves,
+                        })
+                    }
+                    Err(e) => {
+                        println!("Keeping default initial reserves for {}: {}", mint, e);
+                        None
+                    }
+                }
+            });
+            self.reserve_simulations.insert(mint.to_string(), simulation);
+        }
+    }
+
+    // Simulate the buy paths once against a fresh curve to learn their compute unit usage
+    fn spawn_compute_unit_calibration(&mut self, mint: Pubkey) {
+        if self.compute_unit_calibration_pending {
+            self.compute_unit_calibration_pending = false;
+            let config = self.config.clone();
+            tokio::spawn(async move {
+                if let Err(e) = calibrate_compute_units(&config.rpc_url, &config.private_key, mint, config.ata_mode).await {
+                    println!("Compute unit calibration failed, keeping default limits: {}", e);
+                }
+            });
+        }
+    }
+
+    // Evaluate the buys collected in this batch and snipe the eligible ones together
+    fn spawn_batch_snipe(&mut self, slot: u64) {
+        let pending = std::mem::take(&mut self.pending_snipes);
+        let trader = match self.snipe_trader() {
+            Some(auto_trader) => Arc::clone(auto_trader),
+            None => return,
+        };
+
+        let handle = tokio::spawn(async move {
+            // Creates may still be waiting for their commitment, settled before the trader is locked
+            let mut confirmed = Vec::with_capacity(pending.len());
+            let mut unconfirmed = Vec::new();
+            for mut pending in pending {
+                if pending.waits.settle(&mut pending.target).await {
+                    confirmed.push(pending);
+                } else {
+                    unconfirmed.push(pending);
+                }
+            }
+
+            // The lock is released before the sent buys are confirmed
+            let (sent, confirmer) = {
+                let lock_requested = Instant::now();
+                let trader = trader.lock().await;
+                let locked_at = Instant::now();
+                for pending in unconfirmed {
+                    trader.log_snipe_skip(&pending.target.mint, SkipReason::Unconfirmed, pending.detected_at, slot);
+                }
+
+                let mut targets = Vec::with_capacity(confirmed.len());
+                for pending in confirmed {
+                    match trader.evaluate_snipe(pending.sol_amount) {
+                        SnipeDecision::Snipe => {
+                            let start = PipelineStart { detected_at: pending.detected_at, lock_requested, locked_at };
+                            trader.record_pipeline_start(&pending.target.mint, start).await;
+                            targets.push(pending.target);
+                        }
+                        SnipeDecision::Skip(reason) => {
+                            if reason == SkipReason::RateLimited {
+                                println!("Snipe rate limit reached, skipping purchase of {}", pending.target.mint);
+                            }
+                            trader.log_snipe_skip(&pending.target.mint, reason, pending.detected_at, slot);
+                        }
+                    }
+                }
+
+                let mut sent = Vec::new();
+                if !targets.is_empty() {
+                    println!("Detected {} eligible purchases in slot {}, preparing to snipe", targets.len(), slot);
+                    sent = match trader.snipe_batch(targets, Some(slot)).await {
+                        Ok(sent) => sent,
+                        Err(e) => {
+                            println!("Batch snipe failed: {:?}", e);
+                            Vec::new()
+                        }
+                    };
+                }
+                (sent, trader.buy_confirmer())
+            };
+            confirm_sent_buys(&trader, confirmer, sent).await;
+        }.instrument(info_span!("snipe_decision", slot = slot, batch = true)));
+        self.snipe_tasks.track(handle);
+    }
+
+    // Process a batch of entries from one Shredstream message; snipes are spawned as separate tasks
+    #[tracing::instrument(name = "parse", skip_all, fields(slot = slot))]
+    pub async fn process_entries_async(&mut self, entries: Vec<Entry>, slot: u64) -> Result<(), Box<dyn Error>> {
+        self.apply_reserve_updates();
+        self.settle_commitment_gates();
+        self.settle_create_log_fallbacks();
+        self.feed_slot.advance(slot);
+        self.metrics.entries.fetch_add(entries.len() as u64, Ordering::Relaxed);
+
         for entry in entries {
             for tx_data in entry.transactions {
                 let transaction = tx_data;
+
+                if self.config.skip_failed_transactions && is_unusable_transaction(&transaction) {
+                    continue;
+                }
                 
                 match &transaction.message {
-                    VersionedMessage::V0(message) => self.process_message_v0(message, &transaction, slot)?,
-                    VersionedMessage::Legacy(message) => self.process_message_legacy(message, &transaction, slot)?,
+                    VersionedMessage::V0(message) => self.process_message_inner(message, &transaction, slot).await?,
+                    VersionedMessage::Legacy(message) => self.process_message_inner(message, &transaction, slot).await?,
                 }
             }
         }
+
+        if !self.pending_snipes.is_empty() {
+            self.spawn_batch_snipe(slot);
+        }
+
+        self.last_slot = slot;
+        if let Some(shadow) = &mut self.shadow {
+            shadow.on_batch_end(slot, &self.token_reserves);
+        }
         Ok(())
     }
 
-    fn process_message_v0(&mut self, message: &solana_sdk::message::v0::Message, transaction: &VersionedTransaction, slot: u64) -> Result<(), Box<dyn Error>> {
-        if message.account_keys.contains(&self.token_creator_pubkey) {
-            println!("\n{}", "-".repeat(80));
-            println!("[{}] Pumpfun internal token creation event:", Local::now().format("%Y-%m-%d %H:%M:%S%.3f"));
-            println!("Slot: {}", slot);
-            println!("Signatures: {}", transaction.signatures[0]);
-            
-            // Extract key account addresses
-            let mint_address = message.account_keys[1].to_string();
-            let bonding_curve = message.account_keys[2].to_string();
-            
-            println!("Mint: {}", mint_address);
-            println!("Bonding_Curve: {}", bonding_curve);
+    // Sell held positions on large sells of others. Sells of any transaction are checked, not
+    // only of creates; the SOL value is quoted from the tracked reserves where known, otherwise
+    // the seller's minimum SOL output is used as a lower bound.
+    fn check_large_sells<M: MessageAdapter>(&mut self, message: &M) {
+        let (exit, positions) = match (&mut self.large_sell_exit, &self.positions) {
+            (Some(exit), Some(positions)) => (exit, positions),
+            _ => return,
+        };
+        let trader = match &self.auto_trader {
+            Some(trader) => trader,
+            None => return,
+        };
 
-            // Check all instructions in the transaction
-            for instruction in &message.instructions {
-                let program_id = message.account_keys[instruction.program_id_index as usize].to_string();
-                
-                // If the instruction is for the target program
-                if program_id == self.token_creator_pubkey.to_string() || program_id == "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P" {
-                    // Attempt to parse the instruction
-                    if let Ok((instruction_type, create_event, buy_event)) = parse_instruction_data(&instruction.data) {
-                        match instruction_type.as_str() {
-                            "CreateEvent" => {
-                                if let Some(event) = create_event {
-                                    println!("Token_Metadata:");
-                                    println!("  Name: {}", event.name);
-                                    println!("  Symbol: {}", event.symbol);
-                                    println!("  URI: {}", event.uri);
-                                    println!("  Creator: {}", event.user);
-                                    
-                                    // Initialize virtual reserves for the new token
-                                    if !self.token_reserves.contains_key(&mint_address) {
-                                        // Initialize virtual reserve values - adjusted based on transaction records for more accurate values
-                                        let virtual_sol_reserves = 30_000_000_000;             // 30 SOL (lamports)
-                                        let virtual_token_reserves = 1_073_000_000_000_000;    // Approximately 1.073 billion tokens (6 decimal precision)
-                                        
-                                        self.token_reserves.insert(mint_address.clone(), TokenReserves {
-                                            virtual_sol_reserves,
-                                            virtual_token_reserves,
-                                        });
-                                    }
-                                }
-                            }
-                            "Buy" => {
-                                if let Some(event) = buy_event {
-                                    // Use raw values directly, preserving precision
-                                    let token_amount = event.amount;
-                                    let sol_amount = event.max_sol_cost;
-                                    
-                                    // Simplified display output
-                                    let token_amount_display = token_amount as f64 / 1_000_000.0; // Considering 6 decimal places
-                                    let sol_amount_display = sol_amount as f64 / 1_000_000_000.0;
-                                    
-                                    println!("Buy_Event:");
-                                    println!("  User: {}", message.account_keys[0]);
-                                    println!("  SOL_Amount: {:.6}", sol_amount_display);
-                                    println!("  Token_Amount: {:.6}", token_amount_display);
-                                    
-                                    // Check if snipe conditions are met
-                                    if let Some(auto_trader) = &self.auto_trader {
-                                        // Clone mint_address and auto_trader for use in async closure
-                                        let mint = mint_address.clone();
-                                        let trader_clone = Arc::clone(auto_trader);
-                                        
-                                        // Use tokio::spawn to start an async task to check if sniping is needed
-                                        let sol_amount_copy = sol_amount;
-                                        let sol_display = sol_amount_display;
-                                        
-                                        // Get current token price
-                                        let token_price = if let Some(reserves) = self.token_reserves.get(&mint_address) {
-                                            let virtual_sol = reserves.virtual_sol_reserves as f64 / 1_000_000_000.0;
-                                            let virtual_token = reserves.virtual_token_reserves as f64 / 1_000_000.0;
-                                            virtual_sol / virtual_token
-                                        } else {
-                                            0.000000033 // Default estimated value if actual price cannot be obtained
-                                        };
-                                        
-                                        // Pass slot to be used for getting an appropriate block hash
-                                        let current_slot = slot;
-                                        
-                                        // Use tokio::spawn to execute async code
-                                        tokio::spawn(async move {
-                                            // Record start time for monitoring processing delay
-                                            let start_time = std::time::Instant::now();
-                                            
-                                            let should_snipe = {
-                                                let trader = trader_clone.lock().await;
-                                                trader.should_snipe(sol_amount_copy)
-                                            };
-                                            
-                                            if should_snipe {
-                                                println!("Detected eligible purchase, preparing to snipe: {} SOL", sol_display);
-                                                println!("Using slot: {}, current time: {}", current_slot, Local::now().format("%H:%M:%S%.3f"));
-                                                println!("Delay from detection to snipe preparation: {:.3}ms", start_time.elapsed().as_millis());
-                                                
-                                                // Acquire lock to execute snipe, passing slot
-                                                let trader = trader_clone.lock().await;
-                                                if let Err(e) = trader.snipe_token(&mint, token_price, Some(current_slot)).await {
-                                                    println!("Snipe failed: {:?}", e);
-                                                }
-                                            }
-                                        });
-                                    }
-                                    
-                                    // Update virtual reserves (for internal calculation only, not displayed as real values)
-                                    if let Some(reserves) = self.token_reserves.get_mut(&mint_address) {
-                                        // State before update
-                                        let old_virtual_token = reserves.virtual_token_reserves;
-                                        
-                                        // Update virtual reserves, adding overflow check
-                                        reserves.virtual_sol_reserves = reserves.virtual_sol_reserves.saturating_add(sol_amount);
-                                        
-                                        // Use saturating_sub to avoid overflow
-                                        if token_amount <= reserves.virtual_token_reserves {
-                                            reserves.virtual_token_reserves = reserves.virtual_token_reserves.saturating_sub(token_amount);
-                                        }
-                                        
-                                        // Calculate price (using virtual reserves)
-                                        let virtual_sol = reserves.virtual_sol_reserves as f64 / 1_000_000_000.0;
-                                        let virtual_token = reserves.virtual_token_reserves as f64 / 1_000_000.0;
-                                        let price = virtual_sol / virtual_token;
-                                        
-                                        // realSolReserves and realTokenReserves are actually just data extracted from the transaction, not real reserve states
-                                        // realSolReserves is usually the SOL invested in the transaction
-                                        let real_sol_reserves = sol_amount_display;
-                                        
-                                        // realTokenReserves is based on the token reserve before the transaction minus the tokens obtained
-                                        // Use checked_sub to avoid overflow, display 0 if overflow occurs
-                                        let real_token_reserves = if old_virtual_token >= token_amount {
-                                            (old_virtual_token - token_amount) as f64 / 1_000_000.0
-                                        } else {
-                                            0.0 // Display 0 if overflow occurs
-                                        };
-                                        
-                                        println!("  realSolReserves: {:.6}", real_sol_reserves);
-                                        println!("  realTokenReserves: {:.6}", real_token_reserves);
-                                        println!("  Price: {:.9}", price);
-                                    }
+        for instruction in message.instructions() {
+            if message.account_keys().get(instruction.program_id_index as usize) != Some(&PUMP_PROGRAM_ID) {
+                continue;
+            }
+            let sell = match parse_sell_instruction(&instruction.data) {
+                Some(sell) => sell,
+                None => continue,
+            };
+            // Accounts from address lookup tables are not resolved, such sells are not attributed
+            let mint = match instruction
+                .accounts
+                .get(SELL_MINT_ACCOUNT_INDEX)
+                .and_then(|index| message.account_keys().get(*index as usize))
+            {
+                Some(mint) => mint.to_string(),
+                None => continue,
+            };
+
+            // A position cannot be told apart while the inventory is being written, check the next sell
+            let held = match positions.try_read() {
+                Ok(positions) => positions.get_position(&mint).is_some(),
+                Err(_) => continue,
+            };
+            let quoted = self.token_reserves.peek(&mint).map_or(0, |reserves| {
+                (sell.amount as u128 * reserves.virtual_sol_reserves as u128 / reserves.virtual_token_reserves.max(1) as u128) as u64
+            });
+            let sol_value = quoted.max(sell.min_sol_output);
+
+            if exit.on_sell(&mint, sol_value, held) {
+                println!("Large sell of {} SOL on held mint {}, exiting the position", format_amount(sol_value, SOL_DECIMALS), mint);
+                let trader = Arc::clone(trader);
+                tokio::spawn(async move {
+                    trader.lock().await.force_sell_mint(&mint).await;
+                });
+            }
+        }
+    }
+
+    // Track a created mint: metadata, creator and initial reserves. Returns the creator.
+    fn handle_create(&mut self, mint: Pubkey, fee_payer: Pubkey, event: CreateEventInstruction) -> Pubkey {
+        let mint_address = &mint.to_string();
+        self.metrics.creates.fetch_add(1, Ordering::Relaxed);
+        println!("Token_Metadata:");
+        println!("  Name: {}", event.name);
+        println!("  Symbol: {}", event.symbol);
+        println!("  URI: {}", event.uri);
+        println!("  Creator: {}", event.user);
+
+        if let Some(shadow) = &mut self.shadow {
+            shadow.on_token_created(mint_address);
+        }
+
+        if let Some(filter) = &mut self.symbol_filter {
+            filter.on_token_created(mint_address, &event.symbol);
+        }
+
+        // The fee payer of the create is the creator if the event carries none
+        let creator = if event.user == Pubkey::default() { fee_payer } else { event.user };
+        if let Some(limiter) = &mut self.creator_limiter {
+            limiter.on_token_created(mint_address, creator);
+        }
+        if let Some(allowlist) = &mut self.creator_allowlist {
+            allowlist.on_token_created(mint_address, creator);
+        }
+
+        // Without a qualifying buy threshold the ignored dev buy leaves the trigger to the next buyer
+        if self.config.ignore_dev_buy && self.qualifying_buys.is_none() {
+            self.awaiting_third_party_buy.put(mint_address.to_string(), creator);
+        }
+
+        // Initialize virtual reserves for the new token, within MAX_TRACKED_MINTS
+        if !self.token_reserves.contains(mint_address) && self.make_room_for_mint() {
+            let evicted = self.token_reserves.push(mint_address.to_string(), TokenReserves {
+                virtual_sol_reserves: INITIAL_VIRTUAL_SOL_RESERVES,
+                virtual_token_reserves: INITIAL_VIRTUAL_TOKEN_RESERVES,
+            });
+            // Evicted mints fall back to the default price estimate on their next buy
+            if let Some((evicted_mint, _)) = evicted {
+                debug!("Evicted reserves of {} from the token reserves cache", evicted_mint);
+            }
+            self.spawn_reserve_simulation(mint);
+            self.spawn_compute_unit_calibration(mint);
+        }
+        creator
+    }
+
+    // Handle a buy of any transaction: it moves the mint's reserves and buy volume and counts
+    // towards its qualifying buyers. Buys outside a create trigger snipes only through the
+    // qualifying buy threshold, without one a mint is sniped on the buys of its create, or on its
+    // first third-party buy when the dev buy is ignored.
+    fn handle_buy(&mut self, mint_address: &str, buyer: Pubkey, event: BuyInstruction, creator: Option<Pubkey>, in_create: bool, slot: u64) {
+        // Use raw values directly, preserving precision
+        let token_amount = event.amount;
+        let sol_amount = event.max_sol_cost;
+
+        let sol_amount_display = sol_amount as f64 / 1_000_000_000.0;
+
+        // Buys of other transactions are too frequent for the console
+        if in_create {
+            println!("Buy_Event:");
+            println!("  User: {}", buyer);
+            println!("  SOL_Amount: {} SOL", format_amount(sol_amount, SOL_DECIMALS));
+            println!("  Token_Amount: {}", format_amount(token_amount, self.config.token_decimals));
+        } else {
+            debug!("Buy of {} by {}: {} SOL for {} tokens", mint_address, buyer,
+                   format_amount(sol_amount, SOL_DECIMALS), format_amount(token_amount, self.config.token_decimals));
+        }
+
+        // Shadow strategies are compared on the same trigger as the live snipe, the buys of creates
+        if in_create {
+            if let Some(shadow) = &mut self.shadow {
+                shadow.on_buy(&BuyObservation {
+                    mint: mint_address,
+                    sol_amount,
+                    reserves: self.token_reserves.peek(mint_address).copied(),
+                    slot,
+                });
+            }
+        }
+
+        self.record_buy_volume(mint_address, sol_amount);
+
+        let spam = self.symbol_filter.as_ref().is_some_and(|filter| filter.is_skipped(mint_address));
+
+        // The create of this mint was missed (e.g. slot gap), its price falls back to the default estimate
+        let known_mint = self.token_reserves.contains(mint_address);
+        if !known_mint && in_create {
+            warn!("Buy event for unknown mint {}: {} SOL", mint_address, format_amount(sol_amount, SOL_DECIMALS));
+        }
+        let mut snipe_allowed = !spam && (known_mint || self.config.snipe_unknown_mints);
+
+        // Only the first buy of a mint by anyone but its creator, when the dev buy is ignored
+        let first_third_party_buy = self.awaiting_third_party_buy.peek(mint_address).is_some_and(|creator| *creator != buyer);
+        if first_third_party_buy {
+            self.awaiting_third_party_buy.pop(mint_address);
+        }
+
+        // Without a qualifying buy threshold every later buy would trigger another snipe of the mint
+        if !in_create && self.qualifying_buys.is_none() && !first_third_party_buy {
+            snipe_allowed = false;
+        }
+
+        // With an allowlist, mints of other or unknown creators are never sniped
+        if let Some(allowlist) = &self.creator_allowlist {
+            if snipe_allowed && !allowlist.is_allowed(mint_address) {
+                debug!("Skipping {}: creator not on the allowlist", mint_address);
+                snipe_allowed = false;
+            }
+        }
+
+        // The creator's own buy in its create is launch demand, not a trigger
+        if self.config.ignore_dev_buy && snipe_allowed && creator == Some(buyer) {
+            println!("Skipping {}: dev buy by its creator {}", mint_address, buyer);
+            snipe_allowed = false;
+        }
+
+        // Only tokens with momentum: enough buy volume within the last minute
+        if self.config.min_buy_volume_1m > 0 && snipe_allowed {
+            let volume = self.buy_volume_1m(mint_address);
+            if volume < self.config.min_buy_volume_1m {
+                if in_create {
+                    println!("Skipping {}: buy volume of {} SOL in the last minute is below {} SOL",
+                             mint_address, format_amount(volume, SOL_DECIMALS), format_amount(self.config.min_buy_volume_1m, SOL_DECIMALS));
+                }
+                snipe_allowed = false;
+            }
+        }
+
+        // Wait for enough distinct buyers before the first snipe of a mint
+        if let Some(tracker) = &mut self.qualifying_buys {
+            let qualifying = self.trade_limits.in_price_range(sol_amount);
+            snipe_allowed = snipe_allowed && qualifying && tracker.record(mint_address, buyer);
+        }
+
+        // Bound the exposure to a single deployer, counted only for snipes that go ahead
+        let can_snipe = self.snipe_trader().is_some();
+        if let Some(limiter) = &mut self.creator_limiter {
+            if snipe_allowed && can_snipe && !limiter.allow_snipe(mint_address) {
+                snipe_allowed = false;
+                if let Some(auto_trader) = self.snipe_trader() {
+                    let trader = Arc::clone(auto_trader);
+                    let mint = mint_address.to_string();
+                    let detected_at = std::time::Instant::now();
+                    tokio::spawn(async move {
+                        trader.lock().await.log_snipe_outcome(&mint, 0, 0, None,
+                            SnipeOutcome::Skipped("creator_limit".to_string()), detected_at.elapsed());
+                    });
+                }
+            }
+        }
+
+        if snipe_allowed {
+            self.metrics.snipe_triggers.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // Check if snipe conditions are met
+        if let Some(auto_trader) = self.snipe_trader().filter(|_| snipe_allowed) {
+            // Clone mint_address and auto_trader for use in async closure
+            let mint = mint_address.to_string();
+            let trader_clone = Arc::clone(auto_trader);
+
+            // Use tokio::spawn to start an async task to check if sniping is needed
+            let sol_amount_copy = sol_amount;
+            let sol_display = sol_amount_display;
+
+            // Get current token price
+            let token_price = if let Some(reserves) = self.token_reserves.peek(mint_address) {
+                reserves.price(self.config.token_decimals)
+            } else {
+                0.000000033 // Default estimated value if actual price cannot be obtained
+            };
+
+            // Tracked reserves, for exact token amount quotes
+            let reserves = self.token_reserves.peek(mint_address).copied();
+
+            // Pass slot to be used for getting an appropriate block hash
+            let current_slot = slot;
+
+            // Start of the detection -> submission pipeline for this buy
+            let detected_at = std::time::Instant::now();
+
+            if self.config.batch_buys_max > 1 {
+                // Sniped together with the other qualifying buys of this batch
+                if !self.pending_snipes.iter().any(|pending| pending.target.mint == mint) {
+                    let waits = self.snipe_waits(&mint);
+                    self.pending_snipes.push(PendingSnipe {
+                        target: SnipeTarget { mint, token_price, reserves },
+                        sol_amount: sol_amount_copy,
+                        detected_at,
+                        waits,
+                    });
+                }
+            } else {
+                // Use tokio::spawn to execute async code, traced as a child of the batch
+                let decision_span = info_span!("snipe_decision", mint = %mint, slot = current_slot);
+                let waits = self.snipe_waits(&mint);
+                let mut target = SnipeTarget { mint, token_price, reserves };
+                let handle = tokio::spawn(async move {
+                    // The create may still be waiting for its commitment or reserve simulation
+                    if !waits.settle(&mut target).await {
+                        trader_clone.lock().await.log_snipe_skip(&target.mint, SkipReason::Unconfirmed, detected_at, current_slot);
+                        return;
+                    }
+                    let SnipeTarget { mint, token_price, reserves } = target;
+
+                    // Record start time for monitoring processing delay
+                    let start_time = std::time::Instant::now();
+
+                    let decision = {
+                        let trader = trader_clone.lock().await;
+                        trader.evaluate_snipe(sol_amount_copy)
+                    };
+
+                    if decision == SnipeDecision::Skip(SkipReason::RateLimited) {
+                        println!("Snipe rate limit reached, skipping purchase: {} SOL", sol_display);
+                    }
+                    if let SnipeDecision::Skip(reason) = decision {
+                        trader_clone.lock().await.log_snipe_skip(&mint, reason, detected_at, current_slot);
+                    }
+
+                    if decision == SnipeDecision::Snipe {
+                        println!("Detected eligible purchase, preparing to snipe: {} SOL", sol_display);
+                        println!("Using slot: {}, current time: {}", current_slot, Local::now().format("%H:%M:%S%.3f"));
+                        println!("Delay from detection to snipe preparation: {:.3}ms", start_time.elapsed().as_millis());
+
+                        // Acquire lock to execute snipe, passing slot. It is released before the
+                        // sent buy is confirmed.
+                        let (sent, confirmer) = {
+                            let lock_requested = Instant::now();
+                            let trader = trader_clone.lock().await;
+                            let start = PipelineStart { detected_at, lock_requested, locked_at: Instant::now() };
+                            trader.record_pipeline_start(&mint, start).await;
+                            let sent = match trader.snipe_token(&mint, token_price, reserves, Some(current_slot)).await {
+                                Ok(sent) => sent,
+                                Err(e) => {
+                                    println!("Snipe failed: {:?}", e);
+                                    None
                                 }
-                            }
-                            _ => {
-                                // Other instruction types are not processed for now
-                            }
-                        }
+                            };
+                            (sent, trader.buy_confirmer())
+                        };
+                        confirm_sent_buys(&trader_clone, confirmer, sent.into_iter().collect()).await;
                     }
-                }
+                }.instrument(decision_span));
+                self.snipe_tasks.track(handle);
+            }
+        }
+
+        // Update virtual reserves (for internal calculation only, not displayed as real values)
+        if let Some(reserves) = self.token_reserves.get_mut(mint_address) {
+            // State before update
+            let old_virtual_token = reserves.virtual_token_reserves;
+
+            // Update virtual reserves, adding overflow check
+            reserves.virtual_sol_reserves = reserves.virtual_sol_reserves.saturating_add(sol_amount);
+
+            // Use saturating_sub to avoid overflow
+            if token_amount <= reserves.virtual_token_reserves {
+                reserves.virtual_token_reserves = reserves.virtual_token_reserves.saturating_sub(token_amount);
+            }
+
+            if in_create {
+                // Calculate price (using virtual reserves), in SOL per whole token of the mint's decimals
+                let price = reserves.price(self.config.token_decimals);
+
+                // realSolReserves and realTokenReserves are actually just data extracted from the transaction, not real reserve states
+                // realSolReserves is usually the SOL invested in the transaction
+                let real_sol_reserves = sol_amount;
+
+                // realTokenReserves is based on the token reserve before the transaction minus the tokens obtained
+                // Use saturating_sub to avoid overflow, display 0 if overflow occurs
+                let real_token_reserves = old_virtual_token.saturating_sub(token_amount);
+
+                println!("  realSolReserves: {}", format_amount(real_sol_reserves, SOL_DECIMALS));
+                println!("  realTokenReserves: {}", format_amount(real_token_reserves, self.config.token_decimals));
+                println!("  Price: {}", format_price(price));
             }
         }
-        Ok(())
     }
 
-    fn process_message_legacy(&mut self, message: &solana_sdk::message::Message, transaction: &VersionedTransaction, slot: u64) -> Result<(), Box<dyn Error>> {
-        if message.account_keys.contains(&self.token_creator_pubkey) {
+    // Shared handling of v0 and legacy messages, monomorphized per message type via MessageAdapter
+    async fn process_message_inner<M: MessageAdapter>(&mut self, message: &M, transaction: &VersionedTransaction, slot: u64) -> Result<(), SniperError> {
+        if self.large_sell_exit.is_some() {
+            self.check_large_sells(message);
+        }
+
+        // The first signature identifies the transaction, the others belong to additional signers
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+
+        // Mint created by this transaction, if it is a create, with its mint and fee payer accounts
+        let mut created_mint: Option<String> = None;
+        let mut create_accounts: Option<(Pubkey, Pubkey)> = None;
+        if message.account_keys().contains(&self.token_creator_pubkey) {
+            // A create lists the fee payer, mint and bonding curve first, a transaction without them is skipped
+            let account_keys = message.account_keys();
+            let (fee_payer, mint_account, bonding_curve_account) = match (account_keys.first(), account_keys.get(1), account_keys.get(2)) {
+                (Some(fee_payer), Some(mint), Some(bonding_curve)) => (*fee_payer, *mint, *bonding_curve),
+                _ => return Ok(()),
+            };
+
             println!("\n{}", "-".repeat(80));
             println!("[{}] Pumpfun internal token creation event:", Local::now().format("%Y-%m-%d %H:%M:%S%.3f"));
             println!("Slot: {}", slot);
-            println!("Signatures: {}", transaction.signatures[0]);
-            
+            if transaction.signatures.len() > 1 {
+                let signatures: Vec<String> = transaction.signatures.iter().map(Signature::to_string).collect();
+                println!("Signatures ({}): {}", signatures.len(), signatures.join(", "));
+                let signers: Vec<String> = message.signers().iter().map(Pubkey::to_string).collect();
+                println!("Signers: {}", signers.join(", "));
+            } else {
+                println!("Signatures: {}", signature);
+            }
+
+            if account_key_logging_enabled() {
+                log_account_keys(&signature.to_string(), &transaction.message);
+            }
+
             // Extract key account addresses
-            let mint_address = message.account_keys[1].to_string();
-            let bonding_curve = message.account_keys[2].to_string();
-            
+            let mint_address = mint_account.to_string();
+            let bonding_curve = bonding_curve_account.to_string();
+
             println!("Mint: {}", mint_address);
             println!("Bonding_Curve: {}", bonding_curve);
 
-            // Check all instructions in the transaction
-            for instruction in &message.instructions {
-                let program_id = message.account_keys[instruction.program_id_index as usize].to_string();
-                
-                // If the instruction is for the target program
-                if program_id == self.token_creator_pubkey.to_string() || program_id == "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P" {
-                    // Attempt to parse the instruction
-                    if let Ok((instruction_type, create_event, buy_event)) = parse_instruction_data(&instruction.data) {
-                        match instruction_type.as_str() {
-                            "CreateEvent" => {
-                                if let Some(event) = create_event {
-                                    println!("Token_Metadata:");
-                                    println!("  Name: {}", event.name);
-                                    println!("  Symbol: {}", event.symbol);
-                                    println!("  URI: {}", event.uri);
-                                    println!("  Creator: {}", event.user);
-                                    
-                                    // Initialize virtual reserves for the new token
-                                    if !self.token_reserves.contains_key(&mint_address) {
-                                        // Initialize virtual reserve values - adjusted based on transaction records for more accurate values
-                                        let virtual_sol_reserves = 30_000_000_000;             // 30 SOL (lamports)
-                                        let virtual_token_reserves = 1_073_000_000_000_000;    // Approximately 1.073 billion tokens (6 decimal precision)
-                                        
-                                        self.token_reserves.insert(mint_address.clone(), TokenReserves {
-                                            virtual_sol_reserves,
-                                            virtual_token_reserves,
-                                        });
-                                    }
-                                }
+            // Snipes of the mint hold off until the create reaches CREATE_COMMITMENT
+            self.spawn_commitment_gate(&mint_address, signature);
+            created_mint = Some(mint_address);
+            create_accounts = Some((mint_account, fee_payer));
+        }
+
+        // Creator from the create event, to tell its own dev buy apart from third-party buys
+        let mut creator: Option<Pubkey> = None;
+
+        // Check all instructions in the transaction. Buys are handled in every transaction, most
+        // buys of a mint come after its create in transactions of their own.
+        for instruction in message.instructions() {
+            let program_id = match message.account_keys().get(instruction.program_id_index as usize) {
+                Some(program_id) => *program_id,
+                None => continue,
+            };
+
+            // If the instruction is for the target program
+            if program_id == self.token_creator_pubkey || program_id == PUMP_PROGRAM_ID {
+                // Cheap discriminator check first, most instructions are not create/buy/sell
+                if estimate_instruction_type(&instruction.data).is_none() {
+                    continue;
+                }
+
+                // Attempt to parse the instruction
+                if let Ok((instruction_type, create_event, buy_event)) = parse_instruction_data(&instruction.data) {
+                    match instruction_type.as_str() {
+                        "CreateEvent" => {
+                            if let (Some(event), Some((mint_account, fee_payer))) = (create_event, create_accounts) {
+                                creator = Some(self.handle_create(mint_account, fee_payer, event));
                             }
-                            "Buy" => {
-                                if let Some(event) = buy_event {
-                                    // Use raw values directly, preserving precision
-                                    let token_amount = event.amount;
-                                    let sol_amount = event.max_sol_cost;
-                                    
-                                    // Simplified display output
-                                    let token_amount_display = token_amount as f64 / 1_000_000.0; // Considering 6 decimal places
-                                    let sol_amount_display = sol_amount as f64 / 1_000_000_000.0;
-                                    
-                                    println!("Buy_Event:");
-                                    println!("  User: {}", message.account_keys[0]);
-                                    println!("  SOL_Amount: {:.6} SOL", sol_amount_display);
-                                    println!("  Token_Amount: {:.6} ", token_amount_display);
-                                    
-                                    // Check if snipe conditions are met
-                                    if let Some(auto_trader) = &self.auto_trader {
-                                        // Clone mint_address and auto_trader for use in async closure
-                                        let mint = mint_address.clone();
-                                        let trader_clone = Arc::clone(auto_trader);
-                                        
-                                        // Use tokio::spawn to start an async task to check if sniping is needed
-                                        let sol_amount_copy = sol_amount;
-                                        let sol_display = sol_amount_display;
-                                        
-                                        // Get current token price
-                                        let token_price = if let Some(reserves) = self.token_reserves.get(&mint_address) {
-                                            let virtual_sol = reserves.virtual_sol_reserves as f64 / 1_000_000_000.0;
-                                            let virtual_token = reserves.virtual_token_reserves as f64 / 1_000_000.0;
-                                            virtual_sol / virtual_token
-                                        } else {
-                                            0.000000033 // Default estimated value if actual price cannot be obtained
-                                        };
-                                        
-                                        // Pass slot to be used for getting an appropriate block hash
-                                        let current_slot = slot;
-                                        
-                                        // Use tokio::spawn to execute async code
-                                        tokio::spawn(async move {
-                                            // Record start time for monitoring processing delay
-                                            let start_time = std::time::Instant::now();
-                                            
-                                            let should_snipe = {
-                                                let trader = trader_clone.lock().await;
-                                                trader.should_snipe(sol_amount_copy)
-                                            };
-                                            
-                                            if should_snipe {
-                                                println!("Detected eligible purchase, preparing to snipe: {} SOL", sol_display);
-                                                println!("Using slot: {}, current time: {}", current_slot, Local::now().format("%H:%M:%S%.3f"));
-                                                println!("Delay from detection to snipe preparation: {:.3}ms", start_time.elapsed().as_millis());
-                                                
-                                                // Acquire lock to execute snipe, passing slot
-                                                let trader = trader_clone.lock().await;
-                                                if let Err(e) = trader.snipe_token(&mint, token_price, Some(current_slot)).await {
-                                                    println!("Snipe failed: {:?}", e);
-                                                }
-                                            }
-                                        });
-                                    }
-                                    
-                                    // Update virtual reserves (for internal calculation only, not displayed as real values)
-                                    if let Some(reserves) = self.token_reserves.get_mut(&mint_address) {
-                                        // State before update
-                                        let old_virtual_token = reserves.virtual_token_reserves;
-                                        
-                                        // Update virtual reserves, adding overflow check
-                                        reserves.virtual_sol_reserves = reserves.virtual_sol_reserves.saturating_add(sol_amount);
-                                        
-                                        // Use saturating_sub to avoid overflow
-                                        if token_amount <= reserves.virtual_token_reserves {
-                                            reserves.virtual_token_reserves = reserves.virtual_token_reserves.saturating_sub(token_amount);
-                                        }
-                                        
-                                        // Calculate price (using virtual reserves)
-                                        let virtual_sol = reserves.virtual_sol_reserves as f64 / 1_000_000_000.0;
-                                        let virtual_token = reserves.virtual_token_reserves as f64 / 1_000_000.0;
-                                        let price = virtual_sol / virtual_token;
-                                        
-                                        // realSolReserves and realTokenReserves are actually just data extracted from the transaction, not real reserve states
-                                        // realSolReserves is usually the SOL invested in the transaction
-                                        let real_sol_reserves = sol_amount_display;
-                                        
-                                        // realTokenReserves is based on the token reserve before the transaction minus the tokens obtained
-                                        // Use checked_sub to avoid overflow, display 0 if overflow occurs
-                                        let real_token_reserves = if old_virtual_token >= token_amount {
-                                            (old_virtual_token - token_amount) as f64 / 1_000_000.0
-                                        } else {
-                                            0.0 // Display 0 if overflow occurs
-                                        };
-                                        
-                                        println!("  realSolReserves: {:.6}", real_sol_reserves);
-                                        println!("  realTokenReserves: {:.6}", real_token_reserves);
-                                        println!("  Price: {:.9}", price);
-                                    }
+                        }
+                        "Buy" => {
+                            if let Some(event) = buy_event {
+                                // Accounts from address lookup tables are not resolved, the buy of a
+                                // create then falls back to the created mint
+                                let mint_address = instruction
+                                    .accounts
+                                    .get(BUY_MINT_ACCOUNT_INDEX)
+                                    .and_then(|index| message.account_keys().get(*index as usize))
+                                    .map(Pubkey::to_string)
+                                    .or_else(|| created_mint.clone());
+                                if let Some(mint_address) = mint_address {
+                                    let buyer = buyer_of(message, instruction);
+                                    self.handle_buy(&mint_address, buyer, event, creator, created_mint.is_some(), slot);
                                 }
                             }
-                            _ => {
-                                // Other instruction types are not processed for now
-                            }
+                        }
+                        _ => {
+                            // Other instruction types are not processed for now
                         }
                     }
                 }
             }
         }
+
+        // A create whose data no longer parses is read from its program logs instead
+        if let (Some((mint_account, fee_payer)), None) = (create_accounts, creator) {
+            println!("Create data of {} not parsed, reading its event from the logs", signature);
+            self.spawn_create_log_fallback(mint_account, fee_payer, signature);
+        }
         Ok(())
     }
-} 
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::{v0, Message, MessageHeader};
+    use solana_sdk::signature::Keypair;
+    use crate::instruction::{BUY_EVENT_DISCRIMINATOR, CREATE_EVENT_DISCRIMINATOR};
+
+    // Message with a signer count independent of its keys, e.g. one claiming more signers than keys
+    struct MockMessage {
+        account_keys: Vec<Pubkey>,
+        instructions: Vec<CompiledInstruction>,
+        num_required_signatures: usize,
+    }
+
+    impl MessageAdapter for MockMessage {
+        fn account_keys(&self) -> &[Pubkey] {
+            &self.account_keys
+        }
+
+        fn instructions(&self) -> impl Iterator<Item = &CompiledInstruction> {
+            self.instructions.iter()
+        }
+
+        fn num_required_signatures(&self) -> usize {
+            self.num_required_signatures
+        }
+    }
+
+    // Config from the required variables only, everything else at its default
+    fn test_config() -> Arc<Config> {
+        let private_key = Keypair::new().to_base58_string();
+        Arc::new(Config::from_vars(&[("SERVER_URL", "http://127.0.0.1:1"), ("PRIVATE_KEY", &private_key)]))
+    }
+
+    fn header(num_required_signatures: u8) -> MessageHeader {
+        MessageHeader {
+            num_required_signatures,
+            num_readonly_signed_accounts: 0,
+            num_readonly_unsigned_accounts: 0,
+        }
+    }
+
+    fn create_data(name: &str, symbol: &str, uri: &str, user: &Pubkey) -> Vec<u8> {
+        let mut data = CREATE_EVENT_DISCRIMINATOR.to_vec();
+        for field in [name, symbol, uri] {
+            data.extend((field.len() as u32).to_le_bytes());
+            data.extend(field.as_bytes());
+        }
+        data.extend(user.to_bytes());
+        data
+    }
+
+    fn buy_data(token_amount: u64, max_sol_cost: u64) -> Vec<u8> {
+        let mut data = BUY_EVENT_DISCRIMINATOR.to_vec();
+        data.extend(token_amount.to_le_bytes());
+        data.extend(max_sol_cost.to_le_bytes());
+        data
+    }
+
+    // Keys and instructions of a create with the creator's buy: creator and mint sign, the
+    // creator is the user account of the buy
+    struct CreateTransaction {
+        mint: Pubkey,
+        account_keys: Vec<Pubkey>,
+        instructions: Vec<CompiledInstruction>,
+    }
+
+    fn create_transaction(config: &Config) -> CreateTransaction {
+        let creator = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let account_keys = vec![creator, mint, Pubkey::new_unique(), config.token_creator_pubkey, PUMP_PROGRAM_ID];
+        let instructions = vec![
+            CompiledInstruction::new_from_raw_parts(4, create_data("Token", "TKN", "https://example.com", &creator), vec![1, 2, 3, 0]),
+            CompiledInstruction::new_from_raw_parts(4, buy_data(1_000_000, 100_000_000), vec![3, 3, 1, 2, 2, 0, 0]),
+        ];
+        CreateTransaction { mint, account_keys, instructions }
+    }
+
+    fn create_message(create: &CreateTransaction) -> Message {
+        Message {
+            header: header(2),
+            account_keys: create.account_keys.clone(),
+            recent_blockhash: Hash::default(),
+            instructions: create.instructions.clone(),
+        }
+    }
+
+    // A buy of the mint in a transaction of its own, signed and paid by the buyer
+    fn buy_message(mint: Pubkey, buyer: Pubkey, max_sol_cost: u64) -> Message {
+        Message {
+            header: header(1),
+            account_keys: vec![buyer, mint, PUMP_PROGRAM_ID],
+            recent_blockhash: Hash::default(),
+            instructions: vec![CompiledInstruction::new_from_raw_parts(2, buy_data(1_000, max_sol_cost), vec![2, 2, 1, 2, 2, 0, 0])],
+        }
+    }
+
+    // Transaction carrying the message, with a signature for the create handling to log
+    fn signed_transaction(message: &Message) -> VersionedTransaction {
+        VersionedTransaction { signatures: vec![Signature::new_unique()], message: VersionedMessage::Legacy(message.clone()) }
+    }
+
+    #[test]
+    fn signers_follow_num_required_signatures() {
+        let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+
+        let legacy = Message {
+            header: header(2),
+            account_keys: keys.clone(),
+            recent_blockhash: Hash::default(),
+            instructions: vec![],
+        };
+        let versioned = v0::Message {
+            header: header(2),
+            account_keys: keys.clone(),
+            recent_blockhash: Hash::default(),
+            instructions: vec![],
+            address_table_lookups: vec![],
+        };
+
+        assert_eq!(legacy.signers(), &keys[..2]);
+        assert_eq!(versioned.signers(), &keys[..2]);
+    }
+
+    #[test]
+    fn signers_never_exceed_the_account_keys() {
+        let keys: Vec<Pubkey> = (0..2).map(|_| Pubkey::new_unique()).collect();
+        let message = MockMessage { account_keys: keys.clone(), instructions: vec![], num_required_signatures: 5 };
+
+        assert_eq!(message.signers(), &keys[..]);
+    }
+
+    #[test]
+    fn buyer_is_the_signing_user_account_or_the_fee_payer() {
+        let payer = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let keys = vec![payer, user, other, PUMP_PROGRAM_ID];
+        let accounts = |user_index: u8| vec![2, 2, 2, 2, 2, 2, user_index];
+
+        // The user account signs: a relayer pays the fee for the buyer
+        let signed = MockMessage { account_keys: keys.clone(), instructions: vec![], num_required_signatures: 2 };
+        let instruction = CompiledInstruction::new_from_raw_parts(3, buy_data(1, 1), accounts(1));
+        assert_eq!(buyer_of(&signed, &instruction), user);
+
+        // The user account does not sign, the fee payer is the buyer
+        let unsigned = MockMessage { account_keys: keys, instructions: vec![], num_required_signatures: 1 };
+        assert_eq!(buyer_of(&unsigned, &instruction), payer);
+    }
+
+    // Both message versions go through the same handler and track the created mint and its buy
+    #[tokio::test]
+    async fn create_and_buy_are_handled_for_legacy_and_v0_messages() {
+        let config = test_config();
+
+        for versioned in [false, true] {
+            let mut processor = TransactionProcessor::new(config.clone());
+            let create = create_transaction(&config);
+            let message = if versioned {
+                VersionedMessage::V0(v0::Message {
+                    header: header(2),
+                    account_keys: create.account_keys.clone(),
+                    recent_blockhash: Hash::default(),
+                    instructions: create.instructions.clone(),
+                    address_table_lookups: vec![],
+                })
+            } else {
+                VersionedMessage::Legacy(Message {
+                    header: header(2),
+                    account_keys: create.account_keys.clone(),
+                    recent_blockhash: Hash::default(),
+                    instructions: create.instructions.clone(),
+                })
+            };
+            let transaction = VersionedTransaction { signatures: vec![], message };
+
+            match &transaction.message {
+                VersionedMessage::V0(message) => processor.process_message_inner(message, &transaction, 1).await.unwrap(),
+                VersionedMessage::Legacy(message) => processor.process_message_inner(message, &transaction, 1).await.unwrap(),
+            }
+
+            assert_eq!(processor.metrics().creates_seen(), 1);
+            let reserves = processor.token_reserves.peek(&create.mint.to_string()).copied().expect("mint is tracked");
+            assert_eq!(reserves.virtual_sol_reserves, INITIAL_VIRTUAL_SOL_RESERVES + 100_000_000);
+            assert_eq!(reserves.virtual_token_reserves, INITIAL_VIRTUAL_TOKEN_RESERVES - 1_000_000);
+        }
+    }
+
+    // A create whose data does not parse is tracked from the event in its program logs
+    #[tokio::test]
+    async fn unparsed_creates_are_read_from_their_logs() {
+        let config = test_config();
+        let mut processor = TransactionProcessor::new(config.clone());
+        let mut create = create_transaction(&config);
+        create.instructions[0].data.truncate(10);
+        let message = create_message(&create);
+        processor.process_message_inner(&message, &signed_transaction(&message), 1).await.unwrap();
+        assert!(!processor.token_reserves.contains(&create.mint.to_string()));
+        assert!(processor.create_log_fallbacks.contains_key(&create.mint));
+
+        // The logs of the confirmed create carry the event
+        let creator = create.account_keys[0];
+        let event = CreateEventInstruction { name: "Token".to_string(), symbol: "TKN".to_string(), uri: String::new(), user: creator };
+        let logged = PendingCheck::spawn(async move { Some(event) });
+        logged.wait().await;
+        processor.create_log_fallbacks.insert(create.mint, (creator, logged));
+        processor.settle_create_log_fallbacks();
+
+        assert!(processor.create_log_fallbacks.is_empty());
+        assert!(processor.token_reserves.contains(&create.mint.to_string()));
+    }
+
+    // A create transaction without the fee payer, mint and bonding curve accounts is skipped
+    #[tokio::test]
+    async fn creates_missing_their_accounts_are_skipped() {
+        let config = test_config();
+        let mut processor = TransactionProcessor::new(config.clone());
+        let create = create_transaction(&config);
+        let mut instruction = create.instructions[0].clone();
+        instruction.program_id_index = 1;
+        let message = MockMessage {
+            account_keys: vec![create.account_keys[0], config.token_creator_pubkey],
+            instructions: vec![instruction],
+            num_required_signatures: 1,
+        };
+        let transaction = VersionedTransaction { signatures: vec![], message: VersionedMessage::Legacy(create_message(&create)) };
+
+        processor.process_message_inner(&message, &transaction, 1).await.unwrap();
+        assert_eq!(processor.metrics().creates_seen(), 0);
+        assert!(processor.create_log_fallbacks.is_empty());
+    }
+
+    // Buy-only transactions of distinct buyers count towards the qualifying buyers of a mint
+    #[tokio::test]
+    async fn qualifying_buys_are_counted_across_transactions() {
+        let config = Arc::new(Config { min_qualifying_buys: 3, ..(*test_config()).clone() });
+        let mut processor = TransactionProcessor::new(config.clone());
+        let create = create_transaction(&config);
+        let message = create_message(&create);
+        processor.process_message_inner(&message, &signed_transaction(&message), 1).await.unwrap();
+
+        // Buys within the default price range of 0.5 to 3 SOL
+        for slot in 2..4 {
+            let buy = buy_message(create.mint, Pubkey::new_unique(), 1_000_000_000);
+            processor.process_message_inner(&buy, &signed_transaction(&buy), slot).await.unwrap();
+        }
+
+        // The creator's buy is below the range, a third distinct buyer reaches the threshold
+        let tracker = processor.qualifying_buys.as_mut().expect("a threshold is configured");
+        assert!(tracker.record(&create.mint.to_string(), Pubkey::new_unique()));
+    }
+
+    // Buys after the create, in transactions without the mint authority, count towards the volume
+    #[tokio::test]
+    async fn buy_volume_counts_buys_outside_the_create() {
+        let config = test_config();
+        let mut processor = TransactionProcessor::new(config.clone());
+        let create = create_transaction(&config);
+        let message = create_message(&create);
+        processor.process_message_inner(&message, &signed_transaction(&message), 1).await.unwrap();
+
+        for _ in 0..2 {
+            let buy = buy_message(create.mint, Pubkey::new_unique(), 250_000_000);
+            processor.process_message_inner(&buy, &signed_transaction(&buy), 2).await.unwrap();
+        }
+
+        assert_eq!(processor.buy_volume_1m(&create.mint.to_string()), 100_000_000 + 2 * 250_000_000);
+    }
+
+    // With the dev buy ignored, neither the creator's buy in its create nor a later one of the
+    // creator triggers; the first third-party buy does, once
+    #[tokio::test]
+    async fn ignored_dev_buys_leave_the_trigger_to_the_first_third_party_buy() {
+        let config = Arc::new(Config { ignore_dev_buy: true, ..(*test_config()).clone() });
+        let mut processor = TransactionProcessor::new(config.clone());
+        let create = create_transaction(&config);
+        let message = create_message(&create);
+        processor.process_message_inner(&message, &signed_transaction(&message), 1).await.unwrap();
+        assert_eq!(processor.metrics().snipe_triggers(), 0);
+
+        let creator = create.account_keys[0];
+        let dev_buy = buy_message(create.mint, creator, 1_000_000_000);
+        processor.process_message_inner(&dev_buy, &signed_transaction(&dev_buy), 2).await.unwrap();
+        assert_eq!(processor.metrics().snipe_triggers(), 0);
+
+        for slot in 3..5 {
+            let buy = buy_message(create.mint, Pubkey::new_unique(), 1_000_000_000);
+            processor.process_message_inner(&buy, &signed_transaction(&buy), slot).await.unwrap();
+            assert_eq!(processor.metrics().snipe_triggers(), 1);
+        }
+    }
+}
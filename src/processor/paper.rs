@@ -0,0 +1,146 @@
+use std::collections::{HashMap, HashSet};
+use lru::LruCache;
+use crate::processor::TokenReserves;
+use crate::processor::display::{format_amount, format_signed_amount, SOL_DECIMALS};
+use crate::utils::bonding_curve::{quote_buy, quote_sell};
+
+// Approximate slot duration, used to express hold times in slots of the observed data
+pub const SLOT_DURATION_MS: u64 = 400;
+
+// A hypothetical open position
+struct PaperPosition {
+    token_amount: u64,
+    cost: u64,      // lamports
+    sell_slot: u64, // Slot at which the position is sold
+}
+
+// Hypothetical buys and sells of one strategy, priced from the tracked virtual reserves
+pub struct PaperLedger {
+    label: String,
+    token_decimals: u8,
+    tokens_seen: HashSet<String>,
+    eligible_buys: usize,
+    buys: usize,
+    sells: usize,
+    realized_pnl: i128, // lamports
+    open: HashMap<String, PaperPosition>,
+}
+
+impl PaperLedger {
+    pub fn new(label: &str, token_decimals: u8) -> Self {
+        Self {
+            label: label.to_string(),
+            token_decimals,
+            tokens_seen: HashSet::new(),
+            eligible_buys: 0,
+            buys: 0,
+            sells: 0,
+            realized_pnl: 0,
+            open: HashMap::new(),
+        }
+    }
+
+    // Record a newly created token
+    pub fn record_token(&mut self, mint: &str) {
+        self.tokens_seen.insert(mint.to_string());
+    }
+
+    // Buy `buy_sol` lamports worth of the token at the given (pre-buy) reserves, unless a
+    // position is already open
+    pub fn record_buy(&mut self, mint: &str, buy_sol: u64, reserves: Option<TokenReserves>, slot: u64, hold_ms: u64) {
+        self.eligible_buys += 1;
+
+        if self.open.contains_key(mint) {
+            return;
+        }
+        let reserves = match reserves {
+            Some(reserves) => reserves,
+            None => {
+                println!("[PAPER:{}] No tracked reserves for {}, cannot price a buy", self.label, mint);
+                return;
+            }
+        };
+
+        let token_amount = quote_buy(reserves.virtual_sol_reserves, reserves.virtual_token_reserves, buy_sol);
+        if token_amount == 0 {
+            return;
+        }
+
+        println!("[PAPER:{}] Buy {} tokens of {} for {} SOL at slot {}",
+                 self.label, format_amount(token_amount, self.token_decimals), mint, format_amount(buy_sol, SOL_DECIMALS), slot);
+
+        self.buys += 1;
+        self.open.insert(mint.to_string(), PaperPosition {
+            token_amount,
+            cost: buy_sol,
+            sell_slot: slot + hold_ms / SLOT_DURATION_MS,
+        });
+    }
+
+    // Sell the positions whose hold time has elapsed by the given slot
+    pub fn close_due(&mut self, slot: u64, token_reserves: &LruCache<String, TokenReserves>) {
+        let due: Vec<String> = self.open
+            .iter()
+            .filter(|(_, position)| position.sell_slot <= slot)
+            .map(|(mint, _)| mint.clone())
+            .collect();
+
+        for mint in due {
+            self.close(&mint, token_reserves);
+        }
+    }
+
+    // Sell all remaining positions at the current reserve prices
+    pub fn close_all(&mut self, token_reserves: &LruCache<String, TokenReserves>) {
+        let remaining: Vec<String> = self.open.keys().cloned().collect();
+        for mint in remaining {
+            self.close(&mint, token_reserves);
+        }
+    }
+
+    fn close(&mut self, mint: &str, token_reserves: &LruCache<String, TokenReserves>) {
+        if let Some(position) = self.open.remove(mint) {
+            let proceeds = sell_value(mint, position.token_amount, token_reserves);
+            let pnl = proceeds as i128 - position.cost as i128;
+
+            println!("[PAPER:{}] Sell {} tokens of {} for {} SOL (PnL {} SOL)",
+                     self.label,
+                     format_amount(position.token_amount, self.token_decimals),
+                     mint,
+                     format_amount(proceeds, SOL_DECIMALS),
+                     format_signed_amount(pnl, SOL_DECIMALS));
+
+            self.sells += 1;
+            self.realized_pnl += pnl;
+        }
+    }
+
+    // PnL of the open positions if they were sold at the current reserve prices
+    fn unrealized_pnl(&self, token_reserves: &LruCache<String, TokenReserves>) -> i128 {
+        self.open
+            .iter()
+            .map(|(mint, position)| sell_value(mint, position.token_amount, token_reserves) as i128 - position.cost as i128)
+            .sum()
+    }
+
+    // Print the totals, with open positions valued at the current reserve prices
+    pub fn print_summary(&self, token_reserves: &LruCache<String, TokenReserves>) {
+        println!("  [{}] tokens seen: {}, eligible buys: {}, buys: {}, sells: {}, open: {}, realized PnL: {} SOL, unrealized PnL: {} SOL",
+                 self.label,
+                 self.tokens_seen.len(),
+                 self.eligible_buys,
+                 self.buys,
+                 self.sells,
+                 self.open.len(),
+                 format_signed_amount(self.realized_pnl, SOL_DECIMALS),
+                 format_signed_amount(self.unrealized_pnl(token_reserves), SOL_DECIMALS));
+    }
+}
+
+// Lamports received for selling a position at the tracked reserves, 0 if the token is not tracked
+fn sell_value(mint: &str, token_amount: u64, token_reserves: &LruCache<String, TokenReserves>) -> u64 {
+    token_reserves
+        .peek(mint)
+        .map(|reserves| quote_sell(reserves.virtual_sol_reserves, reserves.virtual_token_reserves, token_amount))
+        .unwrap_or(0)
+}
@@ -0,0 +1,105 @@
+use std::future::Future;
+use std::time::Duration;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use crate::utils::auto_trader::SnipeTarget;
+use super::TokenReserves;
+
+// Result of a background check on a mint, started right away and awaited by any number of snipe
+// tasks, so the feed keeps being processed while the check runs
+#[derive(Clone)]
+pub struct PendingCheck<T: Clone>(Shared<BoxFuture<'static, Option<T>>>);
+
+impl<T: Clone + Send + Sync + 'static> PendingCheck<T> {
+    pub fn spawn<F>(check: F) -> Self
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        Self(tokio::spawn(check).map(Result::ok).boxed().shared())
+    }
+
+    // Whether the check has finished
+    pub fn is_done(&self) -> bool {
+        self.0.peek().is_some()
+    }
+
+    // The result once the check finished, None while it runs or if its task panicked
+    pub fn result(&self) -> Option<T> {
+        self.0.peek().cloned().flatten()
+    }
+
+    // Wait for the result, None if the check's task panicked
+    pub async fn wait(&self) -> Option<T> {
+        self.0.clone().await
+    }
+}
+
+// Checks a snipe of a mint waits for before its decision: the commitment gate of the mint's
+// create and the simulation of its initial reserves
+#[derive(Clone, Default)]
+pub struct SnipeWaits {
+    pub gate: Option<PendingCheck<bool>>,
+    pub simulation: Option<PendingCheck<Option<TokenReserves>>>,
+    pub simulation_wait: Duration, // Longest wait for the simulation, the tracked reserves are used after it
+    pub token_decimals: u8,
+}
+
+impl SnipeWaits {
+    // False if the create did not reach its commitment, the snipe is then dropped. Otherwise the
+    // target is repriced at the simulated reserves if the simulation succeeds within its wait.
+    pub async fn settle(&self, target: &mut SnipeTarget) -> bool {
+        if let Some(gate) = &self.gate {
+            if !gate.wait().await.unwrap_or(false) {
+                return false;
+            }
+        }
+
+        if let Some(simulation) = &self.simulation {
+            match tokio::time::timeout(self.simulation_wait, simulation.wait()).await {
+                Ok(Some(Some(reserves))) => {
+                    target.reserves = Some(reserves);
+                    target.token_price = reserves.price(self.token_decimals);
+                }
+                Ok(_) => {}
+                Err(_) => println!("Reserve simulation of {} still running after {}ms, using the tracked reserves",
+                                   target.mint, self.simulation_wait.as_millis()),
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target() -> SnipeTarget {
+        SnipeTarget { mint: "mint".to_string(), token_price: 0.0, reserves: None }
+    }
+
+    // A snipe waits for the simulated reserves of its mint and is repriced at them
+    #[tokio::test]
+    async fn snipe_waits_apply_the_simulation() {
+        let simulated = TokenReserves { virtual_sol_reserves: 40_000_000_000, virtual_token_reserves: 800_000_000_000_000 };
+        let waits = SnipeWaits {
+            simulation: Some(PendingCheck::spawn(async move { Some(simulated) })),
+            simulation_wait: Duration::from_secs(5),
+            token_decimals: 6,
+            ..SnipeWaits::default()
+        };
+
+        let mut settled = target();
+        assert!(waits.settle(&mut settled).await);
+        assert_eq!(settled.token_price, simulated.price(6));
+        assert_eq!(settled.reserves.map(|reserves| reserves.virtual_sol_reserves), Some(40_000_000_000));
+    }
+
+    // A snipe is dropped when the create of its mint does not reach its commitment
+    #[tokio::test]
+    async fn snipe_waits_drop_snipes_of_unconfirmed_creates() {
+        let confirmed = SnipeWaits { gate: Some(PendingCheck::spawn(async { true })), ..SnipeWaits::default() };
+        assert!(confirmed.settle(&mut target()).await);
+
+        let unconfirmed = SnipeWaits { gate: Some(PendingCheck::spawn(async { false })), ..SnipeWaits::default() };
+        assert!(!unconfirmed.settle(&mut target()).await);
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use solana_sdk::pubkey::Pubkey;
+
+// How long a triggered mint is remembered, so later buys do not trigger it again
+const TRIGGERED_RETENTION: Duration = Duration::from_secs(3600);
+
+// Confirmation-by-consensus trigger: a mint qualifies once enough distinct buyers made a
+// qualifying buy within the window
+pub struct QualifyingBuyTracker {
+    min_buys: usize,
+    window: Duration,
+    buys: HashMap<String, HashMap<Pubkey, Instant>>, // Mint -> buyer -> last qualifying buy
+    triggered: HashMap<String, Instant>,
+}
+
+impl QualifyingBuyTracker {
+    pub fn new(min_buys: usize, window_ms: u64) -> Self {
+        Self {
+            min_buys,
+            window: Duration::from_millis(window_ms),
+            buys: HashMap::new(),
+            triggered: HashMap::new(),
+        }
+    }
+
+    // Record a qualifying buy; returns true exactly once per mint, when the threshold is reached
+    pub fn record(&mut self, mint: &str, buyer: Pubkey) -> bool {
+        let now = Instant::now();
+        let window = self.window;
+        self.buys.retain(|_, buyers| {
+            buyers.retain(|_, bought_at| now.duration_since(*bought_at) < window);
+            !buyers.is_empty()
+        });
+        self.triggered.retain(|_, triggered_at| now.duration_since(*triggered_at) < TRIGGERED_RETENTION);
+
+        if self.triggered.contains_key(mint) {
+            return false;
+        }
+
+        let buyers = self.buys.entry(mint.to_string()).or_default();
+        buyers.insert(buyer, now);
+        let count = buyers.len();
+
+        if count < self.min_buys {
+            println!("Qualifying buy {}/{} for {}", count, self.min_buys, mint);
+            return false;
+        }
+
+        println!("{} distinct qualifying buyers for {} within {}ms", count, mint, window.as_millis());
+        self.buys.remove(mint);
+        self.triggered.insert(mint.to_string(), now);
+        true
+    }
+}
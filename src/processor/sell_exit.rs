@@ -0,0 +1,27 @@
+use std::collections::HashSet;
+
+// Exit on observed order flow: a sell of at least min_sell_sol on a mint with an open position
+// sells that position right away instead of on its schedule
+pub struct LargeSellExit {
+    min_sell_sol: u64,          // Lamports
+    requested: HashSet<String>, // Mints whose exit was already requested
+}
+
+impl LargeSellExit {
+    pub fn new(min_sell_sol: u64) -> Self {
+        Self {
+            min_sell_sol,
+            requested: HashSet::new(),
+        }
+    }
+
+    // Whether a sell worth sol_value lamports on a held mint triggers its exit. Each position is
+    // exited once, later large sells of the same mint are ignored until the position is gone.
+    pub fn on_sell(&mut self, mint: &str, sol_value: u64, held: bool) -> bool {
+        if !held {
+            self.requested.remove(mint);
+            return false;
+        }
+        sol_value >= self.min_sell_sol && self.requested.insert(mint.to_string())
+    }
+}
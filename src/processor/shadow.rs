@@ -0,0 +1,82 @@
+use lru::LruCache;
+use crate::processor::TokenReserves;
+use crate::processor::paper::PaperLedger;
+use crate::processor::strategy::{BuyObservation, Strategy};
+
+// A strategy with its own hypothetical trades
+struct Lane {
+    strategy: Box<dyn Strategy>,
+    ledger: PaperLedger,
+}
+
+// Feeds every observed event to several strategies and keeps their simulated trades apart,
+// so they can be compared on the same feed without trading
+pub struct ShadowRunner {
+    lanes: Vec<Lane>,
+    report_interval_slots: u64, // 0 = only report when finished
+    last_report_slot: u64,
+}
+
+impl ShadowRunner {
+    pub fn new(strategies: Vec<Box<dyn Strategy>>, report_interval_slots: u64, token_decimals: u8) -> Self {
+        let lanes = strategies
+            .into_iter()
+            .map(|strategy| Lane {
+                ledger: PaperLedger::new(strategy.name(), token_decimals),
+                strategy,
+            })
+            .collect();
+
+        Self {
+            lanes,
+            report_interval_slots,
+            last_report_slot: 0,
+        }
+    }
+
+    pub fn on_token_created(&mut self, mint: &str) {
+        for lane in &mut self.lanes {
+            lane.ledger.record_token(mint);
+        }
+    }
+
+    pub fn on_buy(&mut self, buy: &BuyObservation) {
+        for lane in &mut self.lanes {
+            if let Some(buy_sol) = lane.strategy.on_buy(buy) {
+                let hold_ms = lane.strategy.hold_ms();
+                lane.ledger.record_buy(buy.mint, buy_sol, buy.reserves, buy.slot, hold_ms);
+            }
+        }
+    }
+
+    // Sell due positions and print the periodic comparison
+    pub fn on_batch_end(&mut self, slot: u64, token_reserves: &LruCache<String, TokenReserves>) {
+        for lane in &mut self.lanes {
+            lane.ledger.close_due(slot, token_reserves);
+        }
+
+        if self.report_interval_slots > 0 && slot >= self.last_report_slot + self.report_interval_slots {
+            if self.last_report_slot > 0 {
+                self.report(slot, token_reserves);
+            }
+            self.last_report_slot = slot;
+        }
+    }
+
+    // Sell all remaining positions and print the final comparison
+    pub fn finish(&mut self, slot: u64, token_reserves: &LruCache<String, TokenReserves>) {
+        for lane in &mut self.lanes {
+            lane.ledger.close_all(token_reserves);
+        }
+        self.report(slot, token_reserves);
+    }
+
+    fn report(&self, slot: u64, token_reserves: &LruCache<String, TokenReserves>) {
+        println!("---------------------------");
+        println!("Strategy comparison at slot {}:", slot);
+        for lane in &self.lanes {
+            lane.ledger.print_summary(token_reserves);
+        }
+        println!("---------------------------");
+    }
+}
@@ -0,0 +1,72 @@
+use crate::config::Config;
+use crate::processor::TokenReserves;
+
+// A buy observed on the feed, as seen by strategies
+pub struct BuyObservation<'a> {
+    pub mint: &'a str,
+    pub sol_amount: u64,                 // lamports
+    pub reserves: Option<TokenReserves>, // Tracked reserves before the observed buy
+    pub slot: u64,
+}
+
+// Entry and exit decisions for observed buys
+pub trait Strategy: Send + Sync {
+    fn name(&self) -> &str;
+
+    // Lamports to invest in response to an observed buy, None to pass
+    fn on_buy(&mut self, buy: &BuyObservation) -> Option<u64>;
+
+    // How long a position is held before it is sold (milliseconds)
+    fn hold_ms(&self) -> u64;
+}
+
+// Buys a fixed amount when the observed buy size is within a range (the trader's behaviour)
+pub struct PriceRangeStrategy {
+    name: String,
+    min_sol_price: u64,
+    max_sol_price: u64,
+    buy_sol_amount: u64,
+    hold_ms: u64,
+}
+
+impl PriceRangeStrategy {
+    // The strategy the trader runs with the configured parameters
+    pub fn primary(config: &Config) -> Self {
+        Self {
+            name: "primary".to_string(),
+            min_sol_price: config.min_sol_price,
+            max_sol_price: config.max_sol_price,
+            buy_sol_amount: config.buy_sol_amount,
+            hold_ms: config.sell_delay_ms,
+        }
+    }
+
+    // The candidate strategy evaluated in shadow mode
+    pub fn candidate(config: &Config) -> Self {
+        Self {
+            name: "candidate".to_string(),
+            min_sol_price: config.shadow_min_sol_price,
+            max_sol_price: config.shadow_max_sol_price,
+            buy_sol_amount: config.shadow_buy_sol_amount,
+            hold_ms: config.shadow_sell_delay_ms,
+        }
+    }
+}
+
+impl Strategy for PriceRangeStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_buy(&mut self, buy: &BuyObservation) -> Option<u64> {
+        if buy.sol_amount >= self.min_sol_price && buy.sol_amount <= self.max_sol_price {
+            Some(self.buy_sol_amount)
+        } else {
+            None
+        }
+    }
+
+    fn hold_ms(&self) -> u64 {
+        self.hold_ms
+    }
+}
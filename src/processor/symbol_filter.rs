@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// How the symbols of new tokens are compared
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolMatch {
+    Exact, // Identical symbol
+    Fuzzy, // Same symbol ignoring case, punctuation and trailing digits ("$PEPE", "pepe2")
+}
+
+// How long a mint skipped as spam stays skipped, so its later buys are not sniped either
+const SKIPPED_MINT_RETENTION: Duration = Duration::from_secs(3600);
+
+// Anti-spam heuristic: skips new mints reusing a symbol seen within the window
+pub struct SymbolFilter {
+    window: Duration,
+    matching: SymbolMatch,
+    seen: HashMap<String, (Instant, String)>, // Symbol key -> (last seen, mint it was last seen with)
+    skipped_mints: HashMap<String, Instant>,
+}
+
+impl SymbolFilter {
+    pub fn new(window_ms: u64, matching: SymbolMatch) -> Self {
+        Self {
+            window: Duration::from_millis(window_ms),
+            matching,
+            seen: HashMap::new(),
+            skipped_mints: HashMap::new(),
+        }
+    }
+
+    fn key(&self, symbol: &str) -> String {
+        match self.matching {
+            SymbolMatch::Exact => symbol.trim().to_string(),
+            SymbolMatch::Fuzzy => symbol
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+                .trim_end_matches(|c: char| c.is_ascii_digit())
+                .to_string(),
+        }
+    }
+
+    // Record a new token; returns true if it reuses a recently seen symbol and should be skipped
+    pub fn on_token_created(&mut self, mint: &str, symbol: &str) -> bool {
+        let now = Instant::now();
+        let window = self.window;
+        self.seen.retain(|_, (seen_at, _)| now.duration_since(*seen_at) < window);
+        self.skipped_mints.retain(|_, skipped_at| now.duration_since(*skipped_at) < SKIPPED_MINT_RETENTION);
+
+        let key = self.key(symbol);
+        if key.is_empty() {
+            return false;
+        }
+
+        // Every reuse extends the window, so a steady spam batch stays filtered
+        match self.seen.insert(key, (now, mint.to_string())) {
+            Some((seen_at, previous_mint)) => {
+                println!("Skipping {} as likely spam: symbol {} reused {}ms after {}",
+                         mint, symbol, now.duration_since(seen_at).as_millis(), previous_mint);
+                self.skipped_mints.insert(mint.to_string(), now);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_skipped(&self, mint: &str) -> bool {
+        self.skipped_mints.contains_key(mint)
+    }
+}
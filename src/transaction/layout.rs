@@ -0,0 +1,378 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use solana_sdk::{instruction::AccountMeta, pubkey::Pubkey, system_program};
+
+use super::{EVENT_AUTHORITY, FEE_RECIPIENT, GLOBAL_ACCOUNT, PUMP_BUY_SELECTOR, PUMP_PROGRAM_ID, PUMP_SELL_SELECTOR, SYSVAR_RENT_PUBKEY};
+
+/// Largest encoded field, enough for u128 arguments
+const MAX_FIELD_SIZE: usize = 16;
+
+/// Layouts loaded from INSTRUCTION_LAYOUT_PATH, the built-in ones apply until then
+static LAYOUTS: OnceLock<InstructionLayouts> = OnceLock::new();
+
+/// Instruction data and account layouts of the buy and sell instructions
+///
+/// Loaded from a JSON file so a change of the program's instruction format can be followed
+/// without recompiling:
+///
+/// ```json
+/// {
+///   "buy":  { "selector": [82, 225, 119, 231, 78, 29, 45, 70],
+///             "fields": [{ "name": "amount", "size": 8 }, { "name": "max_sol_cost", "size": 8 }],
+///             "accounts": [{ "name": "global" }, { "name": "fee_recipient", "writable": true }, ...] },
+///   "sell": { "selector": [83, 225, 119, 231, 78, 29, 45, 70],
+///             "fields": [{ "name": "amount", "size": 8 }, { "name": "min_sol_output", "size": 8 }] }
+/// }
+/// ```
+///
+/// An instruction without `accounts` keeps its built-in account list.
+#[derive(Clone, Debug, Deserialize)]
+pub struct InstructionLayouts {
+    pub buy: InstructionLayout,
+    pub sell: InstructionLayout,
+}
+
+/// Selector followed by the fields in the order they are encoded, and the accounts in the order
+/// they are passed
+#[derive(Clone, Debug, Deserialize)]
+pub struct InstructionLayout {
+    pub selector: Vec<u8>,
+    pub fields: Vec<LayoutField>,
+    #[serde(default)]
+    pub accounts: Vec<LayoutAccount>,
+}
+
+/// An account of the instruction with its `AccountMeta` flags
+///
+/// `name` is one of the accounts of the trade (see [`TradeAccounts`]) or a program constant:
+/// `global`, `fee_recipient`, `system_program`, `token_program`, `associated_token_program`,
+/// `rent`, `event_authority`, `program`. Any other account needs an `address`, e.g. a newly
+/// required program account.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LayoutAccount {
+    pub name: String,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub writable: bool,
+    #[serde(default)]
+    pub signer: bool,
+}
+
+impl LayoutAccount {
+    fn new(name: &str, writable: bool, signer: bool) -> Self {
+        Self { name: name.to_string(), address: None, writable, signer }
+    }
+}
+
+/// The per-trade accounts an account layout refers to by name
+pub struct TradeAccounts {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub associated_bonding_curve: Pubkey,
+    pub associated_user: Pubkey,
+}
+
+impl TradeAccounts {
+    /// Resolves an account name of the layout, None if it is unknown
+    fn resolve(&self, name: &str) -> Option<Pubkey> {
+        Some(match name {
+            "user" => self.user,
+            "mint" => self.mint,
+            "bonding_curve" => self.bonding_curve,
+            "associated_bonding_curve" => self.associated_bonding_curve,
+            "associated_user" => self.associated_user,
+            _ => return constant_account(name),
+        })
+    }
+}
+
+/// Program constants accounts can be referred to by
+fn constant_account(name: &str) -> Option<Pubkey> {
+    Some(match name {
+        "global" => GLOBAL_ACCOUNT,
+        "fee_recipient" => FEE_RECIPIENT,
+        "system_program" => system_program::id(),
+        "token_program" => spl_token::id(),
+        "associated_token_program" => spl_associated_token_account::id(),
+        "rent" => SYSVAR_RENT_PUBKEY,
+        "event_authority" => EVENT_AUTHORITY,
+        "program" => PUMP_PROGRAM_ID,
+        _ => return None,
+    })
+}
+
+/// Names resolved per trade rather than from the constants
+const TRADE_ACCOUNT_NAMES: [&str; 5] = ["user", "mint", "bonding_curve", "associated_bonding_curve", "associated_user"];
+
+/// A little-endian field of the instruction data
+///
+/// Trade fields (`amount`, `max_sol_cost`, `min_sol_output`) take the value of the trade and
+/// are at least 8 bytes wide, wider fields are zero-extended. Any other field is a constant and
+/// needs a `value`, e.g. a newly added flag.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LayoutField {
+    pub name: String,
+    pub size: usize,
+    #[serde(default)]
+    pub value: Option<u64>,
+}
+
+/// Built-in accounts of the buy instruction
+fn builtin_buy_accounts() -> Vec<LayoutAccount> {
+    vec![
+        LayoutAccount::new("global", false, false),
+        LayoutAccount::new("fee_recipient", true, false),
+        LayoutAccount::new("mint", false, false),
+        LayoutAccount::new("bonding_curve", true, false),
+        LayoutAccount::new("associated_bonding_curve", true, false),
+        LayoutAccount::new("associated_user", true, false),
+        LayoutAccount::new("user", true, true),
+        LayoutAccount::new("system_program", false, false),
+        LayoutAccount::new("token_program", false, false),
+        LayoutAccount::new("rent", false, false),
+        LayoutAccount::new("event_authority", false, false),
+        LayoutAccount::new("program", false, false),
+    ]
+}
+
+/// Built-in accounts of the sell instruction
+fn builtin_sell_accounts() -> Vec<LayoutAccount> {
+    vec![
+        LayoutAccount::new("global", false, false),
+        LayoutAccount::new("fee_recipient", true, false),
+        LayoutAccount::new("mint", false, false),
+        LayoutAccount::new("bonding_curve", true, false),
+        LayoutAccount::new("associated_bonding_curve", true, false),
+        LayoutAccount::new("associated_user", true, false),
+        LayoutAccount::new("user", true, true),
+        LayoutAccount::new("system_program", false, false),
+        LayoutAccount::new("associated_token_program", false, false),
+        LayoutAccount::new("token_program", false, false),
+        LayoutAccount::new("event_authority", false, false),
+        LayoutAccount::new("program", false, false),
+    ]
+}
+
+impl InstructionLayout {
+    fn builtin(selector: &[u8; 8], limit_field: &str, accounts: Vec<LayoutAccount>) -> Self {
+        Self {
+            selector: selector.to_vec(),
+            fields: vec![
+                LayoutField { name: "amount".to_string(), size: 8, value: None },
+                LayoutField { name: limit_field.to_string(), size: 8, value: None },
+            ],
+            accounts,
+        }
+    }
+
+    /// Checks that every account resolves and the user signs the instruction
+    fn validate_accounts(&self, instruction: &str) -> Result<(), String> {
+        for account in &self.accounts {
+            match &account.address {
+                Some(address) => {
+                    Pubkey::from_str(address)
+                        .map_err(|e| format!("{}: account {} has an invalid address {}: {}", instruction, account.name, address, e))?;
+                }
+                None => {
+                    if !TRADE_ACCOUNT_NAMES.contains(&account.name.as_str()) && constant_account(&account.name).is_none() {
+                        return Err(format!("{}: unknown account {}, accounts other than the built-in ones need an address", instruction, account.name));
+                    }
+                }
+            }
+        }
+        if !self.accounts.iter().any(|account| account.name == "user" && account.address.is_none() && account.signer) {
+            return Err(format!("{}: the user account must be included as a signer", instruction));
+        }
+        Ok(())
+    }
+
+    /// Builds the account list for a trade
+    fn account_metas(&self, trade: &TradeAccounts) -> Vec<AccountMeta> {
+        self.accounts
+            .iter()
+            .map(|account| {
+                // Validated on load, the built-in layouts only use known names
+                let pubkey = match &account.address {
+                    Some(address) => Pubkey::from_str(address).unwrap_or_default(),
+                    None => trade.resolve(&account.name).unwrap_or_default(),
+                };
+                if account.writable {
+                    AccountMeta::new(pubkey, account.signer)
+                } else {
+                    AccountMeta::new_readonly(pubkey, account.signer)
+                }
+            })
+            .collect()
+    }
+
+    /// Checks that the layout can encode every trade value without truncation
+    fn validate(&self, instruction: &str, trade_fields: &[&str]) -> Result<(), String> {
+        if self.selector.is_empty() {
+            return Err(format!("{}: empty selector", instruction));
+        }
+        for required in trade_fields {
+            if !self.fields.iter().any(|field| field.name == *required) {
+                return Err(format!("{}: missing field {}", instruction, required));
+            }
+        }
+        for field in &self.fields {
+            if field.size == 0 || field.size > MAX_FIELD_SIZE {
+                return Err(format!("{}: field {} has size {}, expected 1-{}", instruction, field.name, field.size, MAX_FIELD_SIZE));
+            }
+            if trade_fields.contains(&field.name.as_str()) {
+                if field.size < 8 {
+                    return Err(format!("{}: field {} must be at least 8 bytes", instruction, field.name));
+                }
+            } else {
+                let value = field
+                    .value
+                    .ok_or_else(|| format!("{}: constant field {} needs a value", instruction, field.name))?;
+                if field.size < 8 && value >> (field.size * 8) != 0 {
+                    return Err(format!("{}: value {} of field {} does not fit in {} bytes", instruction, value, field.name, field.size));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes the instruction data, taking trade field values from `values`
+    fn encode(&self, values: &[(&str, u64)]) -> Vec<u8> {
+        let size = self.selector.len() + self.fields.iter().map(|field| field.size).sum::<usize>();
+        let mut data = Vec::with_capacity(size);
+        data.extend_from_slice(&self.selector);
+        for field in &self.fields {
+            let value = values
+                .iter()
+                .find(|(name, _)| *name == field.name)
+                .map(|(_, value)| *value)
+                .or(field.value)
+                .unwrap_or_default();
+            data.extend_from_slice(&(value as u128).to_le_bytes()[..field.size]);
+        }
+        data
+    }
+}
+
+impl Default for InstructionLayouts {
+    fn default() -> Self {
+        Self {
+            buy: InstructionLayout::builtin(PUMP_BUY_SELECTOR, "max_sol_cost", builtin_buy_accounts()),
+            sell: InstructionLayout::builtin(PUMP_SELL_SELECTOR, "min_sol_output", builtin_sell_accounts()),
+        }
+    }
+}
+
+/// Loads the buy and sell layouts from a JSON file; must be called before the first trade
+pub fn load_instruction_layouts(path: &str) -> Result<(), String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let mut layouts: InstructionLayouts =
+        serde_json::from_str(&contents).map_err(|e| format!("Invalid instruction layout file {}: {}", path, e))?;
+
+    if layouts.buy.accounts.is_empty() {
+        layouts.buy.accounts = builtin_buy_accounts();
+    }
+    if layouts.sell.accounts.is_empty() {
+        layouts.sell.accounts = builtin_sell_accounts();
+    }
+
+    layouts.buy.validate("buy", &["amount", "max_sol_cost"])?;
+    layouts.buy.validate_accounts("buy")?;
+    layouts.sell.validate("sell", &["amount", "min_sol_output"])?;
+    layouts.sell.validate_accounts("sell")?;
+
+    LAYOUTS
+        .set(layouts)
+        .map_err(|_| "Instruction layouts already in use".to_string())
+}
+
+fn layouts() -> &'static InstructionLayouts {
+    LAYOUTS.get_or_init(InstructionLayouts::default)
+}
+
+/// Encodes the data of a buy instruction
+pub fn encode_buy_data(token_amount: u64, max_sol_cost: u64) -> Vec<u8> {
+    layouts().buy.encode(&[("amount", token_amount), ("max_sol_cost", max_sol_cost)])
+}
+
+/// Encodes the data of a sell instruction
+pub fn encode_sell_data(token_amount: u64, min_sol_output: u64) -> Vec<u8> {
+    layouts().sell.encode(&[("amount", token_amount), ("min_sol_output", min_sol_output)])
+}
+
+/// Accounts of a buy instruction, in the configured layout
+pub fn buy_account_metas(trade: &TradeAccounts) -> Vec<AccountMeta> {
+    layouts().buy.account_metas(trade)
+}
+
+/// Accounts of a sell instruction, in the configured layout
+pub fn sell_account_metas(trade: &TradeAccounts) -> Vec<AccountMeta> {
+    layouts().sell.account_metas(trade)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Buy layout as it would be read from INSTRUCTION_LAYOUT_PATH, with a constant flag appended
+    fn buy_layout(fields: &str, accounts: &str) -> InstructionLayout {
+        let json = format!(
+            r#"{{ "selector": [82, 225, 119, 231, 78, 29, 45, 70], "fields": {}, "accounts": {} }}"#,
+            fields, accounts
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    const BUY_FIELDS: &str = r#"[{ "name": "amount", "size": 8 }, { "name": "max_sol_cost", "size": 8 }, { "name": "track_volume", "size": 1, "value": 1 }]"#;
+
+    #[test]
+    fn valid_layouts_pass_validation() {
+        let layout = buy_layout(BUY_FIELDS, r#"[{ "name": "global" }, { "name": "mint" }, { "name": "user", "writable": true, "signer": true },
+            { "name": "volume_accumulator", "address": "11111111111111111111111111111111", "writable": true }]"#);
+        assert_eq!(layout.validate("buy", &["amount", "max_sol_cost"]), Ok(()));
+        assert_eq!(layout.validate_accounts("buy"), Ok(()));
+
+        let builtin = InstructionLayouts::default();
+        assert_eq!(builtin.buy.validate("buy", &["amount", "max_sol_cost"]), Ok(()));
+        assert_eq!(builtin.buy.validate_accounts("buy"), Ok(()));
+        assert_eq!(builtin.sell.validate("sell", &["amount", "min_sol_output"]), Ok(()));
+        assert_eq!(builtin.sell.validate_accounts("sell"), Ok(()));
+    }
+
+    #[test]
+    fn oversize_fields_are_rejected() {
+        let layout = buy_layout(r#"[{ "name": "amount", "size": 17 }, { "name": "max_sol_cost", "size": 8 }]"#, "[]");
+        let error = layout.validate("buy", &["amount", "max_sol_cost"]).unwrap_err();
+        assert!(error.contains("field amount has size 17"), "{}", error);
+    }
+
+    #[test]
+    fn the_user_must_sign() {
+        let layout = buy_layout(BUY_FIELDS, r#"[{ "name": "global" }, { "name": "user", "writable": true }]"#);
+        let error = layout.validate_accounts("buy").unwrap_err();
+        assert!(error.contains("user account must be included as a signer"), "{}", error);
+
+        // An account named user at a fixed address is not the trading wallet
+        let layout = buy_layout(BUY_FIELDS, r#"[{ "name": "user", "address": "11111111111111111111111111111111", "signer": true }]"#);
+        assert!(layout.validate_accounts("buy").is_err());
+    }
+
+    // The built-in buy layout encodes the selector followed by the little-endian amounts
+    #[test]
+    fn builtin_buy_data_matches_the_hardcoded_layout() {
+        let data = InstructionLayouts::default().buy.encode(&[("amount", 1_234_567), ("max_sol_cost", 890_000_000)]);
+
+        let mut expected = PUMP_BUY_SELECTOR.to_vec();
+        expected.extend_from_slice(&1_234_567u64.to_le_bytes());
+        expected.extend_from_slice(&890_000_000u64.to_le_bytes());
+        assert_eq!(data, expected);
+
+        // Constant fields follow with their value, truncated to their size
+        let data = buy_layout(BUY_FIELDS, "[]").encode(&[("amount", 1_234_567), ("max_sol_cost", 890_000_000)]);
+        expected.push(1);
+        assert_eq!(data, expected);
+    }
+}
@@ -1,17 +1,41 @@
-use std::fmt::Error;
+pub mod layout;
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
 
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
-use solana_rpc_client_api::config::RpcSendTransactionConfig;
+use solana_rpc_client_api::config::{
+    RpcSendTransactionConfig, RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+    RpcTransactionConfig,
+};
+use solana_transaction_status::{option_serializer::OptionSerializer, UiTransactionEncoding};
+use solana_account_decoder::UiAccountEncoding;
 use solana_sdk::{
+    account::Account,
     commitment_config::{CommitmentConfig, CommitmentLevel},
     hash::Hash,
     instruction::{AccountMeta, Instruction},
+    message::VersionedMessage,
+    packet::PACKET_DATA_SIZE,
     pubkey::Pubkey,
+    signature::{Keypair, Signature},
     signer::Signer,
+    system_instruction,
     system_program,
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 use spl_associated_token_account::get_associated_token_address;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use tokio::time::{sleep, Duration, Instant};
+use lru::LruCache;
+use tracing::{info_span, Instrument};
+
+use crate::error::SniperError;
+use layout::{buy_account_metas, encode_buy_data, encode_sell_data, sell_account_metas, TradeAccounts};
+use crate::utils::bonding_curve::BondingCurveState;
+use crate::utils::keypair::parse_keypair;
+use crate::utils::{account_key_logging_enabled, log_account_keys};
 
 // Pump protocol related constants
 pub const GLOBAL_ACCOUNT: Pubkey =
@@ -23,101 +47,380 @@ pub const PUMP_PROGRAM_ID: Pubkey =
     solana_sdk::pubkey!("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P");
 pub const PROXY_PROGRAM: Pubkey = solana_sdk::pubkey!("AmXoSVCLjsfKrwCUqvkMFXYcDzZ4FeoMYs7SAhGyfMGy");
 
+// Jito tip account and SPL memo program
+pub const JITO_TIP_ACCOUNT: Pubkey = solana_sdk::pubkey!("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5");
+pub const MEMO_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
 // System accounts
 pub const SYSVAR_RENT_PUBKEY: Pubkey = solana_sdk::pubkey!("SysvarRent111111111111111111111111111111111");
 
-// Instruction discriminators
+// Instruction discriminators, the defaults of the buy/sell layouts (see layout.rs)
 pub const PUMP_BUY_SELECTOR: &[u8; 8] = &[82, 225, 119, 231, 78, 29, 45, 70]; // Internal buy discriminator
 pub const PUMP_SELL_SELECTOR: &[u8; 8] = &[83, 225, 119, 231, 78, 29, 45, 70]; // Internal sell discriminator
 pub const ATA_SELECTOR: &[u8; 8] = &[22, 51, 53, 97, 247, 184, 54, 78]; // Create ATA discriminator
 
 const BONDING_CURVE_SEED: &[u8] = b"bonding-curve";
 
-/// Pump protocol token buy transaction
-///
-/// # Arguments
-///
-/// * `rpc_url` - RPC node URL
-/// * `private_key` - User's private key
-/// * `token_mint` - Token Mint address
-/// * `token_amount` - Amount of tokens to buy
-/// * `max_sol_cost` - Maximum SOL cost (in lamports)
-/// * `slot` - Optional slot number for logging
-/// * `cached_blockhash` - Optional cached blockhash, if provided, RPC will not be queried
-pub async fn pump_buy(
-    rpc_url: &str,
-    private_key: &str,
-    token_mint: Pubkey,
-    token_amount: u64,
-    max_sol_cost: u64,
-    slot: Option<u64>,
-    cached_blockhash: Option<Hash>,
-) -> Result<String, Error> {
-    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
+// Compute unit limit used until the real usage of a path has been measured
+pub const DEFAULT_COMPUTE_UNIT_LIMIT: u32 = 200000;
+// Headroom added on top of simulated compute unit usage
+const COMPUTE_UNIT_MARGIN_PERCENT: u64 = 20;
+// Maximum compute units a transaction may request
+const MAX_COMPUTE_UNIT_LIMIT: u64 = 1_400_000;
 
-    // Construct buy instruction data
-    let mut data = Vec::with_capacity(24);
-    data.extend_from_slice(PUMP_BUY_SELECTOR);
-    data.extend_from_slice(&token_amount.to_le_bytes());
-    data.extend_from_slice(&max_sol_cost.to_le_bytes());
+// Default priority fee of buys and sells (micro-lamports per compute unit)
+pub const DEFAULT_COMPUTE_UNIT_PRICE: u64 = 200000;
+// Compute unit limit of sells
+const SELL_COMPUTE_UNIT_LIMIT: u32 = 200000;
+// Base fee per signature (lamports)
+const BASE_FEE_LAMPORTS: u64 = 5000;
+// Rent-exempt minimum of a 165 byte token account (lamports)
+pub(crate) const TOKEN_ACCOUNT_RENT_LAMPORTS: u64 = 2_039_280;
 
-    let signer = solana_sdk::signature::Keypair::from_base58_string(private_key);
+// Learned compute unit limits for the ATA+buy and bare buy paths (0 = not measured yet)
+static ATA_BUY_COMPUTE_UNITS: AtomicU32 = AtomicU32::new(0);
+static BUY_COMPUTE_UNITS: AtomicU32 = AtomicU32::new(0);
 
-    // Calculate Bonding Curve address
-    let bonding_curve_address =
-        Pubkey::find_program_address(&[BONDING_CURVE_SEED, token_mint.as_ref()], &PUMP_PROGRAM_ID);
+// Nominal buy used when simulating against a fresh curve: 1 token, at most 0.01 SOL
+const NOMINAL_BUY_TOKEN_AMOUNT: u64 = 1_000_000;
+const NOMINAL_BUY_MAX_SOL_COST: u64 = 10_000_000;
 
-    // User's associated token account
-    let associated_user = get_associated_token_address(&signer.pubkey(), &token_mint);
+// Interval between signature status polls while waiting for a commitment level
+const SIGNATURE_STATUS_POLL_MS: u64 = 200;
+
+/// Result of waiting for a transaction to reach a commitment level
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfirmationStatus {
+    Confirmed,                  // Reached the requested commitment
+    Failed(TransactionError),   // Landed or was rejected with this error
+    TimedOut,                   // Not seen at the requested commitment before the deadline
+}
+
+/// Options applied when sending transactions
+#[derive(Clone, Debug)]
+pub struct TransactionOptions {
+    /// Treat "already processed" send errors as success, since a copy of the transaction landed
+    pub duplicate_as_success: bool,
+    /// How the associated token account is created on buys that include it
+    pub ata_mode: AtaMode,
+    /// How often the RPC node rebroadcasts a sent transaction. Retries improve the landing rate of
+    /// non-bundle sends, but the final result is known later; 0 reports the outcome immediately
+    pub max_retries: usize,
+    /// Durable nonce account advanced by buys, whose nonce is then passed as the blockhash
+    pub nonce_account: Option<Pubkey>,
+    /// Priority fee of buys (micro-lamports per compute unit)
+    pub buy_compute_unit_price: u64,
+    /// Priority fee of sells (micro-lamports per compute unit); exits are usually less contested
+    pub sell_compute_unit_price: u64,
+    /// Build and log sells without sending them, returning the signature they would have
+    pub sell_dry_run: bool,
+    /// Send buys as Jito bundles instead of to the RPC node, None = RPC node
+    pub jito_bundle: Option<JitoBundle>,
+}
+
+/// Jito bundle submission of buys
+#[derive(Clone, Debug)]
+pub struct JitoBundle {
+    /// Block engine bundles endpoint (e.g. https://mainnet.block-engine.jito.wtf/api/v1/bundles)
+    pub url: String,
+    /// Tip paid to the Jito tip account by every bundled buy transaction
+    pub tip_lamports: u64,
+}
+
+impl Default for TransactionOptions {
+    fn default() -> Self {
+        Self {
+            duplicate_as_success: true,
+            ata_mode: AtaMode::default(),
+            max_retries: 0,
+            nonce_account: None,
+            buy_compute_unit_price: DEFAULT_COMPUTE_UNIT_PRICE,
+            sell_compute_unit_price: DEFAULT_COMPUTE_UNIT_PRICE,
+            sell_dry_run: false,
+            jito_bundle: None,
+        }
+    }
+}
+
+/// Bonding curve of a token and the curve's associated token account
+#[derive(Clone, Copy, Debug)]
+pub struct CurveAddresses {
+    pub bonding_curve: Pubkey,
+    pub associated_bonding_curve: Pubkey,
+}
+
+// Derived curve addresses of recently traded mints, None when caching is disabled
+static CURVE_ADDRESS_CACHE: Mutex<Option<LruCache<Pubkey, CurveAddresses>>> = Mutex::new(None);
+
+/// Caches the derived curve addresses of up to `size` mints, so a mint that is bought and sold
+/// runs the PDA bump search once; 0 disables the cache
+pub fn set_curve_address_cache_size(size: usize) {
+    let cache = NonZeroUsize::new(size).map(LruCache::new);
+    *CURVE_ADDRESS_CACHE.lock().unwrap_or_else(|e| e.into_inner()) = cache;
+}
+
+/// Derives the bonding curve address of a token
+pub fn derive_bonding_curve(token_mint: &Pubkey) -> Pubkey {
+    curve_addresses(token_mint).bonding_curve
+}
+
+/// Derives the bonding curve and its associated token account of a token, from the cache if enabled
+pub fn curve_addresses(token_mint: &Pubkey) -> CurveAddresses {
+    let mut cache = CURVE_ADDRESS_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(addresses) = cache.as_mut().and_then(|cache| cache.get(token_mint)) {
+        return *addresses;
+    }
+
+    let bonding_curve = Pubkey::find_program_address(&[BONDING_CURVE_SEED, token_mint.as_ref()], &PUMP_PROGRAM_ID).0;
+    let addresses = CurveAddresses {
+        bonding_curve,
+        associated_bonding_curve: get_associated_token_address(&bonding_curve, token_mint),
+    };
+    if let Some(cache) = cache.as_mut() {
+        cache.put(*token_mint, addresses);
+    }
+    addresses
+}
+
+/// Lamports a buy spends besides max_sol_cost: base fee, priority fee at `unit_price` and, when
+/// the token account is created in the same transaction, its rent
+pub fn buy_fee_reserve(include_ata: bool, unit_price: u64) -> u64 {
+    let priority_fee = (unit_price as u128 * buy_compute_unit_limit(include_ata) as u128).div_ceil(1_000_000);
+    let priority_fee = u64::try_from(priority_fee).unwrap_or(u64::MAX);
+    let rent = if include_ata { TOKEN_ACCOUNT_RENT_LAMPORTS } else { 0 };
+
+    BASE_FEE_LAMPORTS.saturating_add(priority_fee).saturating_add(rent)
+}
+
+/// Returns the compute unit limit for a buy, using the learned value for the path if available
+fn buy_compute_unit_limit(include_ata: bool) -> u32 {
+    let learned = if include_ata {
+        ATA_BUY_COMPUTE_UNITS.load(Ordering::Relaxed)
+    } else {
+        BUY_COMPUTE_UNITS.load(Ordering::Relaxed)
+    };
+
+    if learned > 0 { learned } else { DEFAULT_COMPUTE_UNIT_LIMIT }
+}
+
+/// Builds the proxy program buy instruction for a token
+fn build_buy_instruction(user: &Pubkey, token_mint: &Pubkey, token_amount: u64, max_sol_cost: u64) -> Instruction {
+    // Construct buy instruction data, in the configured layout
+    let data = encode_buy_data(token_amount, max_sol_cost);
 
-    // Bonding Curve's associated token account
-    let associated_bonding_curve =
-        get_associated_token_address(&bonding_curve_address.0, &token_mint);
+    // Bonding Curve address and its associated token account
+    let CurveAddresses { bonding_curve, associated_bonding_curve } = curve_addresses(token_mint);
 
-    // Construct buy instruction
-    let buy_instruction = Instruction::new_with_bytes(
+    // User's associated token account
+    let associated_user = get_associated_token_address(user, token_mint);
+
+    Instruction::new_with_bytes(
         PROXY_PROGRAM,
         &data,
-        vec![
-            AccountMeta::new_readonly(GLOBAL_ACCOUNT, false),
-            AccountMeta::new(FEE_RECIPIENT, false),
-            AccountMeta::new_readonly(token_mint, false),
-            AccountMeta::new(bonding_curve_address.0, false),
-            AccountMeta::new(associated_bonding_curve, false),
-            AccountMeta::new(associated_user, false),
-            AccountMeta::new(signer.pubkey(), true),
-            AccountMeta::new_readonly(system_program::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-            AccountMeta::new_readonly(SYSVAR_RENT_PUBKEY, false), // Correct Rent Sysvar address
-            AccountMeta::new_readonly(EVENT_AUTHORITY, false),
-            AccountMeta::new_readonly(PUMP_PROGRAM_ID, false),
-        ],
-    );
+        buy_account_metas(&TradeAccounts {
+            user: *user,
+            mint: *token_mint,
+            bonding_curve,
+            associated_bonding_curve,
+            associated_user,
+        }),
+    )
+}
+
+/// How the user's associated token account is created in a buy transaction
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AtaMode {
+    /// Proxy program ATA instruction
+    #[default]
+    Proxy,
+    /// Standard idempotent create, succeeds even if the account already exists
+    Idempotent,
+}
+
+/// Builds the instruction that creates the user's associated token account
+fn build_ata_instruction(user: &Pubkey, token_mint: &Pubkey, mode: AtaMode) -> Instruction {
+    if mode == AtaMode::Idempotent {
+        return spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            user,
+            user,
+            token_mint,
+            &spl_token::id(),
+        );
+    }
 
     // Create ATA instruction data
     let mut ata_data = Vec::with_capacity(9);
     ata_data.extend_from_slice(ATA_SELECTOR);
     ata_data.extend_from_slice(&[0]);
 
-    // Construct create ATA instruction
-    let ata_instruction = Instruction::new_with_bytes(
+    // User's associated token account
+    let associated_user = get_associated_token_address(user, token_mint);
+
+    Instruction::new_with_bytes(
         PROXY_PROGRAM,
         &ata_data,
         vec![
-            AccountMeta::new(signer.pubkey(), true),
+            AccountMeta::new(*user, true),
             AccountMeta::new(associated_user, false),
-            AccountMeta::new_readonly(token_mint, false),
+            AccountMeta::new_readonly(*token_mint, false),
             AccountMeta::new_readonly(system_program::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
             AccountMeta::new_readonly(spl_associated_token_account::id(), false),
         ],
-    );
+    )
+}
 
-    // Add priority fee instructions - Increase priority fee to 200000 for faster processing
-    let compute_unit_price_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(200000);
+/// Builder for buy transactions
+///
+/// Instructions are always emitted in the same order regardless of the order the
+/// builder methods are called in: nonce advance, compute budget, ATA creation, buy, memo, Jito tip.
+pub struct TransactionBuilder<'a> {
+    signer: &'a Keypair,
+    token_mint: Pubkey,
+    compute_budget: Option<(u64, u32)>, // (unit price in micro-lamports, unit limit)
+    create_ata: Option<AtaMode>,
+    buy: Option<(u64, u64)>,            // (token amount, max SOL cost)
+    memo: Option<String>,
+    jito_tip_lamports: Option<u64>,
+    nonce_account: Option<Pubkey>,
+}
 
-    // Increase maximum compute units to ensure the transaction doesn't fail due to insufficient compute resources
-    let compute_unit_limit_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(200000);
+impl<'a> TransactionBuilder<'a> {
+    /// Creates a builder for a transaction paid for and signed by `signer`
+    pub fn new(signer: &'a Keypair, token_mint: Pubkey) -> Self {
+        Self {
+            signer,
+            token_mint,
+            compute_budget: None,
+            create_ata: None,
+            buy: None,
+            memo: None,
+            jito_tip_lamports: None,
+            nonce_account: None,
+        }
+    }
+
+    /// Advances a durable nonce account of the signer; the transaction must then be built with
+    /// the nonce as its blockhash
+    pub fn with_nonce_advance(mut self, nonce_account: Pubkey) -> Self {
+        self.nonce_account = Some(nonce_account);
+        self
+    }
+
+    /// Sets the compute unit price (micro-lamports) and compute unit limit
+    pub fn with_compute_budget(mut self, unit_price: u64, unit_limit: u32) -> Self {
+        self.compute_budget = Some((unit_price, unit_limit));
+        self
+    }
+
+    /// Creates the signer's associated token account for the mint before buying
+    pub fn with_ata_creation(mut self, mode: AtaMode) -> Self {
+        self.create_ata = Some(mode);
+        self
+    }
+
+    /// Buys `token_amount` tokens for at most `max_sol_cost` lamports
+    pub fn with_buy(mut self, token_amount: u64, max_sol_cost: u64) -> Self {
+        self.buy = Some((token_amount, max_sol_cost));
+        self
+    }
+
+    /// Tips the Jito tip account
+    pub fn with_jito_tip(mut self, lamports: u64) -> Self {
+        self.jito_tip_lamports = Some(lamports);
+        self
+    }
+
+    /// Attaches a memo to the transaction
+    pub fn with_memo(mut self, text: &str) -> Self {
+        self.memo = Some(text.to_string());
+        self
+    }
+
+    /// Returns the instructions in submission order
+    pub fn instructions(&self) -> Vec<Instruction> {
+        let user = self.signer.pubkey();
+        let mut instructions = Vec::with_capacity(7);
+
+        // The runtime only accepts a durable nonce advance as the first instruction
+        if let Some(nonce_account) = self.nonce_account {
+            instructions.push(system_instruction::advance_nonce_account(&nonce_account, &user));
+        }
+        if let Some((unit_price, unit_limit)) = self.compute_budget {
+            instructions.push(solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(unit_price));
+            instructions.push(solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(unit_limit));
+        }
+        if let Some(mode) = self.create_ata {
+            instructions.push(build_ata_instruction(&user, &self.token_mint, mode));
+        }
+        if let Some((token_amount, max_sol_cost)) = self.buy {
+            instructions.push(build_buy_instruction(&user, &self.token_mint, token_amount, max_sol_cost));
+        }
+        if let Some(memo) = &self.memo {
+            instructions.push(Instruction::new_with_bytes(MEMO_PROGRAM_ID, memo.as_bytes(), vec![]));
+        }
+        if let Some(lamports) = self.jito_tip_lamports {
+            instructions.push(system_instruction::transfer(&user, &JITO_TIP_ACCOUNT, lamports));
+        }
+
+        instructions
+    }
+
+    /// Builds and signs the transaction
+    pub fn build(&self, blockhash: Hash) -> Transaction {
+        Transaction::new_signed_with_payer(
+            &self.instructions(),
+            Some(&self.signer.pubkey()),
+            &[self.signer],
+            blockhash,
+        )
+    }
+}
+
+/// Pump protocol token buy transaction
+///
+/// # Arguments
+///
+/// * `rpc_url` - RPC node URL
+/// * `private_key` - User's private key
+/// * `token_mint` - Token Mint address
+/// * `token_amount` - Amount of tokens to buy
+/// * `max_sol_cost` - Maximum SOL cost (in lamports)
+/// * `include_ata` - Whether to create the user's associated token account in the same transaction
+/// * `slot` - Optional slot number for logging
+/// * `cached_blockhash` - Optional cached blockhash, if provided, RPC will not be queried
+/// * `options` - Send options
+#[allow(clippy::too_many_arguments)]
+pub async fn pump_buy(
+    rpc_url: &str,
+    private_key: &str,
+    token_mint: Pubkey,
+    token_amount: u64,
+    max_sol_cost: u64,
+    include_ata: bool,
+    slot: Option<u64>,
+    cached_blockhash: Option<Hash>,
+    options: &TransactionOptions,
+) -> Result<SentBuy, SniperError> {
+    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
+
+    let signer = parse_keypair(private_key).map_err(SniperError::InvalidKey)?;
+
+    // Buy priority fee, with the compute unit limit sized for the path being sent (learned value
+    // once measured)
+    let mut builder = TransactionBuilder::new(&signer, token_mint)
+        .with_compute_budget(options.buy_compute_unit_price, buy_compute_unit_limit(include_ata));
+    if include_ata {
+        builder = builder.with_ata_creation(options.ata_mode);
+    }
+    if let Some(nonce_account) = options.nonce_account {
+        builder = builder.with_nonce_advance(nonce_account);
+    }
+    let mut builder = builder.with_buy(token_amount, max_sol_cost);
+    // Bundled buys pay the tip in the same transaction, after the buy
+    if let Some(bundle) = &options.jito_bundle {
+        builder = builder.with_jito_tip(bundle.tip_lamports);
+    }
 
     // Get blockhash
     let blockhash = if let Some(hash) = cached_blockhash {
@@ -138,27 +441,66 @@ pub async fn pump_buy(
             .get_latest_blockhash_with_commitment(CommitmentConfig {
                 commitment: CommitmentLevel::Confirmed,
             })
-            .await
-            .unwrap()
+            .await?
             .0
     };
 
     // Create transaction
-    let transaction = Transaction::new_signed_with_payer(
-        &[compute_unit_price_ix, compute_unit_limit_ix, ata_instruction, buy_instruction], // Add two priority instructions
-        Some(&signer.pubkey()),
-        &[&signer],
-        blockhash,
-    );
+    let transaction = info_span!("transaction_build").in_scope(|| builder.build(blockhash));
 
-    // Send transaction - Use optimal transaction settings
+    if account_key_logging_enabled() {
+        log_account_keys("pump_buy", &VersionedMessage::Legacy(transaction.message.clone()));
+    }
+
+    send_buy(&rpc_client, &transaction, options)
+        .instrument(info_span!("send"))
+        .await
+}
+
+/// A buy transaction accepted by the RPC node or the Jito block engine
+#[derive(Clone, Debug)]
+pub struct SentBuy {
+    pub signature: String,
+    /// Bundle the transaction was submitted in, None when sent to the RPC node
+    pub bundle_id: Option<String>,
+    /// When the signed transaction was handed to the send, i.e. when building it ended
+    pub built_at: std::time::Instant,
+}
+
+/// Sends a signed buy transaction as a Jito bundle when bundles are enabled, otherwise to the RPC
+/// node. A bundle is only submitted, see `wait_for_bundle` for its landing.
+async fn send_buy(
+    rpc_client: &RpcClient,
+    transaction: &Transaction,
+    options: &TransactionOptions,
+) -> Result<SentBuy, SniperError> {
+    let built_at = std::time::Instant::now();
+    match &options.jito_bundle {
+        Some(bundle) => {
+            let bundle_id = submit_bundle(&reqwest::Client::new(), &bundle.url, transaction).await?;
+            Ok(SentBuy { signature: transaction.signatures[0].to_string(), bundle_id: Some(bundle_id), built_at })
+        }
+        None => {
+            let signature = send_buy_transaction(rpc_client, transaction, options).await?;
+            Ok(SentBuy { signature, bundle_id: None, built_at })
+        }
+    }
+}
+
+/// Sends a signed buy transaction without preflight or retries
+async fn send_buy_transaction(
+    rpc_client: &RpcClient,
+    transaction: &Transaction,
+    options: &TransactionOptions,
+) -> Result<String, SniperError> {
+    // Use optimal transaction settings
     match rpc_client
         .send_transaction_with_config(
-            &transaction,
+            transaction,
             RpcSendTransactionConfig {
                 skip_preflight: true,
                 preflight_commitment: Some(CommitmentLevel::Processed), // Use Processed level for fastest return
-                max_retries: Some(0), // No retries, as we need to know the result immediately
+                max_retries: Some(options.max_retries), // 0 = no retries, as we need to know the result immediately
                 ..Default::default()
             },
         )
@@ -168,11 +510,349 @@ pub async fn pump_buy(
             println!("Buy transaction submitted: {}", signature);
             Ok(signature.to_string())
         }
-        Err(e) => {
-            println!("Buy transaction failed: {:?}", e);
-            Err(Error)
+        Err(e) => match SniperError::from(e) {
+            // A copy of this exact transaction already landed
+            error if error.is_duplicate_signature() && options.duplicate_as_success => {
+                println!("Buy transaction already processed, treating as submitted: {}", transaction.signatures[0]);
+                Ok(transaction.signatures[0].to_string())
+            }
+            error => {
+                println!("Buy transaction failed: {:?}", error);
+                Err(error)
+            }
+        },
+    }
+}
+
+// Maximum wait for the startup self-test transaction to be confirmed
+const SELF_TEST_TIMEOUT_MS: u64 = 30_000;
+
+// Polling of a submitted Jito bundle until it lands
+const BUNDLE_STATUS_POLL_MS: u64 = 400;
+const BUNDLE_STATUS_TIMEOUT_MS: u64 = 5_000;
+
+/// Sends a JSON-RPC request to the Jito block engine and returns its `result`
+async fn block_engine_request(
+    http_client: &reqwest::Client,
+    bundle_url: &str,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, SniperError> {
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    });
+
+    let response: serde_json::Value = http_client
+        .post(bundle_url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| SniperError::Other(format!("{} request failed: {}", method, e)))?
+        .json()
+        .await
+        .map_err(|e| SniperError::Other(format!("Invalid {} response: {}", method, e)))?;
+
+    if let Some(error) = response.get("error") {
+        return Err(SniperError::Other(format!("{} failed: {}", method, error)));
+    }
+    Ok(response.get("result").cloned().unwrap_or(serde_json::Value::Null))
+}
+
+/// Outcome of a landed Jito bundle
+#[derive(Clone, Debug)]
+pub struct BundleResult {
+    pub bundle_id: String,
+    /// Slot the bundle landed in, None if the block engine did not report it
+    pub landed_slot: Option<u64>,
+}
+
+/// Submits a signed transaction, which pays its own tip, as a single-transaction Jito bundle and
+/// returns the bundle id
+async fn submit_bundle(
+    http_client: &reqwest::Client,
+    bundle_url: &str,
+    transaction: &Transaction,
+) -> Result<String, SniperError> {
+    let serialized = bincode::serialize(transaction)
+        .map_err(|e| SniperError::Other(format!("Failed to serialize bundle transaction: {}", e)))?;
+    let bundle_id = block_engine_request(
+        http_client,
+        bundle_url,
+        "sendBundle",
+        serde_json::json!([[BASE64.encode(serialized)], { "encoding": "base64" }]),
+    )
+    .await?
+    .as_str()
+    .map(str::to_string)
+    .ok_or_else(|| SniperError::Other("sendBundle returned no bundle id".to_string()))?;
+    println!("Buy bundle submitted: {} (transaction {})", bundle_id, transaction.signatures[0]);
+
+    Ok(bundle_id)
+}
+
+/// Waits until a submitted Jito bundle lands
+///
+/// The bundle status is polled every 400ms for up to 5 seconds, falling back to the signature
+/// status on the RPC node. Returns `SniperError::BundleTimeout` if the bundle was not seen.
+///
+/// # Arguments
+///
+/// * `rpc_client` - RPC client, used to confirm the transaction signature
+/// * `bundle_url` - Jito block engine bundles endpoint
+/// * `bundle_id` - Id returned when the bundle was submitted
+/// * `signature` - Signature of the bundled transaction
+pub async fn wait_for_bundle(
+    rpc_client: &RpcClient,
+    bundle_url: &str,
+    bundle_id: String,
+    signature: Signature,
+) -> Result<BundleResult, SniperError> {
+    let http_client = reqwest::Client::new();
+
+    let deadline = Instant::now() + Duration::from_millis(BUNDLE_STATUS_TIMEOUT_MS);
+    while Instant::now() < deadline {
+        sleep(Duration::from_millis(BUNDLE_STATUS_POLL_MS)).await;
+
+        // Statuses of recently landed bundles; null until the bundle lands
+        match block_engine_request(&http_client, bundle_url, "getBundleStatuses", serde_json::json!([[bundle_id]])).await {
+            Ok(result) => {
+                let status = result
+                    .get("value")
+                    .and_then(|value| value.get(0))
+                    .filter(|status| !status.is_null());
+                if let Some(status) = status {
+                    if let Some(err) = status.get("err").and_then(|err| err.get("Err")).filter(|err| !err.is_null()) {
+                        return Err(SniperError::Other(format!("Bundle {} failed: {}", bundle_id, err)));
+                    }
+                    let landed_slot = status.get("slot").and_then(|slot| slot.as_u64());
+                    return Ok(BundleResult { bundle_id, landed_slot });
+                }
+            }
+            Err(e) => println!("Failed to get bundle status of {}: {}", bundle_id, e),
+        }
+
+        // The block engine can lag behind the chain, the transaction status is authoritative
+        if let Ok(response) = rpc_client.get_signature_statuses(&[signature]).await {
+            if let Some(Some(status)) = response.value.first() {
+                if let Some(err) = &status.err {
+                    return Err(SniperError::Other(format!("Bundle {} transaction failed: {:?}", bundle_id, err)));
+                }
+                return Ok(BundleResult { bundle_id, landed_slot: Some(status.slot) });
+            }
+        }
+    }
+
+    Err(SniperError::BundleTimeout(bundle_id))
+}
+
+/// Pump protocol token buy sent as a Jito bundle, waiting until the bundle lands
+///
+/// Unlike `pump_buy`, the result tells whether the buy actually landed, see `wait_for_bundle`.
+///
+/// # Arguments
+///
+/// * `rpc_url` - RPC node URL, used to confirm the buy signature
+/// * `bundle_url` - Jito block engine bundles endpoint (e.g. https://mainnet.block-engine.jito.wtf/api/v1/bundles)
+/// * `private_key` - User's private key
+/// * `token_mint` - Token Mint address
+/// * `token_amount` - Amount of tokens to buy
+/// * `max_sol_cost` - Maximum SOL cost (in lamports)
+/// * `tip_lamports` - Tip paid to the Jito tip account
+/// * `blockhash` - Blockhash the transaction is signed with
+#[allow(clippy::too_many_arguments)]
+pub async fn pump_buy_jito_bundle(
+    rpc_url: &str,
+    bundle_url: &str,
+    private_key: &str,
+    token_mint: Pubkey,
+    token_amount: u64,
+    max_sol_cost: u64,
+    tip_lamports: u64,
+    blockhash: Hash,
+) -> Result<BundleResult, SniperError> {
+    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
+
+    let signer = parse_keypair(private_key).map_err(SniperError::InvalidKey)?;
+
+    // Fresh mint, the user's token account is created in the same transaction; the tip goes last
+    let transaction = TransactionBuilder::new(&signer, token_mint)
+        .with_compute_budget(DEFAULT_COMPUTE_UNIT_PRICE, buy_compute_unit_limit(true))
+        .with_ata_creation(AtaMode::default())
+        .with_buy(token_amount, max_sol_cost)
+        .with_jito_tip(tip_lamports)
+        .build(blockhash);
+
+    let bundle_id = submit_bundle(&reqwest::Client::new(), bundle_url, &transaction).await?;
+    let result = wait_for_bundle(&rpc_client, bundle_url, bundle_id, transaction.signatures[0]).await?;
+    println!("Buy bundle {} landed in slot {:?}", result.bundle_id, result.landed_slot);
+
+    Ok(result)
+}
+
+/// One buy of a batched buy transaction
+#[derive(Clone, Debug)]
+pub struct BatchBuy {
+    pub token_mint: Pubkey,
+    pub token_amount: u64,
+    pub max_sol_cost: u64,
+    /// Whether to create the user's token account, false when the mint is already held
+    pub include_ata: bool,
+}
+
+/// Buys sent together in one transaction and the result of sending it
+pub struct BatchBuyResult {
+    pub buys: Vec<BatchBuy>,
+    pub result: Result<SentBuy, SniperError>,
+}
+
+/// Size of the signed transaction for the instructions, without signing it
+fn transaction_size(payer: &Pubkey, instructions: &[Instruction]) -> usize {
+    let message = solana_sdk::message::Message::new(instructions, Some(payer));
+    // Compact length prefix of the single signature, the signature and the message
+    1 + 64 + message.serialize().len()
+}
+
+/// Compute units of the buys of a batch, each sized for its path
+fn batch_compute_units(buys: &[BatchBuy]) -> u64 {
+    buys.iter().map(|buy| buy_compute_unit_limit(buy.include_ata) as u64).sum()
+}
+
+/// Groups buys into transactions of at most `max_batch` buys that fit the packet size and the
+/// compute unit limit. Each transaction is sized with room for the nonce advance, which only the
+/// first one carries.
+fn split_buy_batches(signer: &Keypair, buys: &[BatchBuy], max_batch: usize, options: &TransactionOptions) -> Vec<Vec<BatchBuy>> {
+    let payer = signer.pubkey();
+    let mut batches: Vec<Vec<BatchBuy>> = Vec::new();
+    let mut current: Vec<BatchBuy> = Vec::new();
+
+    for buy in buys {
+        let mut candidate = current.clone();
+        candidate.push(buy.clone());
+
+        let fits = candidate.len() <= max_batch.max(1)
+            && batch_compute_units(&candidate) <= MAX_COMPUTE_UNIT_LIMIT
+            && transaction_size(&payer, &batch_instructions(signer, &candidate, options, options.nonce_account)) <= PACKET_DATA_SIZE;
+
+        if fits || current.is_empty() {
+            current = candidate;
+        } else {
+            batches.push(std::mem::replace(&mut current, vec![buy.clone()]));
+        }
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+
+    batches
+}
+
+/// Nonce advance if given, compute budget, ATA creation (for mints not held yet) and buy of every
+/// token in the batch, and the Jito tip when bundles are enabled
+fn batch_instructions(signer: &Keypair, buys: &[BatchBuy], options: &TransactionOptions, nonce_account: Option<Pubkey>) -> Vec<Instruction> {
+    let unit_limit = batch_compute_units(buys).min(MAX_COMPUTE_UNIT_LIMIT) as u32;
+    let mut instructions = Vec::with_capacity(3 + 2 * buys.len());
+
+    // The runtime only accepts a durable nonce advance as the first instruction
+    if let Some(nonce_account) = nonce_account {
+        instructions.push(system_instruction::advance_nonce_account(&nonce_account, &signer.pubkey()));
+    }
+    instructions.push(solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(options.buy_compute_unit_price));
+    instructions.push(solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(unit_limit));
+
+    for buy in buys {
+        let mut builder = TransactionBuilder::new(signer, buy.token_mint);
+        if buy.include_ata {
+            builder = builder.with_ata_creation(options.ata_mode);
+        }
+        instructions.extend(builder.with_buy(buy.token_amount, buy.max_sol_cost).instructions());
+    }
+
+    // One tip per bundled transaction, after all buys
+    if let Some(bundle) = &options.jito_bundle {
+        instructions.push(system_instruction::transfer(&signer.pubkey(), &JITO_TIP_ACCOUNT, bundle.tip_lamports));
+    }
+
+    instructions
+}
+
+/// Pump protocol buy of several tokens, batched into as few transactions as possible
+///
+/// # Arguments
+///
+/// * `rpc_url` - RPC node URL
+/// * `private_key` - User's private key
+/// * `buys` - Tokens to buy, with the user's token account created in the same transaction unless held
+/// * `max_batch` - Maximum buys per transaction
+/// * `slot` - Optional slot number for logging
+/// * `cached_blockhash` - Optional cached blockhash, if provided, RPC will not be queried. With
+///   `options.nonce_account` this is the nonce, used by the first transaction only
+/// * `options` - Send options
+pub async fn pump_buy_batch(
+    rpc_url: &str,
+    private_key: &str,
+    buys: &[BatchBuy],
+    max_batch: usize,
+    slot: Option<u64>,
+    cached_blockhash: Option<Hash>,
+    options: &TransactionOptions,
+) -> Result<Vec<BatchBuyResult>, SniperError> {
+    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
+
+    let signer = parse_keypair(private_key).map_err(SniperError::InvalidKey)?;
+
+    let blockhash = match cached_blockhash {
+        Some(hash) => hash,
+        None => {
+            rpc_client
+                .get_latest_blockhash_with_commitment(CommitmentConfig {
+                    commitment: CommitmentLevel::Confirmed,
+                })
+                .await?
+                .0
+        }
+    };
+
+    // A nonce is only valid for one transaction: the first one advances it, the others are sent
+    // with a recent blockhash
+    let batches = split_buy_batches(&signer, buys, max_batch, options);
+    let recent_blockhash = if options.nonce_account.is_some() && batches.len() > 1 {
+        rpc_client
+            .get_latest_blockhash_with_commitment(CommitmentConfig {
+                commitment: CommitmentLevel::Confirmed,
+            })
+            .await?
+            .0
+    } else {
+        blockhash
+    };
+
+    let mut results = Vec::new();
+    for (index, batch) in batches.into_iter().enumerate() {
+        println!("Batch buy of {} tokens (slot: {:?})", batch.len(), slot);
+
+        let (nonce_account, blockhash) = match options.nonce_account {
+            Some(nonce_account) if index == 0 => (Some(nonce_account), blockhash),
+            _ => (None, recent_blockhash),
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &batch_instructions(&signer, &batch, options, nonce_account),
+            Some(&signer.pubkey()),
+            &[&signer],
+            blockhash,
+        );
+
+        if account_key_logging_enabled() {
+            log_account_keys("pump_buy_batch", &VersionedMessage::Legacy(transaction.message.clone()));
         }
+
+        let result = send_buy(&rpc_client, &transaction, options).await;
+        results.push(BatchBuyResult { buys: batch, result });
     }
+
+    Ok(results)
 }
 
 /// Pump protocol token sell transaction
@@ -186,6 +866,8 @@ pub async fn pump_buy(
 /// * `min_sol_receive` - Minimum SOL to receive (in lamports)
 /// * `slot` - Optional slot number for logging
 /// * `cached_blockhash` - Optional cached blockhash, if provided, RPC will not be queried
+/// * `options` - Send options
+#[allow(clippy::too_many_arguments)]
 pub async fn pump_sell(
     rpc_url: &str,
     private_key: &str,
@@ -194,52 +876,38 @@ pub async fn pump_sell(
     min_sol_receive: u64,
     slot: Option<u64>,
     cached_blockhash: Option<Hash>,
-) -> Result<String, Error> {
+    options: &TransactionOptions,
+) -> Result<String, SniperError> {
     let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
 
-    // Construct sell instruction data
-    let mut data = Vec::with_capacity(24);
-    data.extend_from_slice(PUMP_SELL_SELECTOR); // Use internal selector PUMPFUN_SELL_SELECTOR
-    data.extend_from_slice(&token_amount.to_le_bytes());
-    data.extend_from_slice(&min_sol_receive.to_le_bytes());
+    // Construct sell instruction data, in the configured layout
+    let data = encode_sell_data(token_amount, min_sol_receive);
 
-    let signer = solana_sdk::signature::Keypair::from_base58_string(private_key);
+    let signer = parse_keypair(private_key).map_err(SniperError::InvalidKey)?;
 
-    // Calculate Bonding Curve address
-    let bonding_curve_address =
-        Pubkey::find_program_address(&[BONDING_CURVE_SEED, token_mint.as_ref()], &PUMP_PROGRAM_ID);
+    // Bonding Curve address and its associated token account
+    let CurveAddresses { bonding_curve, associated_bonding_curve } = curve_addresses(&token_mint);
 
     // User's associated token account
     let associated_user = get_associated_token_address(&signer.pubkey(), &token_mint);
 
-    // Bonding Curve's associated token account
-    let associated_bonding_curve =
-        get_associated_token_address(&bonding_curve_address.0, &token_mint);
-
-    // Add priority fee instructions - Increase priority fee to 200000 for faster processing
-    let compute_unit_price_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(200000);
+    // Sell priority fee, separate from the buy fee since exits are rarely a race
+    let compute_unit_price_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(options.sell_compute_unit_price);
 
     // Increase maximum compute units to ensure the transaction doesn't fail due to insufficient compute resources
-    let compute_unit_limit_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(200000);
+    let compute_unit_limit_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(SELL_COMPUTE_UNIT_LIMIT);
 
     // Construct sell instruction
     let sell_instruction = Instruction::new_with_bytes(
         PROXY_PROGRAM,
         &data,
-        vec![
-            AccountMeta::new_readonly(GLOBAL_ACCOUNT, false),
-            AccountMeta::new(FEE_RECIPIENT, false),
-            AccountMeta::new_readonly(token_mint, false),
-            AccountMeta::new(bonding_curve_address.0, false),
-            AccountMeta::new(associated_bonding_curve, false),
-            AccountMeta::new(associated_user, false),
-            AccountMeta::new(signer.pubkey(), true),
-            AccountMeta::new_readonly(system_program::id(), false),
-            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-            AccountMeta::new_readonly(EVENT_AUTHORITY, false),
-            AccountMeta::new_readonly(PUMP_PROGRAM_ID, false),
-        ],
+        sell_account_metas(&TradeAccounts {
+            user: signer.pubkey(),
+            mint: token_mint,
+            bonding_curve,
+            associated_bonding_curve,
+            associated_user,
+        }),
     );
 
     // Get blockhash
@@ -261,8 +929,7 @@ pub async fn pump_sell(
             .get_latest_blockhash_with_commitment(CommitmentConfig {
                 commitment: CommitmentLevel::Confirmed,
             })
-            .await
-            .unwrap()
+            .await?
             .0
     };
 
@@ -274,6 +941,18 @@ pub async fn pump_sell(
         blockhash,
     );
 
+    if account_key_logging_enabled() {
+        log_account_keys("pump_sell", &VersionedMessage::Legacy(transaction.message.clone()));
+    }
+
+    if options.sell_dry_run {
+        let serialized = bincode::serialize(&transaction)
+            .map_err(|e| SniperError::Other(format!("Failed to serialize sell transaction: {}", e)))?;
+        println!("Dry run, sell of {} tokens of {} (min {} lamports) not sent: {}", token_amount, token_mint, min_sol_receive, transaction.signatures[0]);
+        println!("Transaction ({} bytes, base64): {}", serialized.len(), BASE64.encode(&serialized));
+        return Ok(transaction.signatures[0].to_string());
+    }
+
     // Send transaction - Use optimal transaction settings
     match rpc_client
         .send_transaction_with_config(
@@ -281,7 +960,7 @@ pub async fn pump_sell(
             RpcSendTransactionConfig {
                 skip_preflight: true,
                 preflight_commitment: Some(CommitmentLevel::Processed), // Use Processed level for fastest return
-                max_retries: Some(0), // No retries, as we need to know the result immediately
+                max_retries: Some(options.max_retries), // 0 = no retries, as we need to know the result immediately
                 ..Default::default()
             },
         )
@@ -291,9 +970,395 @@ pub async fn pump_sell(
             println!("Sell transaction submitted: {}", signature);
             Ok(signature.to_string())
         }
+        Err(e) => match SniperError::from(e) {
+            // A copy of this exact transaction already landed
+            error if error.is_duplicate_signature() && options.duplicate_as_success => {
+                println!("Sell transaction already processed, treating as submitted: {}", transaction.signatures[0]);
+                Ok(transaction.signatures[0].to_string())
+            }
+            error => {
+                println!("Sell transaction failed: {:?}", error);
+                Err(error)
+            }
+        },
+    }
+}
+
+/// Closes the user's (empty) token account of a mint, reclaiming its rent
+///
+/// # Arguments
+///
+/// * `rpc_url` - RPC node URL
+/// * `private_key` - User's private key, owner of the token account and receiver of the rent
+/// * `token_mint` - Mint of the sold token
+pub async fn pump_close_token_account(
+    rpc_url: &str,
+    private_key: &str,
+    token_mint: Pubkey,
+) -> Result<String, SniperError> {
+    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
+
+    let signer = parse_keypair(private_key).map_err(SniperError::InvalidKey)?;
+
+    // User's associated token account
+    let associated_user = get_associated_token_address(&signer.pubkey(), &token_mint);
+
+    let close_instruction = spl_token::instruction::close_account(
+        &spl_token::id(),
+        &associated_user,
+        &signer.pubkey(),
+        &signer.pubkey(),
+        &[],
+    )
+    .map_err(|e| SniperError::Other(format!("Failed to build close account instruction: {}", e)))?;
+
+    let recent_blockhash = rpc_client.get_latest_blockhash().await?;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[close_instruction],
+        Some(&signer.pubkey()),
+        &[&signer],
+        recent_blockhash,
+    );
+
+    let signature = rpc_client.send_transaction(&transaction).await?;
+    println!("Close token account transaction submitted for {}: {}", token_mint, signature);
+
+    Ok(signature.to_string())
+}
+
+/// Derives the current virtual reserves of a freshly created curve by simulating a nominal buy
+///
+/// The simulation returns the post-buy bonding curve account, from which the pre-buy
+/// reserves are back-solved using the constant product invariant. Returns
+/// `(virtual_sol_reserves, virtual_token_reserves)`.
+///
+/// # Arguments
+///
+/// * `rpc_url` - RPC node URL
+/// * `private_key` - User's private key (used as fee payer, signature is not verified)
+/// * `token_mint` - Token Mint address
+pub async fn simulate_initial_reserves(
+    rpc_url: &str,
+    private_key: &str,
+    token_mint: Pubkey,
+) -> Result<(u64, u64), SniperError> {
+    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::processed());
+
+    let signer = parse_keypair(private_key).map_err(SniperError::InvalidKey)?;
+
+    let bonding_curve_address = derive_bonding_curve(&token_mint);
+
+    let result = simulate_instructions(
+        &rpc_client,
+        &signer,
+        &[
+            build_ata_instruction(&signer.pubkey(), &token_mint, AtaMode::Proxy),
+            build_buy_instruction(&signer.pubkey(), &token_mint, NOMINAL_BUY_TOKEN_AMOUNT, NOMINAL_BUY_MAX_SOL_COST),
+        ],
+        vec![bonding_curve_address.to_string()],
+    )
+    .await?;
+
+    // Decode the post-buy bonding curve account returned by the simulation
+    let state = result
+        .accounts
+        .and_then(|accounts| accounts.into_iter().next().flatten())
+        .and_then(|ui_account| ui_account.decode::<Account>())
+        .and_then(|account| BondingCurveState::from_account_data(&account.data))
+        .ok_or_else(|| SniperError::Other(format!("Simulation returned no bonding curve state for {}", token_mint)))?;
+
+    // Undo the nominal buy: k = sol * token is preserved across the trade
+    let virtual_token_reserves = state.virtual_token_reserves.saturating_add(NOMINAL_BUY_TOKEN_AMOUNT);
+    let virtual_sol_reserves = (state.virtual_sol_reserves as u128 * state.virtual_token_reserves as u128
+        / virtual_token_reserves as u128) as u64;
+
+    Ok((virtual_sol_reserves, virtual_token_reserves))
+}
+
+/// Measures the compute units used by the ATA+buy and bare buy paths against a fresh curve
+///
+/// The bare buy cannot be simulated on its own before the ATA exists, so its usage is
+/// derived as the difference between the ATA+buy and ATA-only simulations. The learned
+/// limits (plus a safety margin) are used by all subsequent `pump_buy` calls.
+/// Returns `(ata_buy_limit, buy_limit)`.
+///
+/// # Arguments
+///
+/// * `rpc_url` - RPC node URL
+/// * `private_key` - User's private key (used as fee payer, signature is not verified)
+/// * `token_mint` - Mint of a freshly created token the user holds no account for
+/// * `ata_mode` - ATA creation instruction used by buys
+pub async fn calibrate_compute_units(
+    rpc_url: &str,
+    private_key: &str,
+    token_mint: Pubkey,
+    ata_mode: AtaMode,
+) -> Result<(u32, u32), SniperError> {
+    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::processed());
+
+    let signer = parse_keypair(private_key).map_err(SniperError::InvalidKey)?;
+
+    let ata_instruction = build_ata_instruction(&signer.pubkey(), &token_mint, ata_mode);
+    let buy_instruction =
+        build_buy_instruction(&signer.pubkey(), &token_mint, NOMINAL_BUY_TOKEN_AMOUNT, NOMINAL_BUY_MAX_SOL_COST);
+
+    let ata_buy_units = simulate_instructions(&rpc_client, &signer, &[ata_instruction.clone(), buy_instruction], vec![])
+        .await?
+        .units_consumed
+        .ok_or_else(|| SniperError::Simulation("No compute units reported".to_string()))?;
+    let ata_units = simulate_instructions(&rpc_client, &signer, &[ata_instruction], vec![])
+        .await?
+        .units_consumed
+        .ok_or_else(|| SniperError::Simulation("No compute units reported".to_string()))?;
+
+    let with_margin = |units: u64| -> u32 {
+        (units * (100 + COMPUTE_UNIT_MARGIN_PERCENT) / 100).min(MAX_COMPUTE_UNIT_LIMIT) as u32
+    };
+    let ata_buy_limit = with_margin(ata_buy_units);
+    let buy_limit = with_margin(ata_buy_units.saturating_sub(ata_units));
+
+    ATA_BUY_COMPUTE_UNITS.store(ata_buy_limit, Ordering::Relaxed);
+    BUY_COMPUTE_UNITS.store(buy_limit, Ordering::Relaxed);
+
+    println!("Learned compute unit limits: ATA+buy {} CU (used {}), buy {} CU (used {})",
+             ata_buy_limit, ata_buy_units, buy_limit, ata_buy_units.saturating_sub(ata_units));
+
+    Ok((ata_buy_limit, buy_limit))
+}
+
+/// Polls the status of a signature until it reaches the given commitment, fails, or times out
+///
+/// # Arguments
+///
+/// * `rpc_client` - RPC client
+/// * `signature` - Transaction signature to check
+/// * `commitment` - Commitment level the transaction must reach
+/// * `timeout_ms` - Maximum time to wait (milliseconds)
+pub async fn wait_for_commitment(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+    commitment: CommitmentConfig,
+    timeout_ms: u64,
+) -> ConfirmationStatus {
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+
+    loop {
+        match rpc_client.get_signature_statuses(&[*signature]).await {
+            Ok(response) => {
+                if let Some(Some(status)) = response.value.first() {
+                    if let Some(err) = &status.err {
+                        return ConfirmationStatus::Failed(err.clone());
+                    }
+                    if status.satisfies_commitment(commitment) {
+                        return ConfirmationStatus::Confirmed;
+                    }
+                }
+            }
+            Err(e) => println!("Failed to get signature status for {}: {:?}", signature, e),
+        }
+
+        if Instant::now() >= deadline {
+            return ConfirmationStatus::TimedOut;
+        }
+        sleep(Duration::from_millis(SIGNATURE_STATUS_POLL_MS)).await;
+    }
+}
+
+/// Sends a 0-lamport self-transfer through the buy send path and waits until it is confirmed,
+/// validating RPC, keypair, fees and blockhash end to end. Returns the round-trip landing time.
+///
+/// # Arguments
+///
+/// * `rpc_url` - RPC node URL
+/// * `private_key` - User's private key
+/// * `cached_blockhash` - Optional cached blockhash, if provided, RPC will not be queried
+/// * `options` - Send options, as used for buys
+pub async fn send_self_test(
+    rpc_url: &str,
+    private_key: &str,
+    cached_blockhash: Option<Hash>,
+    options: &TransactionOptions,
+) -> Result<Duration, SniperError> {
+    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
+
+    let signer = parse_keypair(private_key).map_err(SniperError::InvalidKey)?;
+    let user = signer.pubkey();
+
+    let blockhash = match cached_blockhash {
+        Some(hash) => hash,
+        None => rpc_client.get_latest_blockhash().await?,
+    };
+
+    // Same priority fee as buys, so the fee payment is exercised too
+    let instructions = [
+        solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(options.buy_compute_unit_price),
+        system_instruction::transfer(&user, &user, 0),
+    ];
+    let transaction = Transaction::new_signed_with_payer(&instructions, Some(&user), &[&signer], blockhash);
+
+    let start = Instant::now();
+    let signature = send_buy_transaction(&rpc_client, &transaction, options).await?;
+    let signature = signature.parse::<Signature>()
+        .map_err(|e| SniperError::Other(format!("Invalid self-test signature {}: {}", signature, e)))?;
+
+    match wait_for_commitment(&rpc_client, &signature, CommitmentConfig::confirmed(), SELF_TEST_TIMEOUT_MS).await {
+        ConfirmationStatus::Confirmed => Ok(start.elapsed()),
+        ConfirmationStatus::Failed(err) => Err(SniperError::Other(format!("Self-test transaction {} failed: {}", signature, err))),
+        ConfirmationStatus::TimedOut => Err(SniperError::Other(format!(
+            "Self-test transaction {} not confirmed within {}ms", signature, SELF_TEST_TIMEOUT_MS
+        ))),
+    }
+}
+
+/// Returns the raw token balance (amount in base units) of the user's associated token account
+/// for a mint, or 0 if the account does not exist
+///
+/// # Arguments
+///
+/// * `rpc_url` - RPC node URL
+/// * `private_key` - User's private key
+/// * `token_mint` - Token Mint address
+pub async fn get_token_balance(rpc_url: &str, private_key: &str, token_mint: Pubkey) -> Result<u64, SniperError> {
+    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
+    let signer = parse_keypair(private_key).map_err(SniperError::InvalidKey)?;
+    let token_account = get_associated_token_address(&signer.pubkey(), &token_mint);
+
+    match rpc_client.get_token_account_balance(&token_account).await {
+        Ok(balance) => balance
+            .amount
+            .parse::<u64>()
+            .map_err(|e| SniperError::Other(format!("Invalid token balance {} of {}: {}", balance.amount, token_account, e))),
         Err(e) => {
-            println!("Sell transaction failed: {:?}", e);
-            Err(Error)
+            // The balance request fails for a missing account, which simply holds nothing
+            let account = rpc_client
+                .get_account_with_commitment(&token_account, CommitmentConfig::confirmed())
+                .await?
+                .value;
+            match account {
+                None => Ok(0),
+                Some(_) => Err(e.into()),
+            }
         }
     }
 }
+
+/// Returns the lamports a landed buy paid to the bonding curve, i.e. the signer's balance change
+/// minus the transaction fee, the rent of a token account created by the buy and the Jito tip
+///
+/// # Arguments
+///
+/// * `rpc_client` - RPC client
+/// * `signature` - Signature of a confirmed buy
+/// * `include_ata` - Whether the buy created the signer's token account
+/// * `tip_lamports` - Jito tip paid in the same transaction, 0 when sent through the RPC node
+pub async fn fetch_buy_sol_cost(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+    include_ata: bool,
+    tip_lamports: u64,
+) -> Result<u64, SniperError> {
+    let transaction = rpc_client
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await?;
+
+    let meta = transaction
+        .transaction
+        .meta
+        .ok_or_else(|| SniperError::Other(format!("No status metadata for {}", signature)))?;
+
+    // The signer is always the first account
+    let (pre, post) = match (meta.pre_balances.first(), meta.post_balances.first()) {
+        (Some(pre), Some(post)) => (*pre, *post),
+        _ => return Err(SniperError::Other(format!("No balances for {}", signature))),
+    };
+
+    let rent = if include_ata { TOKEN_ACCOUNT_RENT_LAMPORTS } else { 0 };
+    Ok(pre.saturating_sub(post).saturating_sub(meta.fee).saturating_sub(rent).saturating_sub(tip_lamports))
+}
+
+/// Returns the program log lines of a confirmed transaction, empty if the node kept none
+///
+/// # Arguments
+///
+/// * `rpc_client` - RPC client
+/// * `signature` - Signature of a confirmed transaction
+pub async fn fetch_transaction_logs(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+) -> Result<Vec<String>, SniperError> {
+    let transaction = rpc_client
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await?;
+
+    match transaction.transaction.meta.map(|meta| meta.log_messages) {
+        Some(OptionSerializer::Some(logs)) => Ok(logs),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Simulates a set of instructions without signature verification
+///
+/// # Arguments
+///
+/// * `rpc_client` - RPC client
+/// * `signer` - Fee payer
+/// * `instructions` - Instructions to simulate
+/// * `addresses` - Accounts whose post-simulation state should be returned
+async fn simulate_instructions(
+    rpc_client: &RpcClient,
+    signer: &solana_sdk::signature::Keypair,
+    instructions: &[Instruction],
+    addresses: Vec<String>,
+) -> Result<solana_rpc_client_api::response::RpcSimulateTransactionResult, SniperError> {
+    // The blockhash is replaced by the RPC node during simulation
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&signer.pubkey()),
+        &[signer],
+        Hash::default(),
+    );
+
+    let accounts = if addresses.is_empty() {
+        None
+    } else {
+        Some(RpcSimulateTransactionAccountsConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            addresses,
+        })
+    };
+
+    let result = rpc_client
+        .simulate_transaction_with_config(
+            &transaction,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                commitment: Some(CommitmentConfig::processed()),
+                accounts,
+                ..Default::default()
+            },
+        )
+        .await?
+        .value;
+
+    if let Some(err) = &result.err {
+        return Err(SniperError::Simulation(format!("{:?}", err)));
+    }
+
+    Ok(result)
+}
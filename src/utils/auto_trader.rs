@@ -1,106 +1,1349 @@
 use std::error::Error;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+use std::future::Future;
+use std::pin::Pin;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use serde::Serialize;
+use tokio::sync::{Mutex, RwLock};
 use tokio::time::{sleep, Duration};
+use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
 use std::str::FromStr;
 use tokio::task::JoinHandle;
-use crate::utils::redis::RedisClient;
-use crate::transaction::{pump_buy, pump_sell};
+use crate::utils::redis::{RedisClient, TradeEvent};
+use crate::utils::store::PositionStore;
+use crate::transaction::{buy_fee_reserve, fetch_buy_sol_cost, get_token_balance, pump_buy, pump_buy_batch, pump_close_token_account, pump_sell, send_self_test, wait_for_bundle, wait_for_commitment, BatchBuy, ConfirmationStatus, JitoBundle, SentBuy, TransactionOptions, TOKEN_ACCOUNT_RENT_LAMPORTS};
+use crate::error::SniperError;
+use tracing::info;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::request::TokenAccountsFilter;
+use solana_account_decoder::{UiAccount, UiAccountData};
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature, signer::Signer};
+use crate::utils::keypair::parse_keypair;
 use crate::utils::blockhash_cache::BlockhashCache;
-use redis::RedisError;
+use crate::utils::nonce_manager::NonceManager;
+use crate::utils::sol_price::TradeLimits;
+use crate::utils::congestion::CongestionState;
+use crate::utils::priority_fee::{FeeHistory, FeeSample, PriorityFeeOracle};
+use crate::utils::replay::FrameCapture;
+use crate::utils::bonding_curve::read_bonding_curve;
+use crate::utils::unix_time_ms;
+use crate::config::{Config, ConfirmTimeoutBehavior, ReentryPolicy};
+use crate::processor::{FeedSlot, TokenReserves};
+use crate::utils::bonding_curve::{quote_buy, quote_sell, TokenAmountStrategy};
+use governor::{clock::DefaultClock, state::{InMemoryState, NotKeyed}, Quota, RateLimiter};
+use std::num::NonZeroU32;
+use rand::Rng;
+use uuid::Uuid;
+
+// Why an eligible-looking buy event did not trigger a snipe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    OutOfPriceRange, // Buy size outside the configured range
+    RateLimited,     // MAX_SNIPES_PER_MINUTE reached
+    Halted,          // Trading halted by a command
+    Congested,       // Snipes paused during network congestion
+    Unconfirmed,     // Create did not reach CREATE_COMMITMENT
+}
+
+impl SkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::OutOfPriceRange => "out_of_price_range",
+            SkipReason::RateLimited => "rate_limited",
+            SkipReason::Halted => "halted",
+            SkipReason::Congested => "congested",
+            SkipReason::Unconfirmed => "unconfirmed",
+        }
+    }
+}
+
+// Result of evaluating a buy event for sniping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnipeDecision {
+    Snipe,
+    Skip(SkipReason),
+}
+
+// A token that qualified for a snipe, with the price and reserves it was seen at
+pub struct SnipeTarget {
+    pub mint: String,
+    pub token_price: f64,
+    pub reserves: Option<TokenReserves>,
+}
+
+// A snipe that passed the pre-send guards, sized and ready to send
+#[derive(Clone)]
+struct PreparedBuy {
+    mint: String,
+    mint_pubkey: Pubkey,
+    token_price: f64,
+    token_amount: u64,
+    max_sol_cost: u64,
+    include_ata: bool,               // False when the mint is still held, its token account exists
+    reserves: Option<TokenReserves>, // Reserves the buy was sized with
+}
+
+// When the processor detected the buy that triggered a snipe and when the snipe waited for and
+// acquired the trader lock, the first phases of the pipeline latency
+#[derive(Clone, Copy, Debug)]
+pub struct PipelineStart {
+    pub detected_at: Instant,    // Buy parsed from the feed
+    pub lock_requested: Instant, // Snipe decided (commitment and simulation waits settled), lock requested
+    pub locked_at: Instant,      // Trader lock acquired
+}
+
+// Instants of the send path of a snipe, the last phases of the pipeline latency
+struct SendTimes {
+    started: Instant,            // Guards passed, buy sized and blockhash fetched
+    built: Option<Instant>,      // First transaction built and signed, None if its send failed
+    first_sent: Option<Instant>, // First send returned
+    retries: u32,                // Sends repeated after write-lock contention
+}
+
+impl SendTimes {
+    fn start() -> Self {
+        SendTimes { started: Instant::now(), built: None, first_sent: None, retries: 0 }
+    }
+
+    fn first_send_returned(&mut self, built: Option<Instant>) {
+        self.built = built;
+        self.first_sent = Some(Instant::now());
+    }
+}
+
+// A sent buy whose position is only recorded once it is confirmed (CONFIRM_BUYS). Confirming it
+// takes up to CONFIRM_TIMEOUT_MS, so it is done by a BuyConfirmer without the trader lock.
+pub struct UnconfirmedBuy {
+    pub signature: String,
+    buy: PreparedBuy,
+    slot: Option<u64>,
+    elapsed: Duration, // Snipe time up to the send, logged with the outcome
+}
+
+// Outcome of a sell attempt, passed to the cleanup hooks
+pub struct SellResult {
+    pub token_amount: u64,
+    pub signature: Option<String>, // None if the sell could not be submitted
+    pub slot: Option<u64>,
+}
+
+impl SellResult {
+    // Trade history entry of a submitted sell, None if the sell was not submitted
+    fn trade_event(&self, mint: &str) -> Option<TradeEvent> {
+        let signature = self.signature.clone()?;
+        Some(TradeEvent {
+            side: "sell",
+            mint: mint.to_string(),
+            token_amount: self.token_amount,
+            sol_amount: 0, // Proceeds are unknown until the sell is confirmed
+            signature,
+            slot: self.slot,
+        })
+    }
+}
+
+pub type CleanupFuture<'a> = Pin<Box<dyn Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send + 'a>>;
+
+// A step run after every sell attempt (position bookkeeping, PnL, notifications, metrics, ...)
+pub trait SellCleanupHook: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn run<'a>(&'a self, mint: &'a str, result: &'a SellResult) -> CleanupFuture<'a>;
+}
+
+type SellCleanupHooks = Arc<RwLock<Vec<Box<dyn SellCleanupHook>>>>;
+
+// Run the hooks in registration order; a failing hook is logged and does not stop the others
+async fn run_sell_cleanup(hooks: &SellCleanupHooks, mint: &str, result: &SellResult) {
+    for hook in hooks.read().await.iter() {
+        if let Err(e) = hook.run(mint, result).await {
+            println!("Sell cleanup step {} failed for {}: {:?}", hook.name(), mint, e);
+        }
+    }
+}
+
+// Removes a sold position from the sell queue and amount table
+struct RedisPositionCleanup {
+    redis_client: Arc<RedisClient>,
+}
+
+impl SellCleanupHook for RedisPositionCleanup {
+    fn name(&self) -> &'static str {
+        "redis_position"
+    }
+
+    fn run<'a>(&'a self, mint: &'a str, result: &'a SellResult) -> CleanupFuture<'a> {
+        Box::pin(async move {
+            // Keep the stored amount of failed sells so the position is not forgotten
+            if result.signature.is_some() {
+                self.redis_client.settle_sold_mint(mint, result.token_amount).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// Appends submitted sells to the trade history stream
+struct TradeEventLog {
+    redis_client: Arc<RedisClient>,
+}
+
+impl SellCleanupHook for TradeEventLog {
+    fn name(&self) -> &'static str {
+        "trade_event_log"
+    }
+
+    fn run<'a>(&'a self, mint: &'a str, result: &'a SellResult) -> CleanupFuture<'a> {
+        Box::pin(async move {
+            if let Some(trade_event) = result.trade_event(mint) {
+                self.redis_client.record_trade_event(&trade_event).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// Journals submitted sells to an additional position store
+struct PositionJournal {
+    store: Arc<dyn PositionStore>,
+}
+
+impl SellCleanupHook for PositionJournal {
+    fn name(&self) -> &'static str {
+        "position_journal"
+    }
+
+    fn run<'a>(&'a self, mint: &'a str, result: &'a SellResult) -> CleanupFuture<'a> {
+        Box::pin(async move {
+            if let Some(trade_event) = result.trade_event(mint) {
+                self.store.record_sell(&trade_event).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// An open position held by this process
+#[derive(Clone, Copy, Debug)]
+pub struct Position {
+    pub token_amount: u64,
+    pub bought_at: Instant,
+}
+
+// In-memory view of the open positions opened since startup, for fast reads without Redis.
+// Only written on buys and sells; Redis remains the source of truth for the sell queue.
+#[derive(Default)]
+pub struct PositionInventory {
+    positions: HashMap<String, Position>,
+}
+
+impl PositionInventory {
+    pub fn count_positions(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn get_position(&self, mint: &str) -> Option<Position> {
+        self.positions.get(mint).copied()
+    }
+
+    pub fn list_positions(&self) -> Vec<(String, Position)> {
+        self.positions.iter().map(|(mint, position)| (mint.clone(), *position)).collect()
+    }
+
+    // Add a buy to the position of its mint, keeping the first buy time (REENTRY_POLICY=aggregate)
+    fn add(&mut self, mint: &str, token_amount: u64) {
+        self.positions
+            .entry(mint.to_string())
+            .and_modify(|position| position.token_amount += token_amount)
+            .or_insert(Position { token_amount, bought_at: Instant::now() });
+    }
+
+    // Subtract a sold amount, dropping the position once nothing is left
+    fn settle(&mut self, mint: &str, sold_amount: u64) {
+        if let Some(position) = self.positions.get_mut(mint) {
+            position.token_amount = position.token_amount.saturating_sub(sold_amount);
+            if position.token_amount == 0 {
+                self.positions.remove(mint);
+            }
+        }
+    }
+
+    fn remove(&mut self, mint: &str) -> bool {
+        self.positions.remove(mint).is_some()
+    }
+}
+
+// Drops sold positions from the in-memory inventory
+struct InventoryCleanup {
+    inventory: Arc<RwLock<PositionInventory>>,
+}
+
+impl SellCleanupHook for InventoryCleanup {
+    fn name(&self) -> &'static str {
+        "position_inventory"
+    }
+
+    fn run<'a>(&'a self, mint: &'a str, result: &'a SellResult) -> CleanupFuture<'a> {
+        Box::pin(async move {
+            // Same rule as the Redis cleanup: failed sells keep their position
+            if result.signature.is_some() {
+                self.inventory.write().await.settle(mint, result.token_amount);
+            }
+            Ok(())
+        })
+    }
+}
+
+// Trading counters of this session, readable without the trader lock
+#[derive(Default)]
+pub struct AutoTraderStats {
+    snipes: AtomicU64,   // Buys recorded as positions (confirmed ones when CONFIRM_BUYS is set)
+    attempts: AtomicU64, // Buys sent or attempted, including failed ones
+    sells: AtomicU64,    // Sells submitted
+}
+
+impl AutoTraderStats {
+    pub fn snipes(&self) -> u64 {
+        self.snipes.load(Ordering::Relaxed)
+    }
+
+    pub fn attempts(&self) -> u64 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    pub fn sells(&self) -> u64 {
+        self.sells.load(Ordering::Relaxed)
+    }
+}
+
+// Wallet state checked at startup
+#[derive(Debug, Default)]
+pub struct WalletHealth {
+    pub sol_balance_lamports: u64,
+    pub token_accounts: Vec<(Pubkey, u64)>, // Mint and raw amount of every non-empty token account
+    pub has_sufficient_balance: bool,       // Enough SOL for at least one buy
+    pub warnings: Vec<String>,
+}
+
+// Mint and raw amount of a jsonParsed token account
+fn parse_token_account(account: &UiAccount) -> Option<(Pubkey, u64)> {
+    let info = match &account.data {
+        UiAccountData::Json(parsed) => parsed.parsed.get("info")?,
+        _ => return None,
+    };
+    let mint = Pubkey::from_str(info.get("mint")?.as_str()?).ok()?;
+    let amount = info.get("tokenAmount")?.get("amount")?.as_str()?.parse().ok()?;
+    Some((mint, amount))
+}
+
+// Snipe activity of one slot: candidates in the price range and what became of them
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BuySummary {
+    pub eligible: u64,     // Buys in the price range that were considered for a snipe
+    pub sniped: u64,       // Buys sent
+    pub skipped: u64,      // Eligible buys not sent, for any reason
+    pub rate_limited: u64, // Skipped by MAX_SNIPES_PER_MINUTE
+    pub budget: u64,       // Skipped because the buy amount does not cover fees and rent
+    pub duplicate: u64,    // Skipped because the mint was already reserved by another task or instance
+}
+
+// Per-slot summaries, accumulated until the slot completes; shared with the feed loop, which
+// logs them without the trader lock
+#[derive(Default)]
+pub struct SlotStats {
+    slots: std::sync::Mutex<BTreeMap<u64, BuySummary>>,
+}
+
+impl SlotStats {
+    fn count(&self, slot: Option<u64>, update: impl FnOnce(&mut BuySummary)) {
+        if let Some(slot) = slot {
+            let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+            update(slots.entry(slot).or_default());
+        }
+    }
+
+    // Remove and return the summaries of all slots up to and including `slot`
+    fn take_through(&self, slot: u64) -> Vec<(u64, BuySummary)> {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        let later = slots.split_off(&(slot + 1));
+        std::mem::replace(&mut *slots, later).into_iter().collect()
+    }
+
+    // Snipe activity of the given slot, removed from the accumulated stats. Summaries of earlier
+    // slots that were never taken are dropped.
+    pub fn buy_summary_after_slot(&self, slot: u64) -> BuySummary {
+        self.take_through(slot)
+            .into_iter()
+            .find(|(summary_slot, _)| *summary_slot == slot)
+            .map(|(_, summary)| summary)
+            .unwrap_or_default()
+    }
+
+    // Called once all entries of a slot were processed. Snipe tasks of the slot may still be
+    // running, so the main loop calls this when the first frame of a later slot arrives.
+    pub fn on_slot_complete(&self, slot: u64) {
+        let summary = self.buy_summary_after_slot(slot);
+        if summary.eligible > 0 {
+            println!("Slot {}: {} eligible tokens, {} sniped, {} skipped (rate_limit: {}, budget: {}, duplicate: {})",
+                     slot, summary.eligible, summary.sniped, summary.skipped,
+                     summary.rate_limited, summary.budget, summary.duplicate);
+        }
+    }
+}
+
+// Outcome of a snipe attempt, as written to the snipe log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SnipeOutcome {
+    Success,
+    Failed(String),  // Reason the buy failed or did not land
+    Skipped(String), // Reason the snipe was not attempted
+    Landed,          // Sent buy seen on-chain without error (TRACK_LANDING)
+    Dropped,         // Sent buy never seen on-chain within LANDING_TIMEOUT_MS
+    Errored(String), // Sent buy landed but failed on-chain, with the transaction error
+}
+
+impl SnipeOutcome {
+    fn parts(&self) -> (&'static str, Option<&str>) {
+        match self {
+            SnipeOutcome::Success => ("success", None),
+            SnipeOutcome::Failed(reason) => ("failed", Some(reason)),
+            SnipeOutcome::Skipped(reason) => ("skipped", Some(reason)),
+            SnipeOutcome::Landed => ("landed", None),
+            SnipeOutcome::Dropped => ("dropped", None),
+            SnipeOutcome::Errored(reason) => ("error", Some(reason)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SnipeLogLine<'a> {
+    timestamp_ms: u64,
+    mint: &'a str,
+    sol_spent: u64, // Maximum SOL cost of the buy (lamports)
+    token_amount: u64,
+    tx_sig: Option<&'a str>,
+    outcome: &'static str,
+    reason: Option<&'a str>,
+    latency_ms: u64,
+}
+
+// Append-only audit trail of snipe attempts, one JSON line per outcome (SNIPE_LOG_FILE), for
+// measuring fill rate, failure breakdown and latency over a session
+#[derive(Clone)]
+pub struct SnipeLog {
+    file: Arc<std::sync::Mutex<BufWriter<File>>>,
+}
+
+impl SnipeLog {
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file: Arc::new(std::sync::Mutex::new(BufWriter::new(file))) })
+    }
+
+    pub fn log_snipe_outcome(&self, mint: &str, sol_spent: u64, token_amount: u64, tx_sig: Option<&str>, outcome: SnipeOutcome, latency_ms: u64) {
+        let (outcome, reason) = outcome.parts();
+        let line = SnipeLogLine { timestamp_ms: unix_time_ms(), mint, sol_spent, token_amount, tx_sig, outcome, reason, latency_ms };
+        let line = match serde_json::to_string(&line) {
+            Ok(line) => line,
+            Err(e) => {
+                println!("Failed to encode snipe log line for {}: {}", mint, e);
+                return;
+            }
+        };
+
+        // Flushed per line so the trail survives a crash
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+            println!("Failed to write snipe log: {}", e);
+        }
+    }
+}
+
+// Handles of spawned snipe tasks, so shutdown can wait for in-flight buys
+#[derive(Clone, Default)]
+pub struct SnipeTasks(Arc<std::sync::Mutex<Vec<JoinHandle<()>>>>);
+
+impl SnipeTasks {
+    // Keep the handle of a snipe task, dropping those of finished tasks
+    pub fn track(&self, handle: JoinHandle<()>) {
+        if let Ok(mut handles) = self.0.lock() {
+            handles.retain(|handle| !handle.is_finished());
+            handles.push(handle);
+        }
+    }
+
+    // Wait up to timeout for the tracked tasks; returns how many completed
+    pub async fn wait(&self, timeout: Duration) -> usize {
+        let mut handles = match self.0.lock() {
+            Ok(mut handles) => std::mem::take(&mut *handles),
+            Err(_) => return 0,
+        };
+
+        if tokio::time::timeout(timeout, futures::future::join_all(handles.iter_mut())).await.is_err() {
+            let pending = handles.iter().filter(|handle| !handle.is_finished()).count();
+            println!("WARNING: {} snipe tasks still running after {}ms", pending, timeout.as_millis());
+        }
+
+        handles.iter().filter(|handle| handle.is_finished()).count()
+    }
+}
+
+// Runtime controls, shared between the trader and the Redis command listener
+pub struct TraderControl {
+    redis_client: Arc<RedisClient>,
+    halted: AtomicBool, // No new positions are opened while set, sells continue
+}
+
+impl TraderControl {
+    // Stop opening new positions
+    pub fn halt(&self) {
+        self.halted.store(true, Ordering::Relaxed);
+        println!("Trading halted, no new positions will be opened");
+    }
+
+    // Resume opening new positions
+    pub fn resume(&self) {
+        self.halted.store(false, Ordering::Relaxed);
+        println!("Trading resumed");
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted.load(Ordering::Relaxed)
+    }
+
+    // Sell an open position on the next sell loop iteration
+    pub async fn force_sell_mint(&self, mint: &str) {
+        match self.redis_client.schedule_sell_now(mint).await {
+            Ok(true) => println!("Force sell scheduled: {}", mint),
+            Ok(false) => println!("Force sell ignored, no open position: {}", mint),
+            Err(e) => println!("Failed to schedule force sell for {}: {:?}", mint, e),
+        }
+    }
+
+    // Commands: "halt", "resume", "force_sell <mint>"
+    async fn handle_command(&self, command: &str) {
+        let mut parts = command.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("halt"), None) => self.halt(),
+            (Some("resume"), None) => self.resume(),
+            (Some("force_sell"), Some(mint)) => self.force_sell_mint(mint).await,
+            _ => println!("Unknown command: {}", command),
+        }
+    }
+}
+
+// How long a snipe reservation blocks other tasks and instances from buying the same mint
+const SNIPE_SLOT_TTL_MS: u64 = 60_000;
+
+// Why a position is too small to be worth a sell transaction, None if it should be sold. The SOL
+// value is quoted against the current bonding curve; an unreadable curve does not block the sell.
+async fn dust_reason(config: &Config, rpc_client: &RpcClient, mint: &Pubkey, token_amount: u64) -> Option<String> {
+    if token_amount < config.min_sell_token_amount {
+        return Some(format!("{} tokens < MIN_SELL_TOKEN_AMOUNT {}", token_amount, config.min_sell_token_amount));
+    }
+
+    if config.min_sell_sol_value > 0 {
+        let curve = read_bonding_curve(rpc_client, mint, 0, 0).await?;
+        let sol_value = quote_sell(curve.virtual_sol_reserves, curve.virtual_token_reserves, token_amount);
+        if sol_value < config.min_sell_sol_value {
+            return Some(format!("worth {} lamports < MIN_SELL_SOL_VALUE {} lamports", sol_value, config.min_sell_sol_value));
+        }
+    }
+
+    None
+}
+
+// Token-2022 program, named in the log when a mint turns out to use it
+const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PE5RRsWCx6V48Tk";
+
+// Why the buy and sell instructions, which are built for the SPL Token program, would fail for
+// this mint, None if they apply. A mint account that is not readable yet does not block the snipe.
+async fn token_program_mismatch(rpc_client: &RpcClient, mint: &Pubkey) -> Option<String> {
+    let account = match rpc_client.get_account(mint).await {
+        Ok(account) => account,
+        Err(e) => {
+            println!("Failed to read mint account {}, not verifying its token program: {}", mint, e);
+            return None;
+        }
+    };
+
+    if account.owner == spl_token::id() {
+        None
+    } else if account.owner.to_string() == TOKEN_2022_PROGRAM_ID {
+        Some("Token-2022 mint, only SPL Token mints are supported".to_string())
+    } else {
+        Some(format!("mint owned by unexpected program {}", account.owner))
+    }
+}
+
+// Slippage buffer used when the exact quote is unavailable (basis points)
+const DEFAULT_SLIPPAGE_BPS: u64 = 1500;
+
+// Estimate the token amount for a buy from the price (SOL per whole token), reduced by the slippage buffer
+fn estimate_token_amount(buy_sol: f64, token_price: f64, slippage_bps: u64, token_decimals: u8) -> Result<u64, Box<dyn Error>> {
+    // Ensure price is not zero to avoid division by zero
+    if token_price <= 0.0 {
+        return Err(Box::new(std::io::Error::other(
+            format!("Invalid token price: {}", token_price)
+        )));
+    }
+
+    // Calculate token amount without precision
+    let token_amount_no_precision = buy_sol / token_price;
+
+    // Precision factor is 10^decimals
+    let precision_factor = 10f64.powi(token_decimals as i32);
+
+    // Calculate token amount with precision, floor
+    // Reduce buy amount by the slippage buffer to avoid slippage errors
+    let reduced_amount = token_amount_no_precision * (10_000 - slippage_bps.min(10_000)) as f64 / 10_000.0;
+
+    println!("Actual price: {} SOL/token", token_price);
+    println!("Calculated token amount: {:.2} (no precision)", token_amount_no_precision);
+    println!("Reduced amount: {:.2} (no precision)", reduced_amount);
+
+    Ok((reduced_amount * precision_factor).floor() as u64)
+}
+
+// Interval of the blockhash prefetch while no snipes or sells refresh the cache
+const BLOCKHASH_HEARTBEAT_MS: u64 = 30_000;
+
+// Sell delay randomized within ±jitter_ms, so exits do not follow a fixed pattern
+fn jittered_sell_delay(delay_ms: u64, jitter_ms: u64) -> u64 {
+    if jitter_ms == 0 {
+        return delay_ms;
+    }
+    let offset = rand::thread_rng().gen_range(-(jitter_ms as i64)..=jitter_ms as i64);
+    (delay_ms as i64 + offset).max(0) as u64
+}
+
+// Maximum time to wait for a sell to confirm before giving up on closing its token account
+const SELL_CONFIRMATION_TIMEOUT_MS: u64 = 30_000;
+
+// Closes the emptied token account once the sell is confirmed, reclaiming its rent
+struct CloseTokenAccount {
+    config: Arc<Config>,
+    rpc_client: Arc<RpcClient>,
+}
+
+impl SellCleanupHook for CloseTokenAccount {
+    fn name(&self) -> &'static str {
+        "close_token_account"
+    }
+
+    fn run<'a>(&'a self, mint: &'a str, result: &'a SellResult) -> CleanupFuture<'a> {
+        Box::pin(async move {
+            let signature = match &result.signature {
+                Some(signature) => Signature::from_str(signature)?,
+                None => return Ok(()),
+            };
+            let mint_pubkey = Pubkey::from_str(mint)?;
+            let config = self.config.clone();
+            let rpc_client = self.rpc_client.clone();
+
+            // Wait for the sell in the background so the sell loop is not held up
+            tokio::spawn(async move {
+                match wait_for_commitment(&rpc_client, &signature, CommitmentConfig::confirmed(), SELL_CONFIRMATION_TIMEOUT_MS).await {
+                    ConfirmationStatus::Confirmed => {
+                        if let Err(e) = pump_close_token_account(&config.rpc_url, &config.private_key, mint_pubkey).await {
+                            println!("Failed to close token account of {}: {:?}", mint_pubkey, e);
+                        }
+                    }
+                    status => println!("Not closing token account of {}, sell {}: {:?}", mint_pubkey, signature, status),
+                }
+            });
+            Ok(())
+        })
+    }
+}
+
+// Maximum time to wait for a buy to confirm before giving up on checking its fill price
+const FILL_CONFIRMATION_TIMEOUT_MS: u64 = 30_000;
+
+// Longest wait for the feed to reach the next slot before retrying a buy anyway (about one slot)
+const NEXT_SLOT_WAIT_MS: u64 = 400;
+const NEXT_SLOT_POLL_MS: u64 = 20;
+
+// Confirms sent buys for CONFIRM_BUYS, holding only what confirming needs so that it runs without
+// the trader lock
+#[derive(Clone)]
+pub struct BuyConfirmer {
+    rpc_client: Arc<RpcClient>,
+    config: Arc<Config>,
+}
+
+impl BuyConfirmer {
+    // Wait for a sent buy to be confirmed; returns the token amount to record, None if the buy
+    // failed or its unconfirmed outcome is treated as failed (CONFIRM_TIMEOUT_BEHAVIOR)
+    pub async fn confirm(&self, unconfirmed: &UnconfirmedBuy) -> Option<u64> {
+        let (mint, signature, token_amount) = (&unconfirmed.buy.mint_pubkey, &unconfirmed.signature, unconfirmed.buy.token_amount);
+        let parsed = match Signature::from_str(signature) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                println!("Cannot confirm buy {}: {}", signature, e);
+                return Some(token_amount);
+            }
+        };
+
+        match wait_for_commitment(&self.rpc_client, &parsed, CommitmentConfig::confirmed(), self.config.confirm_timeout_ms).await {
+            ConfirmationStatus::Confirmed => Some(token_amount),
+            ConfirmationStatus::Failed(err) => {
+                println!("Buy of {} failed on chain: {} ({})", mint, signature, err);
+                None
+            }
+            // The buy may still land later
+            ConfirmationStatus::TimedOut => match self.config.confirm_timeout_behavior {
+                ConfirmTimeoutBehavior::AssumeSuccess => {
+                    println!("Buy of {} not confirmed within {}ms, assuming it landed", mint, self.config.confirm_timeout_ms);
+                    Some(token_amount)
+                }
+                ConfirmTimeoutBehavior::Skip => {
+                    println!("Buy of {} not confirmed within {}ms, skipping it", mint, self.config.confirm_timeout_ms);
+                    None
+                }
+                ConfirmTimeoutBehavior::VerifyBalance => {
+                    // A missing token account reads as 0, i.e. the buy has not landed
+                    match get_token_balance(&self.config.rpc_url, &self.config.private_key, *mint).await {
+                        Ok(received) if received > 0 => {
+                            println!("Buy of {} not confirmed within {}ms, but {} tokens were received", mint, self.config.confirm_timeout_ms, received);
+                            Some(received)
+                        }
+                        Ok(_) => {
+                            println!("Buy of {} not confirmed within {}ms and no tokens received, skipping it", mint, self.config.confirm_timeout_ms);
+                            None
+                        }
+                        Err(e) => {
+                            println!("Buy of {} not confirmed within {}ms and balance unavailable ({}), skipping it", mint, self.config.confirm_timeout_ms, e);
+                            None
+                        }
+                    }
+                }
+            },
+        }
+    }
+}
+
+// Compares the price actually paid by confirmed snipes with the price that triggered them
+struct FillPriceMonitor {
+    rpc_client: Arc<RpcClient>,
+    alert_bps: u64,   // Alert when the fill is more than this above the trigger price
+    token_decimals: u8,
+    tip_lamports: u64, // Jito tip paid by every bundled buy, not part of the fill
+    checked: AtomicU64,
+    alerts: AtomicU64,
+}
+
+impl FillPriceMonitor {
+    async fn check(&self, mint: &str, signature: Signature, token_amount: u64, trigger_price: f64, include_ata: bool) {
+        match wait_for_commitment(&self.rpc_client, &signature, CommitmentConfig::confirmed(), FILL_CONFIRMATION_TIMEOUT_MS).await {
+            ConfirmationStatus::Confirmed => {}
+            status => {
+                println!("Fill price of {} not checked, buy {}: {:?}", mint, signature, status);
+                return;
+            }
+        }
+
+        let sol_cost = match fetch_buy_sol_cost(&self.rpc_client, &signature, include_ata, self.tip_lamports).await {
+            Ok(sol_cost) => sol_cost,
+            Err(e) => {
+                println!("Failed to get fill price of {}: {}", mint, e);
+                return;
+            }
+        };
+
+        if token_amount == 0 || trigger_price <= 0.0 {
+            return;
+        }
+
+        // SOL per token, same unit as the trigger price
+        let fill_price = (sol_cost as f64 / 1_000_000_000.0) / (token_amount as f64 / 10f64.powi(self.token_decimals as i32));
+        let deviation_bps = ((fill_price / trigger_price - 1.0) * 10_000.0).round() as i64;
+        let checked = self.checked.fetch_add(1, Ordering::Relaxed) + 1;
+
+        println!("Fill price of {}: {:.10} SOL/token, trigger {:.10} ({:+} bps)", mint, fill_price, trigger_price, deviation_bps);
+
+        if deviation_bps > self.alert_bps as i64 {
+            let alerts = self.alerts.fetch_add(1, Ordering::Relaxed) + 1;
+            println!("ALERT: snipe of {} filled {} bps above its trigger price (limit {} bps), likely front-run or a fast-moving curve - {} of {} checked snipes alerted",
+                     mint, deviation_bps, self.alert_bps, alerts, checked);
+        }
+    }
+}
+
+// Landing outcomes of sent buys, for the landing rate
+#[derive(Default)]
+pub struct LandingStats {
+    landed: AtomicU64,
+    dropped: AtomicU64,
+    errored: AtomicU64,
+}
+
+impl LandingStats {
+    pub fn landed(&self) -> u64 {
+        self.landed.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn errored(&self) -> u64 {
+        self.errored.load(Ordering::Relaxed)
+    }
+}
+
+// Status poll interval of tracked buys
+const LANDING_POLL_MS: u64 = 1_000;
+
+// Follows every sent buy until it lands or LANDING_TIMEOUT_MS passes. Buys are sent without
+// preflight or RPC retries, so one the leader drops is never reported; this tells a dropped buy
+// (fee too low, leader skipped it) apart from one that landed with an error (invalid buy).
+struct LandingTracker {
+    rpc_client: Arc<RpcClient>,
+    timeout_ms: u64,
+    snipe_log: Option<SnipeLog>,
+    stats: Arc<LandingStats>,
+    fee_history: Option<Arc<FeeHistory>>, // None when FEE_HISTORY_SIZE is 0
+    fee_store: Option<Arc<RedisClient>>,  // Persists the fee samples, None unless PERSIST_FEE_HISTORY
+    fee_history_size: usize,
+}
+
+impl LandingTracker {
+    // Track a sent buy transaction of one or more mints (batch buys), sent with the given compute unit price
+    fn track(self: &Arc<Self>, mints: Vec<String>, signature: &str, compute_unit_price: u64) {
+        let signature = match Signature::from_str(signature) {
+            Ok(signature) => signature,
+            Err(_) => return,
+        };
+        let tracker = self.clone();
+        tokio::spawn(async move {
+            let sent_at = Instant::now();
+            let outcome = tracker.wait_for_landing(&signature).await;
+            match &outcome {
+                SnipeOutcome::Landed => tracker.stats.landed.fetch_add(1, Ordering::Relaxed),
+                SnipeOutcome::Dropped => tracker.stats.dropped.fetch_add(1, Ordering::Relaxed),
+                _ => tracker.stats.errored.fetch_add(1, Ordering::Relaxed),
+            };
+
+            if let Some(fee_history) = &tracker.fee_history {
+                let sample = FeeSample { fee: compute_unit_price, landed: !matches!(outcome, SnipeOutcome::Dropped) };
+                fee_history.record(sample);
+                if let Some(fee_store) = &tracker.fee_store {
+                    if let Err(e) = fee_store.record_fee_sample(&sample.encode(), tracker.fee_history_size).await {
+                        println!("Failed to persist fee sample of {}: {:?}", signature, e);
+                    }
+                }
+            }
+
+            let landed = tracker.stats.landed();
+            let total = landed + tracker.stats.dropped() + tracker.stats.errored();
+            println!("Buy {} of {} {} after {}ms (landing rate {}/{})",
+                     signature, mints.join(", "), outcome.parts().0, sent_at.elapsed().as_millis(), landed, total);
+            if let Some(snipe_log) = &tracker.snipe_log {
+                let signature = signature.to_string();
+                for mint in &mints {
+                    snipe_log.log_snipe_outcome(mint, 0, 0, Some(&signature), outcome.clone(), sent_at.elapsed().as_millis() as u64);
+                }
+            }
+        });
+    }
+
+    async fn wait_for_landing(&self, signature: &Signature) -> SnipeOutcome {
+        let deadline = Instant::now() + Duration::from_millis(self.timeout_ms);
+        loop {
+            match self.rpc_client.get_signature_statuses(&[*signature]).await {
+                Ok(response) => {
+                    if let Some(Some(status)) = response.value.first() {
+                        return match &status.err {
+                            Some(err) => SnipeOutcome::Errored(err.to_string()),
+                            None => SnipeOutcome::Landed,
+                        };
+                    }
+                }
+                Err(e) => println!("Failed to get landing status of {}: {:?}", signature, e),
+            }
+
+            if Instant::now() >= deadline {
+                return SnipeOutcome::Dropped;
+            }
+            sleep(Duration::from_millis(LANDING_POLL_MS)).await;
+        }
+    }
+}
 
 pub struct AutoTrader {
     redis_client: Arc<RedisClient>,
-    rpc_url: String,
-    private_key: String,
+    config: Arc<Config>,
     running: bool,
-    min_sol_price: u64,
-    max_sol_price: u64,
-    buy_amount: u64,     // Buy amount (lamports)
-    sell_delay_ms: u64,  // Sell delay time (milliseconds)
     blockhash_cache: Arc<BlockhashCache>, // Add blockhash cache
+    pipeline_start: Arc<Mutex<HashMap<String, PipelineStart>>>, // Detection and lock times per mint, for end-to-end latency logging
+    transaction_options: TransactionOptions, // Options applied to every buy and sell send
+    priority_fee: PriorityFeeOracle, // Buy priority fee from the fee API, when configured
+    fee_history: Option<Arc<FeeHistory>>, // Fees of tracked buys, None when not recorded
+    cleanup_hooks: SellCleanupHooks, // Steps run after every sell attempt
+    snipe_limiter: Option<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>, // None when snipes are unlimited
+    control: Arc<TraderControl>, // Halt/resume/force sell, also driven by Redis commands
+    position_journal: Option<Arc<dyn PositionStore>>, // Durable copy of positions, trades and entry reserves
+    fill_price_monitor: Option<Arc<FillPriceMonitor>>, // None when fill price alerts are disabled
+    landing_tracker: Option<Arc<LandingTracker>>, // None when TRACK_LANDING is off
+    inventory: Arc<RwLock<PositionInventory>>, // Open positions, read concurrently, written on buy/sell only
+    feed_slot: FeedSlot, // Latest slot seen on the Shredstream feed
+    snipe_tasks: SnipeTasks, // In-flight snipe tasks spawned by the processor
+    rpc_client: Arc<RpcClient>, // Buy confirmations and balance reads
+    stats: Arc<AutoTraderStats>,
+    trade_limits: TradeLimits, // Snipe range and buy amount, converted from USD when configured
+    congestion: CongestionState, // Set by the congestion monitor while snipes are paused
+    nonce_manager: Option<Arc<NonceManager>>, // None when buys use recent blockhashes
+    snipe_log: Option<SnipeLog>, // None when SNIPE_LOG_FILE is empty
+    instance_id: String, // Unique per process, identifies this instance's snipe reservations in Redis
+    frame_capture: Option<FrameCapture>, // Raw frames dumped when a snipe fails
+    slot_stats: Arc<SlotStats>, // Snipe activity per slot, logged by SlotStats::on_slot_complete
 }
 
-impl AutoTrader {
-    // Create a new auto trader, now requires asynchronous initialization
-    pub async fn new(
-        redis_client: Arc<RedisClient>,
-        rpc_url: String,
-        private_key: String,
-    ) -> Self {
-        // Default settings
-        let min_sol_price = 500_000_000; // 0.5 SOL
-        let max_sol_price = 1_000_000_000; // 1 SOL
-        let buy_amount = 100_000_000; // 0.1 SOL
-        let sell_delay_ms = 5000; // Auto sell after 5 seconds
+impl AutoTrader {
+    // Create a new auto trader; price range, buy amount and sell delay are read from the config
+    pub async fn new(redis_client: Arc<RedisClient>, config: Arc<Config>) -> Self {
+        // Create blockhash cache, reduce cache time to 500ms to keep blockhash updated without frequent requests
+        // With several nodes configured, the blockhash most of them return is used
+        let blockhash_rpc_urls: Vec<&str> = config.blockhash_rpc_urls.iter().map(String::as_str).collect();
+        let blockhash_cache = Arc::new(BlockhashCache::new_with_consensus(&blockhash_rpc_urls, 500));
+
+        let transaction_options = TransactionOptions {
+            duplicate_as_success: config.duplicate_as_success,
+            ata_mode: config.ata_mode,
+            max_retries: config.rpc_send_max_retries as usize,
+            nonce_account: None, // Set per buy from the nonce manager
+            buy_compute_unit_price: config.buy_compute_unit_price,
+            sell_compute_unit_price: config.sell_compute_unit_price,
+            sell_dry_run: config.sell_dry_run,
+            jito_bundle: config.jito_bundle_url.clone().map(|url| JitoBundle { url, tip_lamports: config.jito_tip_lamports }),
+        };
+
+        // Built-in cleanup: drop the position from Redis, append the sell to the trade history and
+        // optionally reclaim the token account rent
+        let inventory = Arc::new(RwLock::new(PositionInventory::default()));
+        let mut hooks: Vec<Box<dyn SellCleanupHook>> = vec![
+            Box::new(RedisPositionCleanup { redis_client: redis_client.clone() }),
+            Box::new(TradeEventLog { redis_client: redis_client.clone() }),
+            Box::new(InventoryCleanup { inventory: inventory.clone() }),
+        ];
+        if config.close_token_accounts {
+            hooks.push(Box::new(CloseTokenAccount {
+                config: config.clone(),
+                rpc_client: Arc::new(RpcClient::new(config.rpc_url.clone())),
+            }));
+        }
+        let cleanup_hooks: SellCleanupHooks = Arc::new(RwLock::new(hooks));
+
+        // Cap the number of snipes per minute, 0 means unlimited
+        let snipe_limiter = NonZeroU32::new(config.max_snipes_per_minute)
+            .map(|max_snipes| RateLimiter::direct(Quota::per_minute(max_snipes)));
+
+        // Check fills against the trigger price, 0 disables the check
+        let fill_price_monitor = (config.fill_price_alert_bps > 0).then(|| Arc::new(FillPriceMonitor {
+            rpc_client: Arc::new(RpcClient::new(config.rpc_url.clone())),
+            alert_bps: config.fill_price_alert_bps,
+            token_decimals: config.token_decimals,
+            tip_lamports: transaction_options.jito_bundle.as_ref().map_or(0, |bundle| bundle.tip_lamports),
+            checked: AtomicU64::new(0),
+            alerts: AtomicU64::new(0),
+        }));
+
+        let control = Arc::new(TraderControl {
+            redis_client: redis_client.clone(),
+            halted: AtomicBool::new(false),
+        });
+
+        let rpc_client = Arc::new(RpcClient::new_with_commitment(config.rpc_url.clone(), CommitmentConfig::confirmed()));
+        let trade_limits = TradeLimits::new(config.clone());
+
+        // Durable nonce for buys, when a nonce account is configured for the wallet
+        let nonce_manager = match (&config.nonce_account, parse_keypair(&config.private_key)) {
+            (Some(nonce_account), Ok(keypair)) => match Pubkey::from_str(nonce_account) {
+                Ok(nonce_account) => {
+                    let mut nonce_manager = NonceManager::new(rpc_client.clone());
+                    nonce_manager.add_account(keypair.pubkey(), nonce_account);
+                    println!("Buys use durable nonce account {}", nonce_account);
+                    Some(Arc::new(nonce_manager))
+                }
+                Err(e) => {
+                    println!("Invalid NONCE_ACCOUNT {}, using recent blockhashes: {}", nonce_account, e);
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        let snipe_log = config.snipe_log_file.as_deref().and_then(|path| match SnipeLog::open(path) {
+            Ok(snipe_log) => {
+                println!("Logging snipe outcomes to {}", path);
+                Some(snipe_log)
+            }
+            Err(e) => {
+                println!("Failed to open snipe log {}, snipe outcomes are not logged: {}", path, e);
+                None
+            }
+        });
+
+        // Fees of tracked buys, continuing from the persisted samples of earlier runs
+        let fee_history = (config.track_landing && config.fee_history_size > 0).then(|| Arc::new(FeeHistory::new(&config)));
+        if config.dynamic_fee && fee_history.is_none() {
+            println!("WARNING: DYNAMIC_FEE needs TRACK_LANDING and FEE_HISTORY_SIZE > 0 to learn from landed buys, not adjusting the buy fee");
+        }
+        if let (Some(fee_history), true) = (&fee_history, config.persist_fee_history) {
+            match redis_client.get_fee_samples(config.fee_history_size).await {
+                Ok(samples) => {
+                    let samples: Vec<FeeSample> = samples.iter().rev().filter_map(|sample| FeeSample::decode(sample)).collect();
+                    println!("Loaded {} fee samples of earlier buys", samples.len());
+                    samples.into_iter().for_each(|sample| fee_history.record(sample));
+                }
+                Err(e) => println!("Failed to load fee samples, starting without: {:?}", e),
+            }
+        }
+
+        // Follow sent buys until they land or are dropped
+        let landing_tracker = config.track_landing.then(|| Arc::new(LandingTracker {
+            rpc_client: Arc::new(RpcClient::new(config.rpc_url.clone())),
+            timeout_ms: config.landing_timeout_ms,
+            snipe_log: snipe_log.clone(),
+            stats: Arc::new(LandingStats::default()),
+            fee_history: fee_history.clone(),
+            fee_store: config.persist_fee_history.then(|| redis_client.clone()),
+            fee_history_size: config.fee_history_size,
+        }));
 
-        // Create blockhash cache, reduce cache time to 500ms to keep blockhash updated without frequent requests
-        let blockhash_cache = Arc::new(BlockhashCache::new(&rpc_url, 500));
+        let priority_fee = PriorityFeeOracle::new(&config);
 
         Self {
             redis_client,
-            rpc_url,
-            private_key,
+            config,
             running: false,
-            min_sol_price,
-            max_sol_price,
-            buy_amount,
-            sell_delay_ms,
             blockhash_cache,
+            pipeline_start: Arc::new(Mutex::new(HashMap::new())),
+            transaction_options,
+            priority_fee,
+            fee_history,
+            cleanup_hooks,
+            snipe_limiter,
+            control,
+            position_journal: None,
+            fill_price_monitor,
+            landing_tracker,
+            inventory,
+            feed_slot: FeedSlot::default(),
+            snipe_tasks: SnipeTasks::default(),
+            rpc_client,
+            stats: Arc::new(AutoTraderStats::default()),
+            trade_limits,
+            congestion: CongestionState::default(),
+            nonce_manager,
+            snipe_log,
+            instance_id: Uuid::new_v4().to_string(),
+            frame_capture: None,
+            slot_stats: Arc::new(SlotStats::default()),
+        }
+    }
+
+    pub fn stats(&self) -> Arc<AutoTraderStats> {
+        self.stats.clone()
+    }
+
+    pub fn slot_stats(&self) -> Arc<SlotStats> {
+        self.slot_stats.clone()
+    }
+
+    pub fn trade_limits(&self) -> TradeLimits {
+        self.trade_limits.clone()
+    }
+
+    pub fn blockhash_cache(&self) -> Arc<BlockhashCache> {
+        self.blockhash_cache.clone()
+    }
+
+    pub fn set_frame_capture(&mut self, frame_capture: FrameCapture) {
+        self.frame_capture = Some(frame_capture);
+    }
+
+    fn buy_max_blockhash_age(&self) -> Duration {
+        Duration::from_millis(self.config.buy_max_blockhash_age_ms)
+    }
+
+    // Landing outcomes of sent buys, None when TRACK_LANDING is off
+    pub fn landing_stats(&self) -> Option<Arc<LandingStats>> {
+        self.landing_tracker.as_ref().map(|tracker| tracker.stats.clone())
+    }
+
+    pub fn priority_fee(&self) -> PriorityFeeOracle {
+        self.priority_fee.clone()
+    }
+
+    // Fees of recent tracked buys, None when they are not recorded
+    pub fn fee_history(&self) -> Option<Arc<FeeHistory>> {
+        self.fee_history.clone()
+    }
+
+    // Compute unit price of the next buy: with DYNAMIC_FEE the fee that has been landing, once
+    // enough buys landed; otherwise the current fee from the fee API, or the static fee
+    fn buy_compute_unit_price(&self) -> u64 {
+        let fallback = self.priority_fee.buy_compute_unit_price(self.transaction_options.buy_compute_unit_price);
+        if !self.config.dynamic_fee {
+            return fallback;
+        }
+        match self.fee_history.as_ref().and_then(|fee_history| fee_history.recommended_fee()) {
+            Some(fee) => match self.config.priority_fee_max {
+                0 => fee,
+                max => fee.min(max),
+            },
+            None => fallback,
+        }
+    }
+
+    // Send options of a buy, with the current buy priority fee
+    fn buy_transaction_options(&self) -> TransactionOptions {
+        let mut transaction_options = self.transaction_options.clone();
+        transaction_options.buy_compute_unit_price = self.buy_compute_unit_price();
+        transaction_options
+    }
+
+    pub fn congestion(&self) -> CongestionState {
+        self.congestion.clone()
+    }
+
+    // Handle for registering snipe tasks and waiting for them without the trader lock
+    pub fn snipe_tasks(&self) -> SnipeTasks {
+        self.snipe_tasks.clone()
+    }
+
+    // Confirms the buys returned by snipe_token and snipe_batch without the trader lock
+    pub fn buy_confirmer(&self) -> BuyConfirmer {
+        BuyConfirmer { rpc_client: self.rpc_client.clone(), config: self.config.clone() }
+    }
+
+    // Wait for in-flight snipes before shutting down; returns how many completed within the timeout
+    pub async fn wait_for_snipes_to_complete(&self, timeout: Duration) -> usize {
+        self.snipe_tasks.wait(timeout).await
+    }
+
+    // Use the processor's view of the latest feed slot as the current slot
+    pub fn set_feed_slot(&mut self, feed_slot: FeedSlot) {
+        self.feed_slot = feed_slot;
+    }
+
+    // Latest slot seen on the feed, None before the first batch
+    pub fn current_slot(&self) -> Option<u64> {
+        self.feed_slot.get()
+    }
+
+    // Wait until the feed moves past the current slot, or about one slot without a feed
+    async fn wait_for_next_slot(&self) {
+        let start_slot = self.feed_slot.get();
+        let deadline = Instant::now() + Duration::from_millis(NEXT_SLOT_WAIT_MS);
+        while Instant::now() < deadline {
+            sleep(Duration::from_millis(NEXT_SLOT_POLL_MS)).await;
+            if start_slot.is_some() && self.feed_slot.get() > start_slot {
+                return;
+            }
+        }
+    }
+
+    // Number of open positions
+    pub async fn count_positions(&self) -> usize {
+        self.inventory.read().await.count_positions()
+    }
+
+    // Open position of a mint, if any
+    pub async fn get_position(&self, mint: &str) -> Option<Position> {
+        self.inventory.read().await.get_position(mint)
+    }
+
+    // All open positions
+    pub async fn list_positions(&self) -> Vec<(String, Position)> {
+        self.inventory.read().await.list_positions()
+    }
+
+    // Shared handle for readers outside the trader lock
+    pub fn inventory(&self) -> Arc<RwLock<PositionInventory>> {
+        self.inventory.clone()
+    }
+
+    // Mirror buys, sells and entry reserves to an additional store; Redis stays the live state
+    pub async fn set_position_journal(&mut self, store: Arc<dyn PositionStore>) {
+        println!("Journaling positions to {} store", store.name());
+        self.add_cleanup_hook(Box::new(PositionJournal { store: store.clone() })).await;
+        self.position_journal = Some(store);
+    }
+
+    // Stop opening new positions
+    pub fn halt(&self) {
+        self.control.halt();
+    }
+
+    // Halt/resume state, for the control endpoints and signals
+    pub fn control(&self) -> Arc<TraderControl> {
+        self.control.clone()
+    }
+
+    // Resume opening new positions
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    // Sell an open position on the next sell loop iteration
+    pub async fn force_sell_mint(&self, mint: &str) {
+        self.control.force_sell_mint(mint).await;
+    }
+
+    // Drop the timed sell of a position that must not be sold through the bonding curve anymore
+    // (curve migrated, already exited). Returns whether a queued sell or position was removed.
+    pub async fn cancel_pending_sell(&self, mint: &str) -> Result<bool, SniperError> {
+        let queued = self.redis_client.remove_sold_mint(mint).await?;
+        let held = self.inventory.write().await.remove(mint);
+        if queued || held {
+            println!("Cancelled pending sell of {}", mint);
+        }
+        Ok(queued || held)
+    }
+
+    // Append a snipe attempt outcome to the snipe log, if enabled
+    pub fn log_snipe_outcome(&self, mint: &str, sol_spent: u64, token_amount: u64, tx_sig: Option<&str>, outcome: SnipeOutcome, latency: Duration) {
+        if let Some(snipe_log) = &self.snipe_log {
+            snipe_log.log_snipe_outcome(mint, sol_spent, token_amount, tx_sig, outcome, latency.as_millis() as u64);
+        }
+    }
+
+    // Give up the snipe reservation of a mint after a buy that was not sent, so another task or
+    // instance may still snipe it
+    async fn release_snipe_slot(&self, mint: &str) {
+        if let Err(e) = self.redis_client.release_snipe_slot(mint, &self.instance_id).await {
+            println!("Failed to release snipe reservation of {}: {:?}", mint, e);
         }
     }
 
-    // Set price range
-    pub async fn set_price_range(&mut self, min_sol_price: u64, max_sol_price: u64) {
-        self.min_sol_price = min_sol_price;
-        self.max_sol_price = max_sol_price;
-        println!("Set sniping price range: {} - {} SOL",
-                 min_sol_price as f64 / 1_000_000_000.0,
-                 max_sol_price as f64 / 1_000_000_000.0);
+    // Log a snipe skipped by evaluate_snipe. Buys outside the price range are not snipe attempts
+    // and are not logged, they are the bulk of the feed.
+    pub fn log_snipe_skip(&self, mint: &str, reason: SkipReason, detected_at: Instant, slot: u64) {
+        if reason != SkipReason::OutOfPriceRange {
+            self.slot_stats.count(Some(slot), |summary| {
+                summary.eligible += 1;
+                summary.skipped += 1;
+                if reason == SkipReason::RateLimited {
+                    summary.rate_limited += 1;
+                }
+            });
+            self.log_snipe_outcome(mint, 0, 0, None, SnipeOutcome::Skipped(reason.as_str().to_string()), detected_at.elapsed());
+        }
     }
 
-    // Set buy amount
-    pub async fn set_buy_amount(&mut self, buy_amount: u64) {
-        self.buy_amount = buy_amount;
-        println!("Set sniping buy amount: {} SOL", buy_amount as f64 / 1_000_000_000.0);
+    // Register an additional step to run after every sell attempt
+    pub async fn add_cleanup_hook(&self, hook: Box<dyn SellCleanupHook>) {
+        println!("Registered sell cleanup hook: {}", hook.name());
+        self.cleanup_hooks.write().await.push(hook);
     }
 
-    // Set sell delay time
-    pub async fn set_sell_delay(&mut self, sell_delay_ms: u64) {
-        self.sell_delay_ms = sell_delay_ms;
-        println!("Set auto sell delay: {}ms", sell_delay_ms);
+    // Run all registered cleanup steps for a sold (or failed) position
+    pub async fn post_sell_cleanup(&self, mint: &str, result: &SellResult) {
+        run_sell_cleanup(&self.cleanup_hooks, mint, result).await;
     }
 
     // Start the auto trading background task
     pub fn start(&mut self) -> JoinHandle<Result<(), Box<dyn Error + Send + Sync>>> {
         self.running = true;
-        let rpc_url = self.rpc_url.clone();
-        let private_key = self.private_key.clone();
+        let config = self.config.clone();
         let redis_client = self.redis_client.clone();
         let blockhash_cache = self.blockhash_cache.clone(); // Clone cache reference
+        let transaction_options = self.transaction_options.clone();
+        let cleanup_hooks = self.cleanup_hooks.clone();
+        let control = self.control.clone();
+        let feed_slot = self.feed_slot.clone();
+        let rpc_client = self.rpc_client.clone();
+        let inventory = self.inventory.clone();
+        let stats = self.stats.clone();
 
         println!("Starting auto trading background task");
 
+        // Warm the blockhash cache before the first snipe, then refresh it periodically while idle
+        tokio::spawn({
+            let blockhash_cache = blockhash_cache.clone();
+            async move {
+                loop {
+                    blockhash_cache.prefetch().await;
+                    sleep(Duration::from_millis(BLOCKHASH_HEARTBEAT_MS)).await;
+                }
+            }
+        });
+
+        // Route commands published on the Redis command channel
+        tokio::spawn({
+            let redis_client = redis_client.clone();
+            async move {
+                let result = redis_client.subscribe_commands(move |_channel, message| {
+                    let control = control.clone();
+                    Box::pin(async move { control.handle_command(&message).await })
+                }).await;
+
+                if let Err(e) = result {
+                    println!("Command listener stopped: {}", e);
+                }
+            }
+        });
+
         // Create background task to handle auto sell logic
         tokio::spawn(async move {
             // Auto sell check task
             let sell_task = tokio::spawn({
                 let redis_client = redis_client.clone();
-                let rpc_url = rpc_url.clone();
-                let private_key = private_key.clone();
+                let config = config.clone();
                 let blockhash_cache = blockhash_cache.clone(); // Clone cache reference for internal task
+                let transaction_options = transaction_options.clone();
+                let cleanup_hooks = cleanup_hooks.clone();
+                let rpc_client = rpc_client.clone();
+                let inventory = inventory.clone();
+                let stats = stats.clone();
 
                 async move {
                     println!("Starting auto sell check");
 
                     loop {
-                        // Get and remove all tokens to sell - asynchronous version
-                        match redis_client.get_and_remove_mints_to_sell().await {
-                            Ok(mints) => {
+                        // Get and remove all tokens to sell - asynchronous version. A dry run leaves
+                        // them queued, so every due position is re-evaluated on the next check.
+                        let due = if config.sell_dry_run {
+                            redis_client.get_mints_to_sell().await.map(|mints| mints.into_iter().map(|mint| (mint, 0)).collect())
+                        } else {
+                            redis_client.get_and_remove_mints_to_sell().await
+                        };
+                        match due {
+                            Ok(due) => {
+                                let mut mints: Vec<String> = due.iter().map(|(mint, _)| mint.clone()).collect();
+                                let sell_times: HashMap<String, u64> = due.into_iter().filter(|(_, sell_time)| *sell_time > 0).collect();
+
+                                // Hard cap on position duration, independent of all other exit conditions
+                                if config.max_hold_ms > 0 {
+                                    let expired = if config.sell_dry_run {
+                                        redis_client.get_expired_positions(config.max_hold_ms).await
+                                    } else {
+                                        redis_client.get_and_remove_expired_positions(config.max_hold_ms).await
+                                    };
+                                    match expired {
+                                        Ok(expired) => {
+                                            for mint in expired {
+                                                if !mints.contains(&mint) {
+                                                    println!("Max hold time of {}ms reached, force selling: {}", config.max_hold_ms, mint);
+                                                    mints.push(mint);
+                                                }
+                                            }
+                                        },
+                                        Err(e) => println!("Failed to get positions past max hold time: {:?}", e)
+                                    }
+                                }
+
                                 if !mints.is_empty() {
                                     // If there are tokens to sell, get blockhash once beforehand
                                     // This reduces the number of individual hash requests per transaction
-                                    let blockhash = match blockhash_cache.get_latest_blockhash().await {
+                                    let blockhash = match blockhash_cache.get_blockhash_within(Duration::from_millis(config.sell_max_blockhash_age_ms)).await {
                                         Ok(hash) => Some(hash),
                                         Err(e) => {
                                             println!("Failed to get blockhash: {:?}", e);
@@ -112,24 +1355,20 @@ impl AutoTrader {
                                         // Perform auto sell operation
                                         match Pubkey::from_str(&mint) {
                                             Ok(mint_pubkey) => {
-                                                println!("Executing auto sell for: {}", mint);
+                                                match sell_times.get(&mint) {
+                                                    Some(sell_time) => {
+                                                        let held_ms = unix_time_ms().saturating_sub(*sell_time);
+                                                        info!(mint = %mint, held_ms, "Executing sell for {} (held for {}ms)", mint, held_ms);
+                                                    }
+                                                    // Force sold before its scheduled sell time
+                                                    None => info!(mint = %mint, "Executing sell for {}", mint),
+                                                }
 
                                                 // Get the stored token amount
-                                                match redis_client.get_mint_amount(&mint).await {
+                                                let token_amount = match redis_client.get_mint_amount(&mint).await {
                                                     Ok(Some(token_amount)) => {
                                                         println!("Attempting to sell: {} tokens", token_amount);
-
-                                                        if let Err(e) = pump_sell(
-                                                            &rpc_url,
-                                                            &private_key,
-                                                            mint_pubkey,
-                                                            token_amount, // Use the stored token amount
-                                                            0, // Minimum receive 0 SOL
-                                                            None, // Do not use a specific slot
-                                                            blockhash.clone() // Use the cached blockhash
-                                                        ).await {
-                                                            println!("Auto sell failed: {:?}", e);
-                                                        }
+                                                        token_amount
                                                     },
                                                     Ok(None) => {
                                                         // If stored amount is not found, use an estimated amount
@@ -148,30 +1387,62 @@ impl AutoTrader {
                                                         // Reduce amount by 15% to avoid slippage errors
                                                         let reduced_amount = token_amount_no_precision * 0.85;
 
-                                                        // Precision factor is 10^6
-                                                        let precision_factor = 1_000_000.0;
+                                                        // Precision factor is 10^decimals
+                                                        let precision_factor = 10f64.powi(config.token_decimals as i32);
 
                                                         // Calculate token amount with precision, floor
                                                         let token_amount = (reduced_amount * precision_factor).floor() as u64;
 
                                                         println!("Stored token amount not found, using estimated value: {} tokens (with precision)", token_amount);
-
-                                                        if let Err(e) = pump_sell(
-                                                            &rpc_url,
-                                                            &private_key,
-                                                            mint_pubkey,
-                                                            token_amount,
-                                                            0, // Minimum receive 0 SOL
-                                                            None, // Do not use a specific slot
-                                                            blockhash.clone() // Use the cached blockhash
-                                                        ).await {
-                                                            println!("Auto sell failed: {:?}", e);
-                                                        }
+                                                        token_amount
                                                     },
                                                     Err(e) => {
                                                         println!("Failed to get token amount: {:?}", e);
+                                                        continue;
+                                                    }
+                                                };
+
+                                                // A sell of dust costs more in fees than it returns
+                                                if let Some(reason) = dust_reason(&config, &rpc_client, &mint_pubkey, token_amount).await {
+                                                    println!("Dust position {} ({}), dropping it without selling", mint, reason);
+                                                    if config.sell_dry_run {
+                                                        continue;
+                                                    }
+                                                    if let Err(e) = redis_client.remove_sold_mint(&mint).await {
+                                                        println!("Failed to remove dust position {}: {:?}", mint, e);
+                                                    }
+                                                    inventory.write().await.remove(&mint);
+                                                    continue;
+                                                }
+
+                                                let slot = feed_slot.get();
+                                                let signature = match pump_sell(
+                                                    &config.rpc_url,
+                                                    &config.private_key,
+                                                    mint_pubkey,
+                                                    token_amount,
+                                                    0, // Minimum receive 0 SOL
+                                                    slot, // Latest feed slot, for logging
+                                                    blockhash, // Use the cached blockhash
+                                                    &transaction_options
+                                                ).await {
+                                                    Ok(signature) => {
+                                                        stats.sells.fetch_add(1, Ordering::Relaxed);
+                                                        Some(signature)
+                                                    }
+                                                    Err(e) => {
+                                                        println!("Auto sell failed: {:?}", e);
+                                                        None
                                                     }
+                                                };
+
+                                                // Nothing was sent, the position stays as it is
+                                                if config.sell_dry_run {
+                                                    continue;
                                                 }
+
+                                                let result = SellResult { token_amount, signature, slot };
+                                                run_sell_cleanup(&cleanup_hooks, &mint, &result).await;
                                             },
                                             Err(e) => {
                                                 println!("Invalid token address: {} - {:?}", mint, e);
@@ -198,47 +1469,155 @@ impl AutoTrader {
         })
     }
 
-    // Snipe a specific token
-    pub async fn snipe_token(&self, token_mint: &str, token_price: f64, slot: Option<u64>) -> Result<(), Box<dyn Error>> {
-        // Convert token address to Pubkey
-        let mint_pubkey = Pubkey::from_str(token_mint)?;
+    // Record when the buy that triggered a snipe for this mint was detected and when the snipe
+    // got the trader lock
+    pub async fn record_pipeline_start(&self, token_mint: &str, start: PipelineStart) {
+        self.pipeline_start.lock().await.insert(token_mint.to_string(), start);
+    }
 
-        // Use the configured buy amount
-        let buy_sol = self.buy_amount;
+    // Log the full shred receipt -> snipe submission latency, broken down by phase: deciding the
+    // snipe, waiting for the trader lock, the pre-send guards and blockhash, building the first
+    // transaction, its send, and the retries after write-lock contention with their slot waits
+    async fn log_pipeline_latency(&self, token_mint: &str, times: &SendTimes) {
+        let start = match self.pipeline_start.lock().await.remove(token_mint) {
+            Some(start) => start,
+            None => return,
+        };
+        let first_sent = times.first_sent.unwrap_or_else(Instant::now);
+        // A first send that failed has no build instant, its build is counted in first_send
+        let built = times.built.unwrap_or(times.started);
+        println!("Full pipeline latency for {}: {}ms (decide: {}ms, lock_wait: {}ms, prepare: {}ms, tx_build: {}ms, first_send: {}ms, retries: {} in {}ms)",
+                 token_mint,
+                 start.detected_at.elapsed().as_millis(),
+                 start.lock_requested.duration_since(start.detected_at).as_millis(),
+                 start.locked_at.duration_since(start.lock_requested).as_millis(),
+                 times.started.duration_since(start.locked_at).as_millis(),
+                 built.duration_since(times.started).as_millis(),
+                 first_sent.duration_since(built).as_millis(),
+                 times.retries,
+                 first_sent.elapsed().as_millis());
+    }
 
-        // Convert buy_sol to SOL units (from lamports)
-        let buy_sol_f64 = buy_sol as f64 / 1_000_000_000.0;
+    // Token amount and max SOL cost of a buy with the configured buy amount
+    fn size_buy(&self, token_mint: &str, token_price: f64, reserves: Option<TokenReserves>, include_ata: bool) -> Result<(u64, u64), Box<dyn Error>> {
+        // Use the configured buy amount
+        let buy_sol = self.trade_limits.buy_sol_amount();
 
-        // Ensure price is not zero to avoid division by zero
-        if token_price <= 0.0 {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Invalid token price: {}", token_price)
-            )));
+        // Fees, the token account rent and a bundle tip are paid on top of max_sol_cost, so keep
+        // them inside the budget instead of committing all of it to the curve
+        let tip = self.transaction_options.jito_bundle.as_ref().map_or(0, |bundle| bundle.tip_lamports);
+        let fee_reserve = buy_fee_reserve(include_ata, self.buy_compute_unit_price()).saturating_add(tip);
+        let max_sol_cost = buy_sol.saturating_sub(fee_reserve);
+        if max_sol_cost == 0 {
+            return Err(format!("Buy amount of {} lamports does not cover fees and rent of {} lamports", buy_sol, fee_reserve).into());
         }
 
-        // Calculate token amount without precision
-        let token_amount_no_precision = buy_sol_f64 / token_price;
+        // Convert max_sol_cost to SOL units (from lamports)
+        let buy_sol_f64 = max_sol_cost as f64 / 1_000_000_000.0;
 
-        // Precision factor is 10^6
-        let precision_factor = 1_000_000.0;
+        println!("Investment: {} SOL ({} SOL reserved for fees and rent)", buy_sol_f64, fee_reserve as f64 / 1_000_000_000.0);
 
-        // Calculate token amount with precision, floor
-        // Reduce buy amount by 15% to avoid slippage errors
-        let reduced_amount = token_amount_no_precision * 0.85;
-        let token_amount = (reduced_amount * precision_factor).floor() as u64;
+        let token_amount = match (self.config.token_amount_strategy, reserves) {
+            (TokenAmountStrategy::ExactQuote, Some(reserves)) => {
+                let token_amount = quote_buy(reserves.virtual_sol_reserves, reserves.virtual_token_reserves, max_sol_cost);
+                println!("Quoted token amount: {} (with precision)", token_amount);
+                token_amount
+            },
+            (TokenAmountStrategy::Estimated { slippage_bps }, _) => {
+                estimate_token_amount(buy_sol_f64, token_price, slippage_bps, self.config.token_decimals)?
+            },
+            (TokenAmountStrategy::ExactQuote, None) => {
+                println!("No tracked reserves for {}, falling back to estimated token amount", token_mint);
+                estimate_token_amount(buy_sol_f64, token_price, DEFAULT_SLIPPAGE_BPS, self.config.token_decimals)?
+            },
+        };
 
-        // Record the timestamp when sniping starts
-        let start_time = std::time::Instant::now();
+        if token_amount == 0 {
+            return Err(format!("Calculated token amount is zero for {}", token_mint).into());
+        }
 
-        println!("Starting to snipe token {} (slot: {:?})", token_mint, slot);
-        println!("Investment: {} SOL", buy_sol_f64);
-        println!("Actual price: {} SOL/token", token_price);
-        println!("Calculated token amount: {:.2} (no precision)", token_amount_no_precision);
-        println!("Reduced amount: {:.2} (no precision)", reduced_amount);
         println!("Attempting to buy: {} tokens (with precision)", token_amount);
 
-        // Get cached blockhash, prioritize fast path
+        Ok((token_amount, max_sol_cost))
+    }
+
+    // After a successful buy, store token address and purchased amount in Redis, set for auto sell after delay
+    async fn record_buy(&self, token_mint: &str, token_amount: u64, signature: &str, reserves: Option<TokenReserves>, slot: Option<u64>) -> Result<(), Box<dyn Error>> {
+        let trade_event = TradeEvent {
+            side: "buy",
+            mint: token_mint.to_string(),
+            token_amount,
+            sol_amount: self.trade_limits.buy_sol_amount(),
+            signature: signature.to_string(),
+            slot,
+        };
+        let sell_delay_ms = jittered_sell_delay(self.config.sell_delay_ms, self.config.sell_delay_jitter_ms);
+        self.redis_client.atomic_buy_record(token_mint, token_amount, sell_delay_ms, &trade_event).await?;
+        self.inventory.write().await.add(token_mint, token_amount);
+        self.stats.snipes.fetch_add(1, Ordering::Relaxed);
+
+        // The journal is for analysis only, a failure does not affect the position
+        if let Some(journal) = &self.position_journal {
+            if let Err(e) = journal.record_buy(&trade_event, sell_delay_ms).await {
+                println!("Failed to journal buy of {}: {}", token_mint, e);
+            }
+            if let Some(reserves) = reserves {
+                if let Err(e) = journal.record_reserves(token_mint, reserves, slot).await {
+                    println!("Failed to journal reserves of {}: {}", token_mint, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Check the wallet before trading: SOL for buys, positions queued in Redis without tokens on
+    // chain, and empty token accounts still holding rent. Every finding is logged as a warning.
+    pub async fn check_wallet_health(&self) -> Result<WalletHealth, SniperError> {
+        let owner = parse_keypair(&self.config.private_key).map_err(SniperError::InvalidKey)?.pubkey();
+        let sol_balance_lamports = self.rpc_client.get_balance(&owner).await?;
+        let buy_sol = self.trade_limits.buy_sol_amount();
+        let mut health = WalletHealth {
+            sol_balance_lamports,
+            has_sufficient_balance: sol_balance_lamports >= buy_sol,
+            ..WalletHealth::default()
+        };
+        if !health.has_sufficient_balance {
+            health.warnings.push(format!("SOL balance {} lamports is below the buy amount of {} lamports",
+                                         health.sol_balance_lamports, buy_sol));
+        }
+
+        let accounts = self.rpc_client
+            .get_token_accounts_by_owner(&owner, TokenAccountsFilter::ProgramId(spl_token::id()))
+            .await?;
+        let mut empty_accounts = 0;
+        for account in &accounts {
+            match parse_token_account(&account.account) {
+                Some((_, 0)) => empty_accounts += 1,
+                Some(token_account) => health.token_accounts.push(token_account),
+                None => health.warnings.push(format!("Token account {} could not be parsed", account.pubkey)),
+            }
+        }
+        if empty_accounts > 0 {
+            health.warnings.push(format!("{} empty token accounts hold {} lamports of rent, close them to reclaim it",
+                                         empty_accounts, empty_accounts * TOKEN_ACCOUNT_RENT_LAMPORTS));
+        }
+
+        for mint in self.redis_client.get_mints_to_sell().await? {
+            let held = Pubkey::from_str(&mint)
+                .map(|mint| health.token_accounts.iter().any(|(held_mint, _)| *held_mint == mint))
+                .unwrap_or(false);
+            if !held {
+                health.warnings.push(format!("Position {} is queued for sell but the wallet holds none of its tokens", mint));
+            }
+        }
+
+        Ok(health)
+    }
+
+    // Send and confirm a no-op transaction through the same blockhash cache and send options as
+    // snipes, returning the landing time
+    pub async fn self_test(&self) -> Result<Duration, SniperError> {
         let blockhash = match self.blockhash_cache.get_latest_blockhash().await {
             Ok(hash) => Some(hash),
             Err(e) => {
@@ -246,38 +1625,435 @@ impl AutoTrader {
                 None
             }
         };
+        send_self_test(&self.config.rpc_url, &self.config.private_key, blockhash, &self.transaction_options).await
+    }
+
+    // Pre-send guards of a snipe: the per-mint reservation, REENTRY_POLICY, VERIFY_TOKEN_PROGRAM,
+    // the on-chain reserves and the buy size. None if the snipe was skipped, an error if it could
+    // not be sized; both are logged here.
+    async fn prepare_snipe(&self, target: &SnipeTarget, slot: Option<u64>, start_time: Instant) -> Result<Option<PreparedBuy>, String> {
+        let token_mint = target.mint.as_str();
+        let mint_pubkey = Pubkey::from_str(token_mint).map_err(|e| e.to_string())?;
+
+        // At most one snipe per mint, even if duplicate snipe tasks were spawned or several instances
+        // share the Redis server. The reservation is a single SET NX, so there is no window between
+        // checking and setting it.
+        match self.redis_client.reserve_snipe_slot(token_mint, &self.instance_id, SNIPE_SLOT_TTL_MS).await {
+            Ok(true) => {},
+            Ok(false) => {
+                println!("Snipe of {} already started by another task or instance, skipping", token_mint);
+                self.slot_stats.count(slot, |summary| {
+                    summary.skipped += 1;
+                    summary.duplicate += 1;
+                });
+                self.log_snipe_outcome(token_mint, 0, 0, None, SnipeOutcome::Skipped("already_claimed".to_string()), start_time.elapsed());
+                return Ok(None);
+            },
+            Err(e) => println!("Failed to reserve snipe of {}, sniping anyway: {:?}", token_mint, e),
+        }
+
+        // A mint that is still held is only bought again when its position can absorb the buy. Its
+        // token account exists, so a re-entry does not create it again.
+        let held = self.inventory.read().await.get_position(token_mint).is_some();
+        if self.config.reentry_policy == ReentryPolicy::Skip && held {
+            println!("Skipping snipe of {}: position still open (REENTRY_POLICY=skip)", token_mint);
+            self.slot_stats.count(slot, |summary| summary.skipped += 1);
+            self.log_snipe_outcome(token_mint, 0, 0, None, SnipeOutcome::Skipped("position_open".to_string()), start_time.elapsed());
+            self.release_snipe_slot(token_mint).await;
+            return Ok(None);
+        }
+
+        // The instructions hardcode the SPL Token program, a mint of another program would only fail on-chain
+        if self.config.verify_token_program {
+            if let Some(reason) = token_program_mismatch(&self.rpc_client, &mint_pubkey).await {
+                println!("Skipping snipe of {}: {}", token_mint, reason);
+                self.slot_stats.count(slot, |summary| summary.skipped += 1);
+                self.log_snipe_outcome(token_mint, 0, 0, None, SnipeOutcome::Skipped("token_program_mismatch".to_string()), start_time.elapsed());
+                self.release_snipe_slot(token_mint).await;
+                return Ok(None);
+            }
+        }
+
+        println!("Starting to snipe token {} (slot: {:?})", token_mint, slot);
+
+        // Prefer the on-chain reserves; a curve that is not readable yet does not abort the snipe
+        let reserves = if self.config.verify_reserves {
+            match read_bonding_curve(&self.rpc_client, &mint_pubkey, self.config.curve_read_retries, self.config.curve_read_retry_delay_ms).await {
+                Some(state) => Some(TokenReserves {
+                    virtual_sol_reserves: state.virtual_sol_reserves,
+                    virtual_token_reserves: state.virtual_token_reserves,
+                }),
+                None => {
+                    println!("Bonding curve of {} unavailable, using tracked reserves", token_mint);
+                    target.reserves
+                }
+            }
+        } else {
+            target.reserves
+        };
+
+        // The boxed error is not Send, only its message is kept across the release below
+        match self.size_buy(token_mint, target.token_price, reserves, !held).map_err(|e| e.to_string()) {
+            Ok((token_amount, max_sol_cost)) => Ok(Some(PreparedBuy {
+                mint: target.mint.clone(),
+                mint_pubkey,
+                token_price: target.token_price,
+                token_amount,
+                max_sol_cost,
+                include_ata: !held,
+                reserves,
+            })),
+            Err(reason) => {
+                self.slot_stats.count(slot, |summary| {
+                    summary.skipped += 1;
+                    summary.budget += 1;
+                });
+                self.log_snipe_outcome(token_mint, 0, 0, None, SnipeOutcome::Failed(reason.clone()), start_time.elapsed());
+                self.release_snipe_slot(token_mint).await;
+                Err(reason)
+            }
+        }
+    }
+
+    // Blockhash of a buy. With a nonce account the current nonce takes the place of the blockhash
+    // and the buy advances it, otherwise the cached blockhash is used.
+    async fn buy_blockhash(&self, transaction_options: &mut TransactionOptions) -> Result<Option<Hash>, SniperError> {
+        if let Some(nonce_manager) = &self.nonce_manager {
+            let user = parse_keypair(&self.config.private_key).map_err(SniperError::InvalidKey)?.pubkey();
+            let (nonce, nonce_account) = nonce_manager.get_nonce(&user).await?;
+            transaction_options.nonce_account = Some(nonce_account);
+            return Ok(Some(nonce));
+        }
+        Ok(self.cached_buy_blockhash().await)
+    }
+
+    // Cached blockhash within the buy age limit, prioritizing the fast path; None lets the send fetch one
+    async fn cached_buy_blockhash(&self) -> Option<Hash> {
+        match self.blockhash_cache.get_blockhash_within(self.buy_max_blockhash_age()).await {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                println!("Failed to get blockhash: {:?}", e);
+                None
+            }
+        }
+    }
+
+    // After a buy was sent: watch its fill price and record the position. With CONFIRM_BUYS the buy
+    // is returned instead, to be confirmed outside the trader lock and passed to record_confirmed_buy.
+    async fn finish_buy(&self, buy: &PreparedBuy, signature: &str, slot: Option<u64>, elapsed: Duration) -> Result<Option<UnconfirmedBuy>, Box<dyn Error>> {
+        if let (Some(monitor), Ok(parsed)) = (&self.fill_price_monitor, Signature::from_str(signature)) {
+            let monitor = monitor.clone();
+            let mint = buy.mint.clone();
+            let (token_amount, token_price, include_ata) = (buy.token_amount, buy.token_price, buy.include_ata);
+            tokio::spawn(async move {
+                monitor.check(&mint, parsed, token_amount, token_price, include_ata).await;
+            });
+        }
+
+        if self.config.confirm_buys {
+            return Ok(Some(UnconfirmedBuy { signature: signature.to_string(), buy: buy.clone(), slot, elapsed }));
+        }
+        self.log_snipe_outcome(&buy.mint, buy.max_sol_cost, buy.token_amount, Some(signature), SnipeOutcome::Success, elapsed);
+
+        self.record_buy(&buy.mint, buy.token_amount, signature, buy.reserves, slot).await?;
+        Ok(None)
+    }
+
+    // Record a buy after its confirmation, with the token amount BuyConfirmer returned for it
+    pub async fn record_confirmed_buy(&self, unconfirmed: UnconfirmedBuy, token_amount: Option<u64>) -> Result<(), Box<dyn Error>> {
+        let UnconfirmedBuy { signature, buy, slot, elapsed } = unconfirmed;
+        let token_amount = match token_amount {
+            Some(token_amount) => token_amount,
+            None => {
+                self.log_snipe_outcome(&buy.mint, buy.max_sol_cost, buy.token_amount, Some(&signature),
+                                       SnipeOutcome::Failed("not_landed".to_string()), elapsed);
+                return Err(format!("Buy {} of {} did not land", signature, buy.mint).into());
+            }
+        };
+        self.log_snipe_outcome(&buy.mint, buy.max_sol_cost, token_amount, Some(&signature), SnipeOutcome::Success, elapsed);
+
+        self.record_buy(&buy.mint, token_amount, &signature, buy.reserves, slot).await
+    }
+
+    // After a buy that was not sent or failed: log it and give up the reservation of its mint
+    async fn fail_buy(&self, buy: &PreparedBuy, error: &SniperError, elapsed: Duration) {
+        self.log_snipe_outcome(&buy.mint, buy.max_sol_cost, buy.token_amount, None, SnipeOutcome::Failed(error.to_string()), elapsed);
+        self.release_snipe_slot(&buy.mint).await;
+    }
+
+    // Follow a submitted buy bundle in the background, outside the trader lock, and log whether
+    // it landed. Buys sent to the RPC node are followed by the landing tracker instead.
+    fn track_bundle(&self, mints: Vec<String>, sent: &SentBuy) {
+        let (bundle_id, bundle) = match (&sent.bundle_id, &self.transaction_options.jito_bundle) {
+            (Some(bundle_id), Some(bundle)) => (bundle_id.clone(), bundle.url.clone()),
+            _ => return,
+        };
+        let signature = match Signature::from_str(&sent.signature) {
+            Ok(signature) => signature,
+            Err(_) => return,
+        };
+        let rpc_client = self.rpc_client.clone();
+        tokio::spawn(async move {
+            match wait_for_bundle(&rpc_client, &bundle, bundle_id, signature).await {
+                Ok(result) => println!("Buy bundle {} of {} landed in slot {:?}", result.bundle_id, mints.join(", "), result.landed_slot),
+                Err(e) => println!("Buy bundle of {} did not land: {}", mints.join(", "), e),
+            }
+        });
+    }
+
+    // Snipe a specific token; returns the sent buy if it still has to be confirmed (CONFIRM_BUYS)
+    #[tracing::instrument(name = "snipe", skip_all, fields(mint = token_mint, slot = ?slot))]
+    pub async fn snipe_token(&self, token_mint: &str, token_price: f64, reserves: Option<TokenReserves>, slot: Option<u64>) -> Result<Option<UnconfirmedBuy>, Box<dyn Error>> {
+        // Record the timestamp when sniping starts (after the trader lock was acquired)
+        let start_time = Instant::now();
+        self.slot_stats.count(slot, |summary| summary.eligible += 1);
+
+        let target = SnipeTarget { mint: token_mint.to_string(), token_price, reserves };
+        let buy = match self.prepare_snipe(&target, slot, start_time).await? {
+            Some(buy) => buy,
+            None => return Ok(None),
+        };
+
+        let mut transaction_options = self.buy_transaction_options();
+        let mut blockhash = match self.buy_blockhash(&mut transaction_options).await {
+            Ok(blockhash) => blockhash,
+            Err(e) => {
+                self.fail_buy(&buy, &e, start_time.elapsed()).await;
+                return Err(e.into());
+            }
+        };
 
         // Buy the token, using the cached blockhash
-        match pump_buy(
-            &self.rpc_url,
-            &self.private_key,
-            mint_pubkey,
-            token_amount,
-            buy_sol,
-            slot,
-            blockhash
-        ).await {
+        let mut times = SendTimes::start();
+        self.stats.attempts.fetch_add(1, Ordering::Relaxed);
+        let mut attempt = 0;
+        let buy_result = loop {
+            let result = pump_buy(
+                &self.config.rpc_url,
+                &self.config.private_key,
+                buy.mint_pubkey,
+                buy.token_amount,
+                buy.max_sol_cost,
+                buy.include_ata,
+                slot,
+                blockhash,
+                &transaction_options
+            ).await;
+            if attempt == 0 {
+                times.first_send_returned(result.as_ref().ok().map(|sent| sent.built_at));
+            }
+            let result = result.map(|sent| {
+                self.track_bundle(vec![token_mint.to_string()], &sent);
+                sent.signature
+            });
+
+            // Write-lock contention on a hot curve is transient, retry on the next slot with a
+            // fresh blockhash. Other failures (slippage, insufficient funds) are not retried. A
+            // transaction rejected on contention is never committed, so only the send error
+            // shows it; a sent buy is not retried since it may still land. A buy rejected on
+            // contention did not advance the nonce, so it is kept for the retry.
+            match result {
+                Err(e) if e.is_account_in_use() && attempt < self.config.account_in_use_retries => {
+                    attempt += 1;
+                    println!("Account in use for {}, retrying on the next slot ({}/{})",
+                             token_mint, attempt, self.config.account_in_use_retries);
+                    self.wait_for_next_slot().await;
+                    if transaction_options.nonce_account.is_none() {
+                        self.blockhash_cache.prefetch().await;
+                        blockhash = self.blockhash_cache.get_blockhash_within(self.buy_max_blockhash_age()).await.ok();
+                    }
+                }
+                result => break result,
+            }
+        };
+
+        times.retries = attempt;
+        self.log_pipeline_latency(token_mint, &times).await;
+
+        match buy_result {
             Ok(signature) => {
                 let elapsed = start_time.elapsed();
+                self.slot_stats.count(slot, |summary| summary.sniped += 1);
                 println!("Snipe successful! Transaction signature: {}", signature);
                 println!("Total snipe time: {:.3}ms", elapsed.as_millis());
 
-                // After successful buy, store token address and purchased amount in Redis, set for auto sell after delay
-                self.redis_client.store_mint_with_amount(token_mint, token_amount, self.sell_delay_ms).await?;
+                if let Some(tracker) = &self.landing_tracker {
+                    tracker.track(vec![token_mint.to_string()], &signature, transaction_options.buy_compute_unit_price);
+                }
 
-                Ok(())
+                self.finish_buy(&buy, &signature, slot, elapsed).await
             },
             Err(e) => {
                 let elapsed = start_time.elapsed();
                 println!("Snipe failed: {:?}", e);
                 println!("Failed time: {:.3}ms", elapsed.as_millis());
-                Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, format!("Snipe failed: {:?}", e))))
+                self.fail_buy(&buy, &e, elapsed).await;
+                if let Some(frame_capture) = &self.frame_capture {
+                    frame_capture.dump(&format!("failed snipe of {}", token_mint));
+                }
+                Err(Box::new(std::io::Error::other(format!("Snipe failed: {:?}", e))))
+            }
+        }
+    }
+
+    // Snipe several tokens with as few transactions as possible, up to BATCH_BUYS_MAX buys each.
+    // All buys of a transaction land or fail together. Every token passes the guards of a single
+    // snipe, and a transaction rejected on write-lock contention is retried like a single buy.
+    // Returns the sent buys that still have to be confirmed (CONFIRM_BUYS).
+    pub async fn snipe_batch(&self, targets: Vec<SnipeTarget>, slot: Option<u64>) -> Result<Vec<UnconfirmedBuy>, Box<dyn Error>> {
+        if targets.len() == 1 {
+            let target = &targets[0];
+            return Ok(self.snipe_token(&target.mint, target.token_price, target.reserves, slot).await?.into_iter().collect());
+        }
+
+        let snipe_entered = Instant::now();
+        println!("Starting to snipe {} tokens in batches (slot: {:?})", targets.len(), slot);
+        self.slot_stats.count(slot, |summary| summary.eligible += targets.len() as u64);
+
+        let mut buys = Vec::with_capacity(targets.len());
+        let mut prepared = HashMap::with_capacity(targets.len());
+        for target in &targets {
+            match self.prepare_snipe(target, slot, snipe_entered).await {
+                Ok(Some(buy)) => {
+                    buys.push(BatchBuy {
+                        token_mint: buy.mint_pubkey,
+                        token_amount: buy.token_amount,
+                        max_sol_cost: buy.max_sol_cost,
+                        include_ata: buy.include_ata,
+                    });
+                    prepared.insert(buy.mint_pubkey, buy);
+                },
+                Ok(None) => {},
+                Err(e) => println!("Skipping {} in batch: {}", target.mint, e),
+            }
+        }
+        if buys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut transaction_options = self.buy_transaction_options();
+        let mut blockhash = match self.buy_blockhash(&mut transaction_options).await {
+            Ok(blockhash) => blockhash,
+            Err(e) => {
+                for buy in prepared.values() {
+                    self.fail_buy(buy, &e, snipe_entered.elapsed()).await;
+                }
+                return Err(e.into());
+            }
+        };
+
+        let mut times = SendTimes::start();
+        self.stats.attempts.fetch_add(buys.len() as u64, Ordering::Relaxed);
+        let mut attempt = 0;
+        let mut unconfirmed = Vec::new();
+        loop {
+            let results = match pump_buy_batch(
+                &self.config.rpc_url,
+                &self.config.private_key,
+                &buys,
+                self.config.batch_buys_max,
+                slot,
+                blockhash,
+                &transaction_options
+            ).await {
+                Ok(results) => results,
+                Err(e) => {
+                    for buy in &buys {
+                        self.fail_buy(&prepared[&buy.token_mint], &e, snipe_entered.elapsed()).await;
+                    }
+                    // Buys sent by earlier attempts still have to be confirmed
+                    if unconfirmed.is_empty() {
+                        return Err(e.into());
+                    }
+                    println!("Batch buy retry of {} tokens failed: {:?}", buys.len(), e);
+                    return Ok(unconfirmed);
+                }
+            };
+
+            if attempt == 0 {
+                times.first_send_returned(results.iter().find_map(|batch| batch.result.as_ref().ok().map(|sent| sent.built_at)));
+            }
+            times.retries = attempt;
+
+            let mut contended = Vec::new();
+            for batch in results {
+                let result = batch.result.map(|sent| {
+                    self.track_bundle(batch.buys.iter().map(|buy| prepared[&buy.token_mint].mint.clone()).collect(), &sent);
+                    sent.signature
+                });
+                match result {
+                    Err(e) if e.is_account_in_use() && attempt < self.config.account_in_use_retries => {
+                        println!("Account in use for batch of {} tokens, retrying on the next slot ({}/{})",
+                                 batch.buys.len(), attempt + 1, self.config.account_in_use_retries);
+                        contended.extend(batch.buys);
+                    }
+                    Ok(signature) => {
+                        println!("Batch snipe of {} tokens successful! Transaction signature: {}", batch.buys.len(), signature);
+                        self.slot_stats.count(slot, |summary| summary.sniped += batch.buys.len() as u64);
+
+                        if let Some(tracker) = &self.landing_tracker {
+                            tracker.track(batch.buys.iter().map(|buy| prepared[&buy.token_mint].mint.clone()).collect(), &signature,
+                                          transaction_options.buy_compute_unit_price);
+                        }
+
+                        for buy in &batch.buys {
+                            let buy = &prepared[&buy.token_mint];
+                            self.log_pipeline_latency(&buy.mint, &times).await;
+                            match self.finish_buy(buy, &signature, slot, snipe_entered.elapsed()).await {
+                                Ok(Some(buy)) => unconfirmed.push(buy),
+                                Ok(None) => {},
+                                Err(e) => println!("Failed to record batch buy of {}: {:?}", buy.mint, e),
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        println!("Batch buy of {} tokens failed: {:?}", batch.buys.len(), e);
+                        for buy in &batch.buys {
+                            self.fail_buy(&prepared[&buy.token_mint], &e, snipe_entered.elapsed()).await;
+                        }
+                        if let Some(frame_capture) = &self.frame_capture {
+                            frame_capture.dump(&format!("failed batch snipe of {} tokens", batch.buys.len()));
+                        }
+                    }
+                }
             }
+
+            if contended.is_empty() {
+                return Ok(unconfirmed);
+            }
+
+            // Only the buys of the contended transactions are sent again. The nonce was advanced if
+            // any transaction landed, so retries use a fresh blockhash.
+            attempt += 1;
+            self.wait_for_next_slot().await;
+            transaction_options.nonce_account = None;
+            self.blockhash_cache.prefetch().await;
+            blockhash = self.cached_buy_blockhash().await;
+            buys = contended;
         }
     }
 
-    // Determine if sniping should occur
-    pub fn should_snipe(&self, sol_amount: u64) -> bool {
-        sol_amount >= self.min_sol_price && sol_amount <= self.max_sol_price
+    // Determine if sniping should occur; a rate limit slot is only consumed by buys in the price range
+    pub fn evaluate_snipe(&self, sol_amount: u64) -> SnipeDecision {
+        if self.control.is_halted() {
+            return SnipeDecision::Skip(SkipReason::Halted);
+        }
+
+        if !self.trade_limits.in_price_range(sol_amount) {
+            return SnipeDecision::Skip(SkipReason::OutOfPriceRange);
+        }
+
+        // Checked after the price range so only buys that would be sniped are reported as paused
+        if self.congestion.is_congested() {
+            return SnipeDecision::Skip(SkipReason::Congested);
+        }
+
+        if let Some(limiter) = &self.snipe_limiter {
+            if limiter.check().is_err() {
+                return SnipeDecision::Skip(SkipReason::RateLimited);
+            }
+        }
+
+        SnipeDecision::Snipe
     }
 }
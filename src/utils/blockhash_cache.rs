@@ -1,14 +1,19 @@
+use futures::future::join_all;
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{commitment_config::{CommitmentConfig, CommitmentLevel}, hash::Hash};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
 /// Blockhash cache to reduce RPC calls
 pub struct BlockhashCache {
-    rpc_client: RpcClient,
+    rpc_clients: Vec<RpcClient>,
     cached_blockhash: Arc<Mutex<Option<(Hash, Instant)>>>,
     max_age: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
 }
 
 impl BlockhashCache {
@@ -19,37 +24,135 @@ impl BlockhashCache {
     /// * `rpc_url` - RPC node URL
     /// * `max_age_ms` - Maximum cache validity period (milliseconds)
     pub fn new(rpc_url: &str, max_age_ms: u64) -> Self {
+        Self::new_with_consensus(&[rpc_url], max_age_ms)
+    }
+
+    /// Creates a blockhash cache that queries several RPC nodes on refresh and keeps the
+    /// blockhash returned by the majority of them
+    ///
+    /// # Arguments
+    ///
+    /// * `rpc_urls` - RPC node URLs, queried concurrently
+    /// * `max_age_ms` - Maximum cache validity period (milliseconds)
+    pub fn new_with_consensus(rpc_urls: &[&str], max_age_ms: u64) -> Self {
         Self {
-            rpc_client: RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed()),
+            rpc_clients: rpc_urls
+                .iter()
+                .map(|url| RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed()))
+                .collect(),
             cached_blockhash: Arc::new(Mutex::new(None)),
             max_age: Duration::from_millis(max_age_ms),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
         }
     }
 
     /// Gets the latest blockhash, fetching from cache if valid
     pub async fn get_latest_blockhash(&self) -> Result<Hash, Box<dyn std::error::Error + Send + Sync>> {
+        self.get_blockhash_within(self.max_age).await
+    }
+
+    /// Gets the latest blockhash, using the cached one only if it is younger than `max_age`
+    /// instead of the cache's own validity period
+    ///
+    /// # Arguments
+    ///
+    /// * `max_age` - Maximum age of the cached blockhash for this caller
+    pub async fn get_blockhash_within(&self, max_age: Duration) -> Result<Hash, Box<dyn std::error::Error + Send + Sync>> {
         let mut cache = self.cached_blockhash.lock().await;
 
         // Check if cache is valid
         if let Some((hash, timestamp)) = &*cache {
-            if timestamp.elapsed() < self.max_age {
+            if timestamp.elapsed() < max_age {
+                self.hits.fetch_add(1, Ordering::Relaxed);
                 println!("Using cached blockhash");
                 return Ok(*hash);
             }
         }
 
         // Cache is missing or expired, fetch from RPC
+        self.misses.fetch_add(1, Ordering::Relaxed);
         println!("Fetching new blockhash");
-        let blockhash = self.rpc_client
-            .get_latest_blockhash_with_commitment(CommitmentConfig {
-                commitment: CommitmentLevel::Confirmed,
-            })
-            .await?
-            .0;
+        let blockhash = self.fetch_blockhash().await?;
 
         // Update cache
         *cache = Some((blockhash, Instant::now()));
 
         Ok(blockhash)
     }
+
+    /// Time since the cached blockhash was fetched, None if none was fetched yet. A recent one
+    /// shows the RPC nodes are responding, the cache is refreshed periodically.
+    pub async fn age(&self) -> Option<Duration> {
+        self.cached_blockhash.lock().await.as_ref().map(|(_, fetched_at)| fetched_at.elapsed())
+    }
+
+    /// Share of blockhash requests served from the cache since startup, None before the first one
+    pub fn hit_rate(&self) -> Option<f64> {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let total = hits + self.misses.load(Ordering::Relaxed);
+        (total > 0).then(|| hits as f64 / total as f64)
+    }
+
+    /// Fetches a blockhash from RPC and stores it, regardless of the cached one's age
+    pub async fn prefetch(&self) {
+        match self.fetch_blockhash().await {
+            Ok(blockhash) => {
+                *self.cached_blockhash.lock().await = Some((blockhash, Instant::now()));
+                println!("Prefetched blockhash: {}", blockhash);
+            }
+            Err(e) => println!("Failed to prefetch blockhash: {:?}", e),
+        }
+    }
+
+    /// Fetches the latest blockhash from every node. Picks the one returned by the majority,
+    /// or the most recent one (highest last valid block height) when there is no majority.
+    async fn fetch_blockhash(&self) -> Result<Hash, Box<dyn std::error::Error + Send + Sync>> {
+        let commitment = CommitmentConfig {
+            commitment: CommitmentLevel::Confirmed,
+        };
+        let results = join_all(
+            self.rpc_clients
+                .iter()
+                .map(|client| client.get_latest_blockhash_with_commitment(commitment)),
+        )
+        .await;
+
+        let mut responses = Vec::with_capacity(results.len());
+        let mut last_error = None;
+        for result in results {
+            match result {
+                Ok(response) => responses.push(response),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        if responses.is_empty() {
+            return Err(match last_error {
+                Some(e) => e.into(),
+                None => "No RPC nodes configured for blockhash".into(),
+            });
+        }
+
+        let mut votes: HashMap<Hash, usize> = HashMap::new();
+        for (hash, _) in &responses {
+            *votes.entry(*hash).or_default() += 1;
+        }
+
+        if let Some((hash, count)) = votes.iter().max_by_key(|(_, count)| **count) {
+            if *count * 2 > responses.len() {
+                return Ok(*hash);
+            }
+        }
+
+        // No majority, the nodes disagree due to propagation lag
+        let (blockhash, _) = responses
+            .iter()
+            .max_by_key(|(_, last_valid_block_height)| *last_valid_block_height)
+            .copied()
+            .unwrap();
+        println!("No blockhash majority across {} nodes, using the most recent: {}", responses.len(), blockhash);
+
+        Ok(blockhash)
+    }
 }
@@ -0,0 +1,255 @@
+use borsh::BorshDeserialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+use crate::config::Config;
+use crate::processor::{ReserveUpdates, TokenReserves};
+use crate::transaction::derive_bonding_curve;
+use crate::utils::redis::RedisClient;
+
+/// On-chain state of a Pump bonding curve account
+///
+/// Layout follows the 8-byte Anchor account discriminator. Newer program versions may
+/// append fields after `complete`, which are ignored.
+#[derive(BorshDeserialize, Debug, Clone, Copy)]
+pub struct BondingCurveState {
+    pub virtual_token_reserves: u64,
+    pub virtual_sol_reserves: u64,
+    pub real_token_reserves: u64,
+    pub real_sol_reserves: u64,
+    pub token_total_supply: u64,
+    pub complete: bool,
+}
+
+impl BondingCurveState {
+    /// Decodes the bonding curve state from raw account data
+    pub fn from_account_data(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+        Self::deserialize(&mut &data[8..]).ok()
+    }
+}
+
+// Pump protocol fee charged on top of the SOL cost of a buy (basis points)
+const PUMP_FEE_BPS: u128 = 100;
+
+/// How the number of tokens to request for a buy is computed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenAmountStrategy {
+    /// `buy_sol / price`, reduced by `slippage_bps` to leave room for price movement
+    Estimated { slippage_bps: u64 },
+    /// Constant-product quote against the tracked virtual reserves
+    ExactQuote,
+}
+
+/// Quotes the tokens received for `max_sol_cost` lamports on a constant-product curve
+///
+/// `token_out = virtual_token - k / (virtual_sol + sol_in)` with `k = virtual_sol * virtual_token`,
+/// where `sol_in` is `max_sol_cost` net of the protocol fee. Rounds in favour of the curve so
+/// the buy does not exceed `max_sol_cost`.
+///
+/// # Arguments
+///
+/// * `virtual_sol_reserves` - Current virtual SOL reserves (lamports)
+/// * `virtual_token_reserves` - Current virtual token reserves (with precision)
+/// * `max_sol_cost` - Lamports available for the buy, including the fee
+pub fn quote_buy(virtual_sol_reserves: u64, virtual_token_reserves: u64, max_sol_cost: u64) -> u64 {
+    let virtual_sol = virtual_sol_reserves as u128;
+    let virtual_token = virtual_token_reserves as u128;
+    let sol_in = max_sol_cost as u128 * 10_000 / (10_000 + PUMP_FEE_BPS);
+
+    let new_virtual_sol = virtual_sol + sol_in;
+    if new_virtual_sol == 0 {
+        return 0;
+    }
+
+    let k = virtual_sol * virtual_token;
+    // Round the remaining reserves up, i.e. the tokens out down
+    let new_virtual_token = k.div_ceil(new_virtual_sol);
+
+    virtual_token.saturating_sub(new_virtual_token) as u64
+}
+
+/// Quotes the lamports received for selling `token_amount` on a constant-product curve, net of
+/// the protocol fee
+///
+/// # Arguments
+///
+/// * `virtual_sol_reserves` - Current virtual SOL reserves (lamports)
+/// * `virtual_token_reserves` - Current virtual token reserves (with precision)
+/// * `token_amount` - Tokens sold (with precision)
+pub fn quote_sell(virtual_sol_reserves: u64, virtual_token_reserves: u64, token_amount: u64) -> u64 {
+    let virtual_sol = virtual_sol_reserves as u128;
+    let virtual_token = virtual_token_reserves as u128;
+
+    let new_virtual_token = virtual_token + token_amount as u128;
+    if new_virtual_token == 0 {
+        return 0;
+    }
+
+    let k = virtual_sol * virtual_token;
+    // Round the remaining reserves up, i.e. the SOL out down
+    let new_virtual_sol = k.div_ceil(new_virtual_token);
+    let sol_out = virtual_sol.saturating_sub(new_virtual_sol);
+
+    (sol_out * (10_000 - PUMP_FEE_BPS) / 10_000) as u64
+}
+
+/// Reads the bonding curve of a token, retrying while the account does not exist yet
+///
+/// A create seen in shreds can be acted on before the curve account is queryable, so a missing
+/// account is retried `retries` times after `retry_delay_ms`. Returns None if it is still missing
+/// or the read fails.
+///
+/// # Arguments
+///
+/// * `rpc_client` - RPC client to read the account with
+/// * `token_mint` - Token Mint address
+/// * `retries` - Additional reads after the first one
+/// * `retry_delay_ms` - Delay between reads (milliseconds)
+pub async fn read_bonding_curve(
+    rpc_client: &RpcClient,
+    token_mint: &Pubkey,
+    retries: u32,
+    retry_delay_ms: u64,
+) -> Option<BondingCurveState> {
+    let bonding_curve = derive_bonding_curve(token_mint);
+
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            sleep(Duration::from_millis(retry_delay_ms)).await;
+        }
+
+        match rpc_client.get_account_with_commitment(&bonding_curve, CommitmentConfig::processed()).await {
+            Ok(response) => match response.value {
+                Some(account) => return BondingCurveState::from_account_data(&account.data),
+                None => println!("Bonding curve of {} not found yet (attempt {}/{})", token_mint, attempt + 1, retries + 1),
+            },
+            Err(e) => {
+                println!("Failed to read bonding curve of {}: {:?}", token_mint, e);
+                return None;
+            }
+        }
+    }
+
+    None
+}
+
+/// Periodically overwrites the tracked reserves of open positions with the on-chain values
+///
+/// Local reserve tracking only sees buys from the creator feed and drifts over time. Each
+/// cycle reads at most `max_reads` bonding curves in a single `getMultipleAccounts` call,
+/// rotating through the open positions so all of them are refreshed when capped.
+///
+/// # Arguments
+///
+/// * `config` - Runtime configuration (RPC URL, refresh interval and read cap)
+/// * `redis_client` - Redis client holding the open positions
+/// * `reserve_updates` - Processor handle the refreshed reserves are pushed to
+pub fn spawn_repricing_task(
+    config: Arc<Config>,
+    redis_client: Arc<RedisClient>,
+    reserve_updates: ReserveUpdates,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let rpc_client = RpcClient::new_with_commitment(config.rpc_url.clone(), CommitmentConfig::confirmed());
+        let interval_ms = config.reprice_interval_ms;
+        let max_reads = config.reprice_max_reads;
+        let mut cursor = 0;
+
+        println!("Starting reserve re-pricing every {}ms (max {} reads per cycle)", interval_ms, max_reads);
+
+        loop {
+            sleep(Duration::from_millis(interval_ms)).await;
+
+            let open_mints = match redis_client.get_open_positions().await {
+                Ok(mints) => mints,
+                Err(e) => {
+                    println!("Failed to get open positions for re-pricing: {:?}", e);
+                    continue;
+                }
+            };
+            if open_mints.is_empty() || max_reads == 0 {
+                continue;
+            }
+
+            // Rotate through the positions so every one is refreshed even when capped
+            let count = open_mints.len().min(max_reads);
+            let mints: Vec<(String, Pubkey)> = (0..count)
+                .map(|i| open_mints[(cursor + i) % open_mints.len()].clone())
+                .filter_map(|mint| Pubkey::from_str(&mint).ok().map(|pubkey| (mint, pubkey)))
+                .collect();
+            cursor = (cursor + count) % open_mints.len();
+
+            let bonding_curves: Vec<Pubkey> = mints.iter().map(|(_, mint)| derive_bonding_curve(mint)).collect();
+
+            match rpc_client.get_multiple_accounts(&bonding_curves).await {
+                Ok(accounts) => {
+                    for ((mint, _), account) in mints.into_iter().zip(accounts) {
+                        if let Some(state) = account.and_then(|account| BondingCurveState::from_account_data(&account.data)) {
+                            reserve_updates.push(mint, TokenReserves {
+                                virtual_sol_reserves: state.virtual_sol_reserves,
+                                virtual_token_reserves: state.virtual_token_reserves,
+                            });
+                        }
+                    }
+                }
+                Err(e) => println!("Failed to read bonding curves for re-pricing: {:?}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reserves of a fresh curve: 30 SOL and 1.073 billion tokens (6 decimals)
+    const VIRTUAL_SOL: u64 = 30_000_000_000;
+    const VIRTUAL_TOKEN: u64 = 1_073_000_000_000_000;
+
+    #[test]
+    fn quotes_match_the_constant_product_curve() {
+        // 1.01 SOL is 1 SOL into the curve after the 1% fee: 1.073e15 / 31 = 34_612_903_225_806.45 tokens
+        assert_eq!(quote_buy(VIRTUAL_SOL, VIRTUAL_TOKEN, 1_010_000_000), 34_612_903_225_806);
+
+        // Selling them back into the fresh curve: 937_499_999.05 lamports out, 99% of it after the fee
+        assert_eq!(quote_sell(VIRTUAL_SOL, VIRTUAL_TOKEN, 34_612_903_225_806), 928_124_999);
+    }
+
+    #[test]
+    fn quotes_round_in_favour_of_the_curve() {
+        // The reserves after a quoted buy never fall below the constant product
+        for max_sol_cost in [1, 999, 1_010_000_000, 7_777_777_777] {
+            let sol_in = max_sol_cost as u128 * 10_000 / (10_000 + PUMP_FEE_BPS);
+            let token_out = quote_buy(VIRTUAL_SOL, VIRTUAL_TOKEN, max_sol_cost) as u128;
+            assert!((VIRTUAL_SOL as u128 + sol_in) * (VIRTUAL_TOKEN as u128 - token_out) >= VIRTUAL_SOL as u128 * VIRTUAL_TOKEN as u128);
+        }
+
+        // Buying and selling back at once returns less than the 1 SOL that went into the curve
+        let token_out = quote_buy(VIRTUAL_SOL, VIRTUAL_TOKEN, 1_010_000_000);
+        let sol_out = quote_sell(VIRTUAL_SOL + 1_000_000_000, VIRTUAL_TOKEN - token_out, token_out);
+        assert_eq!(sol_out, 989_999_999);
+        assert!(sol_out < 1_000_000_000 * 99 / 100);
+    }
+
+    #[test]
+    fn quotes_handle_zero_and_overflow_sized_inputs() {
+        assert_eq!(quote_buy(VIRTUAL_SOL, VIRTUAL_TOKEN, 0), 0);
+        assert_eq!(quote_sell(VIRTUAL_SOL, VIRTUAL_TOKEN, 0), 0);
+        assert_eq!(quote_buy(0, 0, 0), 0);
+        assert_eq!(quote_sell(0, 0, 0), 0);
+        assert_eq!(quote_buy(VIRTUAL_SOL, 0, 1_000_000_000), 0);
+        assert_eq!(quote_sell(0, VIRTUAL_TOKEN, 1_000_000), 0);
+
+        // Products of u64 reserves fit in u128, the quote stays below the reserves it is paid from
+        let token_out = quote_buy(u64::MAX, u64::MAX, u64::MAX);
+        assert!(token_out > 0 && token_out < u64::MAX);
+        let sol_out = quote_sell(u64::MAX, u64::MAX, u64::MAX);
+        assert!(sol_out > 0 && sol_out < u64::MAX / 2);
+    }
+}
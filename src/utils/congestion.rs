@@ -0,0 +1,71 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+use crate::config::Config;
+use crate::transaction::PUMP_PROGRAM_ID;
+
+// Whether snipes are suspended because of network congestion, shared between the monitor task
+// and evaluate_snipe
+#[derive(Clone, Default)]
+pub struct CongestionState {
+    congested: Arc<AtomicBool>,
+    fee: Arc<AtomicU64>, // Last observed fee percentile (micro-lamports per CU)
+}
+
+impl CongestionState {
+    pub fn is_congested(&self) -> bool {
+        self.congested.load(Ordering::Relaxed)
+    }
+
+    pub fn last_fee(&self) -> u64 {
+        self.fee.load(Ordering::Relaxed)
+    }
+}
+
+// Fee at the given percentile (0-100) of the recent per-slot prioritization fees
+fn fee_percentile(mut fees: Vec<u64>, percentile: u64) -> Option<u64> {
+    if fees.is_empty() {
+        return None;
+    }
+    fees.sort_unstable();
+    let index = ((fees.len() - 1) as u64 * percentile.min(100) / 100) as usize;
+    Some(fees[index])
+}
+
+// Poll the recent prioritization fees paid for the Pump program. Snipes are suspended once the
+// fee percentile exceeds CONGESTION_FEE_THRESHOLD and resumed when it falls back below
+// CONGESTION_RESUME_PERCENT of the threshold, so a fee hovering around the threshold does not
+// flip the state on every check. A failed check keeps the current state.
+pub fn spawn_congestion_monitor(config: Arc<Config>, state: CongestionState) -> JoinHandle<()> {
+    let rpc_client = RpcClient::new(config.rpc_url.clone());
+    let threshold = config.congestion_fee_threshold;
+    let resume_below = threshold * config.congestion_resume_percent.min(100) / 100;
+
+    tokio::spawn(async move {
+        loop {
+            match rpc_client.get_recent_prioritization_fees(&[PUMP_PROGRAM_ID]).await {
+                Ok(fees) => {
+                    let fees = fees.into_iter().map(|fee| fee.prioritization_fee).collect();
+                    if let Some(fee) = fee_percentile(fees, config.congestion_fee_percentile) {
+                        state.fee.store(fee, Ordering::Relaxed);
+                        let congested = state.is_congested();
+                        if !congested && fee > threshold {
+                            state.congested.store(true, Ordering::Relaxed);
+                            println!("ALERT: Network congested (p{} priority fee {} > {} micro-lamports/CU), pausing snipes",
+                                     config.congestion_fee_percentile, fee, threshold);
+                        } else if congested && fee < resume_below {
+                            state.congested.store(false, Ordering::Relaxed);
+                            println!("Network congestion eased (p{} priority fee {} micro-lamports/CU), resuming snipes",
+                                     config.congestion_fee_percentile, fee);
+                        }
+                    }
+                }
+                Err(e) => println!("Congestion check failed: {}", e),
+            }
+
+            sleep(Duration::from_millis(config.congestion_check_interval_ms.max(1_000))).await;
+        }
+    })
+}
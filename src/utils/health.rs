@@ -0,0 +1,117 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use crate::processor::FeedSlot;
+use crate::utils::auto_trader::TraderControl;
+use crate::utils::blockhash_cache::BlockhashCache;
+use crate::utils::redis::RedisClient;
+
+// Subsystems composed into the liveness probe
+#[derive(Clone)]
+pub struct HealthSources {
+    pub redis_client: Arc<RedisClient>,
+    pub feed_slot: FeedSlot,
+    pub blockhash_cache: Arc<BlockhashCache>,
+    pub control: Arc<TraderControl>, // Paused state, toggled by POST /pause and /resume
+    pub feed_stall_ms: u64,        // The feed is down when no batch arrived for this long
+    pub blockhash_max_age_ms: u64, // The RPC is down when no blockhash was fetched for this long
+}
+
+fn age_json(age: Option<Duration>) -> String {
+    age.map(|age| age.as_millis().to_string()).unwrap_or_else(|| "null".to_string())
+}
+
+// Minimal HTTP endpoint for load balancers and uptime monitors: GET /health returns 200 when the
+// feed is delivering entries, Redis is reachable and the RPC answered a recent blockhash fetch,
+// 503 otherwise, with the status of each subsystem as JSON. POST /pause stops new snipes (sells
+// continue), POST /resume restarts them and GET /status reports whether snipes are paused.
+pub async fn spawn_health_server(bind_addr: &str, sources: HealthSources) -> std::io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(bind_addr).await?;
+    println!("Health endpoint listening on http://{}/health", listener.local_addr()?);
+
+    Ok(tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let sources = sources.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, &sources).await {
+                            println!("Health request failed: {:?}", e);
+                        }
+                    });
+                }
+                Err(e) => println!("Failed to accept health connection: {:?}", e),
+            }
+        }
+    }))
+}
+
+// Liveness of every subsystem, and whether all of them are up
+async fn health_report(sources: &HealthSources) -> (bool, String) {
+    let redis = sources.redis_client.health_status().await;
+
+    let feed_age = sources.feed_slot.since_last_batch();
+    let feed_ok = feed_age.is_some_and(|age| age <= Duration::from_millis(sources.feed_stall_ms));
+
+    let blockhash_age = sources.blockhash_cache.age().await;
+    let rpc_ok = blockhash_age.is_some_and(|age| age <= Duration::from_millis(sources.blockhash_max_age_ms));
+
+    // A paused trader is still healthy
+    let healthy = feed_ok && redis.connected && rpc_ok;
+    let body = format!(
+        "{{\"healthy\":{},\"paused\":{},\"feed\":{{\"ok\":{},\"slot\":{},\"last_batch_ms\":{}}},\"redis\":{{\"ok\":{},\"status\":{}}},\"rpc\":{{\"ok\":{},\"blockhash_age_ms\":{}}}}}",
+        healthy,
+        sources.control.is_halted(),
+        feed_ok,
+        sources.feed_slot.get().map(|slot| slot.to_string()).unwrap_or_else(|| "null".to_string()),
+        age_json(feed_age),
+        redis.connected,
+        redis.to_json(),
+        rpc_ok,
+        age_json(blockhash_age),
+    );
+
+    (healthy, body)
+}
+
+fn status_json(control: &TraderControl) -> String {
+    let paused = control.is_halted();
+    format!("{{\"paused\":{},\"state\":\"{}\"}}", paused, if paused { "paused" } else { "running" })
+}
+
+async fn handle_connection(mut stream: TcpStream, sources: &HealthSources) -> std::io::Result<()> {
+    // Only the request line matters
+    let mut buffer = [0u8; 1024];
+    let read = stream.read(&mut buffer).await?;
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+
+    let (status, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/health")) => {
+            let (healthy, body) = health_report(sources).await;
+            let status = if healthy { "200 OK" } else { "503 Service Unavailable" };
+            (status, body)
+        }
+        (Some("GET"), Some("/status")) => ("200 OK", status_json(&sources.control)),
+        (Some("POST"), Some("/pause")) => {
+            sources.control.halt();
+            ("200 OK", status_json(&sources.control))
+        }
+        (Some("POST"), Some("/resume")) => {
+            sources.control.resume();
+            ("200 OK", status_json(&sources.control))
+        }
+        _ => ("404 Not Found", "{\"error\":\"not found\"}".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
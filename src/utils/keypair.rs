@@ -0,0 +1,44 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use solana_sdk::signature::Keypair;
+
+// Parse a wallet private key given as base58, base64 or a JSON byte array (as written by
+// `solana-keygen`), ignoring surrounding whitespace
+pub fn parse_keypair(input: &str) -> Result<Keypair, String> {
+    let input = input.trim();
+
+    if input.is_empty() {
+        return Err("Private key is empty".to_string());
+    }
+
+    // JSON array of 64 bytes, e.g. [12, 34, ...]
+    if input.starts_with('[') {
+        let bytes = parse_json_bytes(input)
+            .ok_or_else(|| "Couldn't parse private key as a JSON byte array".to_string())?;
+        return keypair_from_bytes(&bytes, "json-array");
+    }
+
+    // Some strings decode under both encodings, so only accept a 64-byte result
+    if let Some(bytes) = solana_sdk::bs58::decode(input).into_vec().ok().filter(|bytes| bytes.len() == 64) {
+        return keypair_from_bytes(&bytes, "base58");
+    }
+
+    if let Some(bytes) = BASE64.decode(input).ok().filter(|bytes| bytes.len() == 64) {
+        return keypair_from_bytes(&bytes, "base64");
+    }
+
+    Err("Couldn't parse private key as base58/base64/json-array (expected 64 key bytes)".to_string())
+}
+
+fn keypair_from_bytes(bytes: &[u8], format: &str) -> Result<Keypair, String> {
+    Keypair::from_bytes(bytes).map_err(|e| {
+        format!("Private key decoded as {} but is not a valid keypair ({} bytes): {}", format, bytes.len(), e)
+    })
+}
+
+fn parse_json_bytes(input: &str) -> Option<Vec<u8>> {
+    let inner = input.strip_prefix('[')?.strip_suffix(']')?;
+    inner
+        .split(',')
+        .map(|byte| byte.trim().parse::<u8>().ok())
+        .collect()
+}
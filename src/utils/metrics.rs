@@ -0,0 +1,127 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use crate::processor::FeedSlot;
+use crate::utils::auto_trader::{LandingStats, PositionInventory};
+use crate::utils::priority_fee::FeeHistory;
+use crate::utils::redis::RedisClient;
+use crate::utils::slot_gaps::SlotGapStats;
+
+// Upper bound of a single push, so an unreachable gateway never stalls the next interval
+const PUSH_TIMEOUT_MS: u64 = 5_000;
+
+// Sources of the pushed gauges
+pub struct MetricsSources {
+    pub redis_client: Arc<RedisClient>,
+    pub feed_slot: FeedSlot,
+    pub inventory: Arc<RwLock<PositionInventory>>,
+    pub slot_gaps: Option<Arc<SlotGapStats>>, // None when slot history is disabled
+    pub landing: Option<Arc<LandingStats>>,   // None when landing tracking is disabled
+    pub fees: Option<Arc<FeeHistory>>,        // None when buy fees are not recorded
+}
+
+impl MetricsSources {
+    // Current values in the Prometheus text exposition format
+    async fn render(&self) -> String {
+        let health = self.redis_client.health_status().await;
+        let open_positions = self.inventory.read().await.count_positions();
+
+        let mut gauges = vec![
+            ("sniper_redis_connected", "Whether Redis answered the last ping", if health.connected { 1 } else { 0 }),
+            ("sniper_redis_ping_latency_ms", "Latency of the last Redis ping", health.ping_latency_ms),
+            ("sniper_sell_queue_depth", "Positions waiting in the sell queue", health.queue_depth as u64),
+            ("sniper_open_positions", "Positions held by this instance", open_positions as u64),
+        ];
+        if let Some(ms) = health.oldest_pending_sell_ms {
+            gauges.push(("sniper_oldest_pending_sell_ms", "How long the earliest scheduled sell has been due", ms));
+        }
+        if let Some(slot_gaps) = &self.slot_gaps {
+            gauges.push(("sniper_feed_gaps", "Subscriptions that started after a gap in processed slots", slot_gaps.gaps()));
+            gauges.push(("sniper_feed_missed_slots", "Slots missed across subscriptions", slot_gaps.missed_slots()));
+            gauges.push(("sniper_feed_last_gap_slots", "Slots missed before the latest subscription", slot_gaps.last_gap()));
+        }
+        if let Some(landing) = &self.landing {
+            gauges.push(("sniper_buys_landed", "Tracked buys that landed without error", landing.landed()));
+            gauges.push(("sniper_buys_dropped", "Tracked buys never seen on-chain", landing.dropped()));
+            gauges.push(("sniper_buys_errored", "Tracked buys that landed with an error", landing.errored()));
+        }
+        if let Some(fees) = &self.fees {
+            let (landed, dropped) = fees.counts();
+            gauges.push(("sniper_fee_samples_landed", "Recent tracked buys whose fee landed", landed as u64));
+            gauges.push(("sniper_fee_samples_dropped", "Recent tracked buys whose fee was dropped", dropped as u64));
+            if let Some(fee) = fees.recommended_fee() {
+                gauges.push(("sniper_recommended_fee", "Buy compute unit price derived from recently landed buys", fee));
+            }
+        }
+        if let Some(slot) = self.feed_slot.get() {
+            gauges.push(("sniper_feed_slot", "Latest slot received from Shredstream", slot));
+        }
+
+        gauges
+            .iter()
+            .map(|(name, help, value)| format!("# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n"))
+            .collect()
+    }
+}
+
+// Periodically push the metrics to a Prometheus Pushgateway, for instances a scraper cannot
+// reach (behind NAT, short-lived containers). Failed pushes are logged and retried next interval.
+pub fn spawn_pushgateway(gateway_url: &str, job: &str, interval_ms: u64, sources: MetricsSources) -> Result<JoinHandle<()>, String> {
+    let (host, base_path) = parse_http_url(gateway_url)?;
+    let path = format!("{}/metrics/job/{}", base_path.trim_end_matches('/'), job);
+    println!("Pushing metrics to http://{}{} every {}ms", host, path, interval_ms);
+
+    Ok(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(interval_ms.max(1)));
+        loop {
+            interval.tick().await;
+            let body = sources.render().await;
+            match tokio::time::timeout(Duration::from_millis(PUSH_TIMEOUT_MS), push(&host, &path, &body)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => println!("Failed to push metrics to {}: {}", host, e),
+                Err(_) => println!("Failed to push metrics to {}: timed out", host),
+            }
+        }
+    }))
+}
+
+// Split "http://host:port/path" into ("host:port", "/path"); only plain HTTP is supported
+fn parse_http_url(url: &str) -> Result<(String, String), String> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("Unsupported push gateway URL {}, expected http://host:port", url))?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, ""),
+    };
+    if host.is_empty() {
+        return Err(format!("Missing host in push gateway URL {}", url));
+    }
+    let host = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+    Ok((host, path.to_string()))
+}
+
+// PUT replaces all metrics of the job, so gauges that disappear are not left stale
+async fn push(host: &str, path: &str, body: &str) -> Result<(), String> {
+    let mut stream = TcpStream::connect(host).await.map_err(|e| e.to_string())?;
+    let request = format!(
+        "PUT {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.map_err(|e| e.to_string())?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.map_err(|e| e.to_string())?;
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("unexpected response: {}", status_line)),
+    }
+}
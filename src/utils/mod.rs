@@ -1,10 +1,98 @@
 use solana_entry::entry::Entry;
+use solana_sdk::message::VersionedMessage;
 use bincode::Error as BincodeError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub mod redis;
 pub mod auto_trader;
 pub mod blockhash_cache;
+pub mod bonding_curve;
+pub mod congestion;
+pub mod keypair;
+pub mod replay;
+pub mod retry;
+pub mod store;
+pub mod health;
+pub mod metrics;
+pub mod nonce_manager;
+pub mod priority_fee;
+pub mod slot_gaps;
+pub mod sol_price;
+pub mod telemetry;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
 
 pub fn deserialize_entries(data: &[u8]) -> Result<Vec<Entry>, BincodeError> {
     bincode::deserialize::<Vec<Entry>>(data)
-} 
\ No newline at end of file
+}
+
+// Entries salvaged from a frame, and whether entries after them could not be decoded
+pub struct PartialEntries {
+    pub entries: Vec<Entry>,
+    pub truncated: bool,
+}
+
+// Decode the entries of a frame one by one, keeping the valid prefix if the tail is truncated or
+// corrupt instead of discarding the whole batch. Only fails if the length prefix is unreadable.
+pub fn deserialize_entries_resilient(data: &[u8]) -> Result<PartialEntries, BincodeError> {
+    let mut reader = data;
+    // Same layout as Vec<Entry>: u64 length followed by the entries
+    let len: u64 = bincode::deserialize_from(&mut reader)?;
+
+    let mut entries = Vec::new();
+    for _ in 0..len {
+        match bincode::deserialize_from::<_, Entry>(&mut reader) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => return Ok(PartialEntries { entries, truncated: true }),
+        }
+    }
+
+    Ok(PartialEntries { entries, truncated: false })
+}
+
+// Debug switch for dumping full account key lists, off by default
+static LOG_ACCOUNT_KEYS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_account_key_logging(enabled: bool) {
+    LOG_ACCOUNT_KEYS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn account_key_logging_enabled() -> bool {
+    LOG_ACCOUNT_KEYS.load(Ordering::Relaxed)
+}
+
+// Current Unix time in milliseconds, 0 if the clock is set before the epoch
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+// Number at the dot-separated `field` path of a JSON response, given as a number or a numeric string
+pub fn json_number_at(response: &serde_json::Value, field: &str) -> Result<f64, String> {
+    let value = field
+        .split('.')
+        .try_fold(response, |value, key| value.get(key))
+        .ok_or_else(|| format!("no {} in response", field))?;
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|number| number.parse().ok()))
+        .ok_or_else(|| format!("{} is not a number: {}", field, value))
+}
+
+// Dump the ordered account keys of a message with their signer/writable flags
+pub fn log_account_keys(label: &str, message: &VersionedMessage) {
+    let account_keys = message.static_account_keys();
+    println!("Account keys for {} ({} keys):", label, account_keys.len());
+    for (index, key) in account_keys.iter().enumerate() {
+        println!("  [{:>2}] {} signer={} writable={}",
+                 index,
+                 key,
+                 message.is_signer(index),
+                 message.is_maybe_writable(index));
+    }
+    if let Some(lookups) = message.address_table_lookups() {
+        if !lookups.is_empty() {
+            println!("  (+ {} address table lookups not listed)", lookups.len());
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    hash::Hash,
+    nonce::state::{State, Versions},
+    pubkey::Pubkey,
+};
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Duration, Instant};
+use crate::error::SniperError;
+
+// How long to wait for a handed-out nonce to be advanced on chain before reusing it
+const NONCE_ADVANCE_WAIT_MS: u64 = 2_000;
+const NONCE_POLL_MS: u64 = 50;
+
+// Durable nonce of one keypair: its nonce account and the last nonce handed out for it
+struct NonceSlot {
+    account: Pubkey,
+    last_nonce: Mutex<Option<Hash>>,
+}
+
+// Hands out durable nonces, one nonce account per keypair. Every buy advances the nonce in the
+// transaction itself (AdvanceNonceAccount), so a nonce is only valid for one landed transaction
+// and two concurrent buys of the same keypair never share one.
+pub struct NonceManager {
+    rpc_client: Arc<RpcClient>,
+    slots: HashMap<Pubkey, NonceSlot>, // Keyed by the keypair, which is also the nonce authority
+}
+
+impl NonceManager {
+    pub fn new(rpc_client: Arc<RpcClient>) -> Self {
+        Self { rpc_client, slots: HashMap::new() }
+    }
+
+    // Register the nonce account of a keypair; the keypair must be its nonce authority
+    pub fn add_account(&mut self, keypair_pubkey: Pubkey, nonce_account: Pubkey) {
+        self.slots.insert(keypair_pubkey, NonceSlot { account: nonce_account, last_nonce: Mutex::new(None) });
+    }
+
+    pub fn nonce_account(&self, keypair_pubkey: &Pubkey) -> Option<Pubkey> {
+        self.slots.get(keypair_pubkey).map(|slot| slot.account)
+    }
+
+    // Current nonce of the keypair and its nonce account address. While the on-chain nonce is still
+    // the one handed out last (that transaction has not advanced it yet), wait briefly for it to
+    // change rather than handing out the same nonce twice.
+    pub async fn get_nonce(&self, keypair_pubkey: &Pubkey) -> Result<(Hash, Pubkey), SniperError> {
+        let slot = self
+            .slots
+            .get(keypair_pubkey)
+            .ok_or_else(|| SniperError::Other(format!("No nonce account configured for {}", keypair_pubkey)))?;
+
+        // Held for the whole read, so concurrent callers are served one after the other
+        let mut last_nonce = slot.last_nonce.lock().await;
+        let deadline = Instant::now() + Duration::from_millis(NONCE_ADVANCE_WAIT_MS);
+        let nonce = loop {
+            let nonce = self.read_nonce(&slot.account).await?;
+            if Some(nonce) != *last_nonce {
+                break nonce;
+            }
+            if Instant::now() >= deadline {
+                println!("Nonce of {} not advanced within {}ms, the previous transaction likely did not land",
+                         slot.account, NONCE_ADVANCE_WAIT_MS);
+                break nonce;
+            }
+            sleep(Duration::from_millis(NONCE_POLL_MS)).await;
+        };
+
+        *last_nonce = Some(nonce);
+        Ok((nonce, slot.account))
+    }
+
+    async fn read_nonce(&self, nonce_account: &Pubkey) -> Result<Hash, SniperError> {
+        let account = self.rpc_client.get_account(nonce_account).await?;
+        let versions: Versions = bincode::deserialize(&account.data)
+            .map_err(|e| SniperError::Other(format!("Invalid nonce account {}: {}", nonce_account, e)))?;
+        match versions.state() {
+            State::Initialized(data) => Ok(data.blockhash()),
+            State::Uninitialized => Err(SniperError::Other(format!("Nonce account {} is not initialized", nonce_account))),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+use crate::config::Config;
+use crate::transaction::PUMP_PROGRAM_ID;
+use crate::utils::{json_number_at, unix_time_ms};
+
+// A fetched fee is used for this many refresh intervals, then the static fee applies again
+const FEE_VALID_INTERVALS: u64 = 3;
+
+// While too few buys landed above the highest dropped fee, the recommendation is this fraction
+// of that fee above it (1/4 = 25%)
+const DROPPED_FEE_STEP_DIVISOR: u64 = 4;
+
+// Shape of the fee API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeeApiProvider {
+    // Helius getPriorityFeeEstimate (JSON-RPC POST) for the Pump program at PRIORITY_FEE_API_LEVEL
+    Helius,
+    // Plain GET, the fee is read from the dot-separated PRIORITY_FEE_API_FIELD of the response
+    Custom,
+}
+
+// Latest buy priority fee from the fee API, shared between the refresh task and the buy paths
+#[derive(Clone, Default)]
+pub struct PriorityFeeOracle {
+    fee: Arc<AtomicU64>,        // Micro-lamports per compute unit
+    fetched_at: Arc<AtomicU64>, // Unix time of the last successful fetch, 0 if none
+    valid_ms: u64,              // How long a fetched fee is used (0 = never, no API configured)
+}
+
+impl PriorityFeeOracle {
+    pub fn new(config: &Config) -> Self {
+        let valid_ms = if config.priority_fee_api_url.is_some() {
+            config.priority_fee_refresh_ms.max(250) * FEE_VALID_INTERVALS
+        } else {
+            0
+        };
+        Self { valid_ms, ..Self::default() }
+    }
+
+    // Compute unit price for a buy: the fetched fee while it is recent, `fallback` otherwise
+    pub fn buy_compute_unit_price(&self, fallback: u64) -> u64 {
+        let fetched_at = self.fetched_at.load(Ordering::Relaxed);
+        if fetched_at == 0 || unix_time_ms().saturating_sub(fetched_at) > self.valid_ms {
+            return fallback;
+        }
+        self.fee.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, fee: u64) {
+        self.fee.store(fee, Ordering::Relaxed);
+        self.fetched_at.store(unix_time_ms(), Ordering::Relaxed);
+    }
+}
+
+async fn fetch_priority_fee(http_client: &reqwest::Client, config: &Config, url: &str) -> Result<u64, String> {
+    let response: serde_json::Value = match config.priority_fee_api_provider {
+        FeeApiProvider::Helius => {
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "getPriorityFeeEstimate",
+                "params": [{
+                    "accountKeys": [PUMP_PROGRAM_ID.to_string()],
+                    "options": { "priorityLevel": config.priority_fee_api_level },
+                }],
+            });
+            http_client.post(url).json(&request).send().await
+        }
+        FeeApiProvider::Custom => http_client.get(url).send().await,
+    }
+    .map_err(|e| e.to_string())?
+    .json()
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let field = match config.priority_fee_api_provider {
+        FeeApiProvider::Helius => "result.priorityFeeEstimate",
+        FeeApiProvider::Custom => config.priority_fee_api_field.as_str(),
+    };
+    let fee = json_number_at(&response, field)?;
+    if fee < 0.0 || !fee.is_finite() {
+        return Err(format!("invalid fee {}", fee));
+    }
+
+    let fee = fee.ceil() as u64;
+    Ok(match config.priority_fee_max {
+        0 => fee,
+        max => fee.min(max),
+    })
+}
+
+// Refresh the buy priority fee from the configured fee API in the background. On failure the
+// last fee is kept until it expires, after which buys use the static BUY_COMPUTE_UNIT_PRICE.
+pub fn spawn_priority_fee_refresh(config: Arc<Config>, oracle: PriorityFeeOracle) -> Option<JoinHandle<()>> {
+    let url = config.priority_fee_api_url.clone()?;
+    let http_client = reqwest::Client::new();
+
+    Some(tokio::spawn(async move {
+        let mut failing = false;
+        loop {
+            match fetch_priority_fee(&http_client, &config, &url).await {
+                Ok(fee) => {
+                    if failing {
+                        println!("Priority fee API recovered, buy priority fee {} micro-lamports/CU", fee);
+                        failing = false;
+                    }
+                    oracle.set(fee);
+                }
+                Err(e) => {
+                    // Logged once per outage, not on every refresh
+                    if !failing {
+                        println!("ALERT: Priority fee API failed ({}), falling back to {} micro-lamports/CU once the last fee expires",
+                                 e, config.buy_compute_unit_price);
+                        failing = true;
+                    }
+                }
+            }
+            sleep(Duration::from_millis(config.priority_fee_refresh_ms.max(250))).await;
+        }
+    }))
+}
+
+// Compute unit price of one tracked buy and whether it landed. Buys that landed with an error
+// count as landed: their fee was enough, the buy itself was not.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeSample {
+    pub fee: u64, // Micro-lamports per compute unit
+    pub landed: bool,
+}
+
+impl FeeSample {
+    // Stored in Redis as "landed:<fee>" or "dropped:<fee>"
+    pub fn encode(&self) -> String {
+        format!("{}:{}", if self.landed { "landed" } else { "dropped" }, self.fee)
+    }
+
+    pub fn decode(value: &str) -> Option<Self> {
+        let (outcome, fee) = value.split_once(':')?;
+        let landed = match outcome {
+            "landed" => true,
+            "dropped" => false,
+            _ => return None,
+        };
+        Some(Self { fee: fee.parse().ok()?, landed })
+    }
+}
+
+// Fees of the most recent tracked buys, from which the fee that has been landing is derived
+pub struct FeeHistory {
+    samples: Mutex<VecDeque<FeeSample>>, // Oldest first
+    capacity: usize,
+    percentile: u64,
+    min_landed: usize,
+}
+
+impl FeeHistory {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            samples: Mutex::new(VecDeque::with_capacity(config.fee_history_size)),
+            capacity: config.fee_history_size.max(1),
+            percentile: config.fee_history_percentile.min(100),
+            min_landed: config.fee_history_min_landed.max(1),
+        }
+    }
+
+    pub fn record(&self, sample: FeeSample) {
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        if samples.len() >= self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    // Landed and dropped buys among the recorded ones
+    pub fn counts(&self) -> (usize, usize) {
+        let samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let landed = samples.iter().filter(|sample| sample.landed).count();
+        (landed, samples.len() - landed)
+    }
+
+    // FEE_HISTORY_PERCENTILE of the fees of the recently landed buys above the highest recently
+    // dropped fee, None until FEE_HISTORY_MIN_LANDED buys have landed. While fewer than that
+    // landed above the drops, one step over the highest dropped fee, so drops raise the fee.
+    pub fn recommended_fee(&self) -> Option<u64> {
+        let (mut landed, highest_dropped) = {
+            let samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+            let landed: Vec<u64> = samples.iter().filter(|sample| sample.landed).map(|sample| sample.fee).collect();
+            let highest_dropped = samples.iter().filter(|sample| !sample.landed).map(|sample| sample.fee).max();
+            (landed, highest_dropped)
+        };
+        if landed.len() < self.min_landed {
+            return None;
+        }
+
+        // A fee at or below a dropped one did not reliably land
+        if let Some(dropped) = highest_dropped {
+            landed.retain(|fee| *fee > dropped);
+            if landed.len() < self.min_landed {
+                return Some(dropped.saturating_add((dropped / DROPPED_FEE_STEP_DIVISOR).max(1)));
+            }
+        }
+        landed.sort_unstable();
+        let index = ((landed.len() - 1) as u64 * self.percentile / 100) as usize;
+        Some(landed[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(percentile: u64, min_landed: usize) -> FeeHistory {
+        FeeHistory { samples: Mutex::new(VecDeque::new()), capacity: 100, percentile, min_landed }
+    }
+
+    fn record(history: &FeeHistory, fees: &[u64], landed: bool) {
+        for &fee in fees {
+            history.record(FeeSample { fee, landed });
+        }
+    }
+
+    #[test]
+    fn dropped_buys_raise_the_recommended_fee() {
+        let history = history(50, 3);
+        record(&history, &[10_000, 20_000, 30_000], true);
+        assert_eq!(history.recommended_fee(), Some(20_000));
+
+        // Buys at the recommended fee are dropped, only one landed above it: a step over the drops
+        record(&history, &[20_000, 20_000], false);
+        assert_eq!(history.recommended_fee(), Some(25_000));
+
+        // Enough buys landed above the drops, the percentile is taken over those
+        record(&history, &[25_000, 40_000], true);
+        assert_eq!(history.recommended_fee(), Some(30_000));
+    }
+
+    #[test]
+    fn no_fee_is_recommended_before_enough_buys_landed() {
+        let history = history(50, 3);
+        record(&history, &[10_000, 20_000], true);
+        record(&history, &[50_000], false);
+        assert_eq!(history.recommended_fee(), None);
+    }
+}
@@ -1,27 +1,215 @@
-use redis::{AsyncCommands, Client, RedisError, aio::Connection as AsyncConnection};
-use tokio::sync::Mutex;
+use redis::{AsyncCommands, Client, ErrorKind, IntoConnectionInfo, RedisError, Value, aio::Connection as AsyncConnection};
+use tokio::sync::{Mutex, MutexGuard};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use futures::StreamExt;
+use crate::error::SniperError;
+use chrono::{Local, TimeZone};
+use tracing::info;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// Pub/sub channel for runtime control commands
+pub const COMMAND_CHANNEL: &str = "sniper:commands";
+
+// Key names, always used through RedisClient::key so the instance prefix is applied
+const MINTS_TO_SELL: &str = "mints_to_sell";   // Sorted set of mints scored by sell time
+const MINT_AMOUNTS: &str = "mint_amounts";     // Hash of mint -> purchased token amount
+const MINT_BOUGHT_AT: &str = "mint_bought_at"; // Hash of mint -> buy time
+const PROCESSED_SLOTS: &str = "processed_slots"; // List of the last processed slots, newest first
+const SNIPE_SLOT_PREFIX: &str = "snipe:"; // Per-mint reservation of a snipe, holding the reserving instance's ID
+const TRADE_EVENTS: &str = "trade_events";     // Stream of buys and sells
+const CREATOR_ALLOWLIST: &str = "creator_allowlist"; // Set of creator pubkeys whose mints may be sniped
+const FEE_SAMPLES: &str = "fee_samples";       // List of landed/dropped buy fees, newest first
+
+// A trade appended to the trade history stream
+#[derive(Clone)]
+pub struct TradeEvent {
+    pub side: &'static str,  // "buy" or "sell"
+    pub mint: String,
+    pub token_amount: u64,
+    pub sol_amount: u64,     // lamports
+    pub signature: String,
+    pub slot: Option<u64>,
+}
+
+impl TradeEvent {
+    // Stream entry fields for XADD
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        vec![
+            ("side", self.side.to_string()),
+            ("mint", self.mint.clone()),
+            ("token_amount", self.token_amount.to_string()),
+            ("sol_amount", self.sol_amount.to_string()),
+            ("signature", self.signature.clone()),
+            ("slot", self.slot.map(|slot| slot.to_string()).unwrap_or_default()),
+            ("timestamp", timestamp.to_string()),
+        ]
+    }
+}
+
+// Accumulated latency of one Redis operation since the last report
+#[derive(Default)]
+struct OpLatency {
+    count: u64,
+    lock_wait_us: u64, // Time spent waiting for the shared connection
+    total_us: u64,     // Lock wait plus the round trip
+    max_us: u64,
+}
+
+// Per-operation Redis latency, collected only when enabled
+#[derive(Default)]
+pub struct RedisLatencyStats(std::sync::Mutex<HashMap<&'static str, OpLatency>>);
+
+impl RedisLatencyStats {
+    fn record(&self, op: &'static str, lock_wait: Duration, total: Duration) {
+        if let Ok(mut ops) = self.0.lock() {
+            let entry = ops.entry(op).or_default();
+            let total_us = total.as_micros() as u64;
+            entry.count += 1;
+            entry.lock_wait_us += lock_wait.as_micros() as u64;
+            entry.total_us += total_us;
+            entry.max_us = entry.max_us.max(total_us);
+        }
+    }
+
+    // Print the stats collected since the last report and reset them
+    fn report(&self) {
+        let ops = match self.0.lock() {
+            Ok(mut ops) => std::mem::take(&mut *ops),
+            Err(_) => return,
+        };
+
+        for (op, latency) in ops {
+            println!("Redis {}: {} ops, avg {}us (lock wait avg {}us), max {}us",
+                     op,
+                     latency.count,
+                     latency.total_us / latency.count,
+                     latency.lock_wait_us / latency.count,
+                     latency.max_us);
+        }
+    }
+}
+
+// Times one operation from before the connection lock until it is dropped
+struct OpTimer {
+    stats: Arc<RedisLatencyStats>,
+    op: &'static str,
+    started: Instant,
+    lock_wait: Duration,
+}
+
+impl Drop for OpTimer {
+    fn drop(&mut self) {
+        self.stats.record(self.op, self.lock_wait, self.started.elapsed());
+    }
+}
+
+// Connectivity and sell queue health, for monitoring
+#[derive(Clone, Debug, Default)]
+pub struct HealthStatus {
+    pub connected: bool,
+    pub ping_latency_ms: u64,
+    pub queue_depth: usize,                 // Positions waiting in the sell queue
+    pub oldest_pending_sell_ms: Option<u64>, // How long the earliest scheduled sell has been due (0 = not due yet)
+}
+
+impl HealthStatus {
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"connected\":{},\"ping_latency_ms\":{},\"queue_depth\":{},\"oldest_pending_sell_ms\":{}}}",
+            self.connected,
+            self.ping_latency_ms,
+            self.queue_depth,
+            self.oldest_pending_sell_ms.map(|ms| ms.to_string()).unwrap_or_else(|| "null".to_string()),
+        )
+    }
+}
 
 pub struct RedisClient {
     client: Client,
     connection: Arc<Mutex<AsyncConnection>>,
+    latency: Option<Arc<RedisLatencyStats>>, // None when latency metrics are disabled
+    key_prefix: String, // Prepended to every key and the command channel, isolates instances sharing a server
 }
 
 impl RedisClient {
     pub async fn new(redis_url: &str) -> Result<Self, RedisError> {
-        let client = Client::open(redis_url)?;
+        Self::connect(redis_url, None).await
+    }
+
+    // Connect, optionally selecting a database other than the one in the URL
+    pub async fn connect(redis_url: &str, db: Option<i64>) -> Result<Self, RedisError> {
+        let mut connection_info = redis_url.into_connection_info()?;
+        if let Some(db) = db {
+            connection_info.redis.db = db;
+        }
+        let client = Client::open(connection_info)?;
         let connection = Arc::new(Mutex::new(client.get_async_connection().await?));
 
         Ok(Self {
             client,
             connection,
+            latency: None,
+            key_prefix: String::new(),
         })
     }
 
+    // Namespace all keys and the command channel as "<prefix>:<name>"
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = if prefix.is_empty() { String::new() } else { format!("{}:", prefix) };
+        self
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{}", self.key_prefix, name)
+    }
+
+    // Collect per-operation latency, including the wait for the shared connection
+    pub fn with_latency_metrics(mut self) -> Self {
+        self.latency = Some(Arc::new(RedisLatencyStats::default()));
+        self
+    }
+
+    // Periodically print and reset the collected latency, if metrics are enabled
+    pub fn spawn_latency_report(&self, interval_ms: u64) -> Option<JoinHandle<()>> {
+        let stats = self.latency.clone()?;
+
+        Some(tokio::spawn(async move {
+            loop {
+                sleep(Duration::from_millis(interval_ms)).await;
+                stats.report();
+            }
+        }))
+    }
+
+    // Lock the shared connection; when metrics are enabled the returned timer records the
+    // lock wait now and the total operation time when it is dropped
+    async fn lock_connection(&self, op: &'static str) -> (MutexGuard<'_, AsyncConnection>, Option<OpTimer>) {
+        let started = Instant::now();
+        let conn = self.connection.lock().await;
+
+        let timer = self.latency.as_ref().map(|stats| OpTimer {
+            stats: stats.clone(),
+            op,
+            started,
+            lock_wait: started.elapsed(),
+        });
+
+        (conn, timer)
+    }
+
     // Store Mint address in Redis as an automatic trading queue, with a specified delay time
     pub async fn store_mint_data(&self, mint: &str, delay_ms: u64) -> Result<(), RedisError> {
-        let mut conn = self.connection.lock().await;
+        let (mut conn, _timer) = self.lock_connection("store_mint_data").await;
 
         // Get the current timestamp as the score and add the specified delay time
         let now = SystemTime::now()
@@ -32,16 +220,19 @@ impl RedisClient {
         let sell_time = now + delay_ms; // Sell after the specified time
 
         // Add the mint address to the sorted set, with the score being the sell time
-        conn.zadd("mints_to_sell", mint, sell_time).await?;
+        let _: () = conn.zadd(self.key(MINTS_TO_SELL), mint, sell_time).await?;
 
-        println!("Token {} added to the sell queue, will be sold after {}ms", mint, delay_ms);
+        info!(mint, delay_ms, sell_time, "Queued {} for sell in {}ms (at {})", mint, delay_ms, format_wall_clock(sell_time));
 
         Ok(())
     }
 
-    // Store Mint address and corresponding token amount, and set the automatic sell time
-    pub async fn store_mint_with_amount(&self, mint: &str, amount: u64, delay_ms: u64) -> Result<(), RedisError> {
-        let mut conn = self.connection.lock().await;
+    // Record a completed buy in one MULTI/EXEC block: queue the mint for sale, store the
+    // purchased amount and buy time, and append the trade to the history stream. A buy of a mint
+    // that is still held adds to its position: the amounts are summed, the first buy time is
+    // kept and so is the queued sell time (ZADD NX), the position is sold as a whole.
+    pub async fn atomic_buy_record(&self, mint: &str, amount: u64, delay_ms: u64, trade_event: &TradeEvent) -> Result<(), RedisError> {
+        let (mut conn, _timer) = self.lock_connection("atomic_buy_record").await;
 
         // Get the current timestamp as the score and add the specified delay time
         let now = SystemTime::now()
@@ -51,23 +242,118 @@ impl RedisClient {
 
         let sell_time = now + delay_ms; // Sell after the specified time
 
-        // Add the mint address to the sorted set, with the score being the sell time
-        conn.zadd("mints_to_sell", mint, sell_time).await?;
+        let results: Vec<Value> = redis::pipe()
+            .atomic()
+            .cmd("ZADD").arg(self.key(MINTS_TO_SELL)).arg("NX").arg(sell_time).arg(mint)
+            .hincr(self.key(MINT_AMOUNTS), mint, amount)
+            .hset_nx(self.key(MINT_BOUGHT_AT), mint, now)
+            .xadd(self.key(TRADE_EVENTS), "*", &trade_event.fields())
+            .query_async(&mut *conn)
+            .await?;
+
+        // EXEC returns one reply per queued command; anything else means the block did not fully apply
+        if results.len() != 4 || results.contains(&Value::Nil) {
+            return Err(RedisError::from((
+                ErrorKind::ResponseError,
+                "Buy record pipeline partially executed",
+                format!("{:?}", results),
+            )));
+        }
+
+        info!(mint, amount, delay_ms, sell_time, "Queued {} for sell in {}ms (at {})", mint, delay_ms, format_wall_clock(sell_time));
+
+        Ok(())
+    }
+
+    // Reserve the snipe of a mint with SET NX PX, so concurrent snipe tasks and instances sharing
+    // the server buy it at most once. Returns false if another task or instance holds it.
+    pub async fn reserve_snipe_slot(&self, mint: &str, instance_id: &str, ttl_ms: u64) -> Result<bool, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("reserve_snipe_slot").await;
+
+        let reserved: Option<String> = redis::cmd("SET")
+            .arg(self.key(&format!("{}{}", SNIPE_SLOT_PREFIX, mint)))
+            .arg(instance_id)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl_ms)
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(reserved.is_some())
+    }
 
-        // Also save the token amount to another hash table
-        conn.hset("mint_amounts", mint, amount.to_string()).await?;
+    // Release a snipe reservation, only if it is still held by this instance: after it expired,
+    // another instance may hold it. Check and delete are one script, so they are atomic.
+    pub async fn release_snipe_slot(&self, mint: &str, instance_id: &str) -> Result<bool, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("release_snipe_slot").await;
+
+        let script = redis::Script::new(
+            r"if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) else return 0 end",
+        );
+        let released: i64 = script
+            .key(self.key(&format!("{}{}", SNIPE_SLOT_PREFIX, mint)))
+            .arg(instance_id)
+            .invoke_async(&mut *conn)
+            .await?;
+
+        Ok(released > 0)
+    }
+
+    // Remember a processed slot, keeping only the newest `keep` of them
+    pub async fn record_processed_slot(&self, slot: u64, keep: usize) -> Result<(), RedisError> {
+        let (mut conn, _timer) = self.lock_connection("record_processed_slot").await;
+
+        redis::pipe()
+            .lpush(self.key(PROCESSED_SLOTS), slot)
+            .ignore()
+            .ltrim(self.key(PROCESSED_SLOTS), 0, keep as isize - 1)
+            .ignore()
+            .query_async(&mut *conn)
+            .await
+    }
+
+    // The most recently processed slot, e.g. from before a restart
+    pub async fn get_last_processed_slot(&self) -> Result<Option<u64>, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("get_last_processed_slot").await;
 
-        println!("Token {} (amount: {}) added to the sell queue, will be sold after {}ms", mint, amount, delay_ms);
+        conn.lindex(self.key(PROCESSED_SLOTS), 0).await
+    }
+
+    // Remember the fee and landing outcome of a tracked buy, keeping only the newest `keep`
+    pub async fn record_fee_sample(&self, sample: &str, keep: usize) -> Result<(), RedisError> {
+        let (mut conn, _timer) = self.lock_connection("record_fee_sample").await;
+
+        redis::pipe()
+            .lpush(self.key(FEE_SAMPLES), sample)
+            .ignore()
+            .ltrim(self.key(FEE_SAMPLES), 0, keep as isize - 1)
+            .ignore()
+            .query_async(&mut *conn)
+            .await
+    }
+
+    // The newest `limit` fee samples, newest first
+    pub async fn get_fee_samples(&self, limit: usize) -> Result<Vec<String>, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("get_fee_samples").await;
+
+        conn.lrange(self.key(FEE_SAMPLES), 0, limit as isize - 1).await
+    }
+
+    // Append a trade to the trade history stream
+    pub async fn record_trade_event(&self, trade_event: &TradeEvent) -> Result<(), RedisError> {
+        let (mut conn, _timer) = self.lock_connection("record_trade_event").await;
+
+        let _: String = conn.xadd(self.key(TRADE_EVENTS), "*", &trade_event.fields()).await?;
 
         Ok(())
     }
 
     // Get the amount of a specified token
     pub async fn get_mint_amount(&self, mint: &str) -> Result<Option<u64>, RedisError> {
-        let mut conn = self.connection.lock().await;
+        let (mut conn, _timer) = self.lock_connection("get_mint_amount").await;
 
         // Get the token amount from the hash table
-        let amount: Option<String> = conn.hget("mint_amounts", mint).await?;
+        let amount: Option<String> = conn.hget(self.key(MINT_AMOUNTS), mint).await?;
 
         // Convert the string to u64
         match amount {
@@ -81,9 +367,27 @@ impl RedisClient {
         }
     }
 
+    // Get all mints with a stored token amount, i.e. positions that have not been sold yet
+    pub async fn get_open_positions(&self) -> Result<Vec<String>, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("get_open_positions").await;
+
+        let mints: Vec<String> = conn.hkeys(self.key(MINT_AMOUNTS)).await?;
+
+        Ok(mints)
+    }
+
+    // Creators on the shared allowlist, maintained with SADD/SREM
+    pub async fn get_creator_allowlist(&self) -> Result<Vec<String>, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("get_creator_allowlist").await;
+
+        let creators: Vec<String> = conn.smembers(self.key(CREATOR_ALLOWLIST)).await?;
+
+        Ok(creators)
+    }
+
     // Get the list of tokens that need to be sold upon expiration
     pub async fn get_mints_to_sell(&self) -> Result<Vec<String>, RedisError> {
-        let mut conn = self.connection.lock().await;
+        let (mut conn, _timer) = self.lock_connection("get_mints_to_sell").await;
 
         // Get the current timestamp
         let now = SystemTime::now()
@@ -92,49 +396,191 @@ impl RedisClient {
             .as_millis() as u64;
 
         // Query all mint addresses with a score less than or equal to the current time
-        let mints_to_sell: Vec<String> = conn.zrangebyscore("mints_to_sell", 0, now).await?;
+        let mints_to_sell: Vec<String> = conn.zrangebyscore(self.key(MINTS_TO_SELL), 0, now).await?;
 
         Ok(mints_to_sell)
     }
 
     // Remove sold tokens from Redis
-    pub async fn remove_sold_mint(&self, mint: &str) -> Result<(), RedisError> {
-        let mut conn = self.connection.lock().await;
+    // Returns whether the mint was still queued for sale
+    pub async fn remove_sold_mint(&self, mint: &str) -> Result<bool, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("remove_sold_mint").await;
 
         // Remove the specified mint address from the sorted set
-        conn.zrem("mints_to_sell", mint).await?;
+        let removed: u64 = conn.zrem(self.key(MINTS_TO_SELL), mint).await?;
 
-        // Also delete the token amount record
-        conn.hdel("mint_amounts", mint).await?;
+        // Also delete the token amount and buy time records
+        let _: () = conn.hdel(self.key(MINT_AMOUNTS), mint).await?;
+        let _: () = conn.hdel(self.key(MINT_BOUGHT_AT), mint).await?;
 
         println!("Removed token from sell queue: {}", mint);
 
-        Ok(())
+        Ok(removed > 0)
     }
 
-    // Get and remove all tokens that need to be sold
-    pub async fn get_and_remove_mints_to_sell(&self) -> Result<Vec<String>, RedisError> {
-        // First get the tokens to be sold
-        let mints_to_sell = self.get_mints_to_sell().await?;
-
-        if mints_to_sell.is_empty() {
-            return Ok(vec![]);
+    // Settle a submitted sell of `sold_amount` tokens: subtract it from the stored amount and
+    // close the position (queue entry, amount and buy time) once nothing is left. Tokens bought
+    // while the sell was in flight stay queued. Returns whether the position was closed.
+    pub async fn settle_sold_mint(&self, mint: &str, sold_amount: u64) -> Result<bool, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("settle_sold_mint").await;
+
+        let script = redis::Script::new(
+            r"local remaining = redis.call('HINCRBY', KEYS[1], ARGV[1], -tonumber(ARGV[2]))
+              if remaining > 0 then return 0 end
+              redis.call('HDEL', KEYS[1], ARGV[1])
+              redis.call('HDEL', KEYS[2], ARGV[1])
+              redis.call('ZREM', KEYS[3], ARGV[1])
+              return 1",
+        );
+        let closed: i64 = script
+            .key(self.key(MINT_AMOUNTS))
+            .key(self.key(MINT_BOUGHT_AT))
+            .key(self.key(MINTS_TO_SELL))
+            .arg(mint)
+            .arg(sold_amount)
+            .invoke_async(&mut *conn)
+            .await?;
+
+        if closed > 0 {
+            println!("Removed token from sell queue: {}", mint);
+        } else {
+            println!("Sold {} tokens of {}, the rest of the position stays queued", sold_amount, mint);
         }
 
-        let mut conn = self.connection.lock().await;
+        Ok(closed > 0)
+    }
+
+    // Get and remove all tokens that need to be sold, with the sell time they were queued for
+    pub async fn get_and_remove_mints_to_sell(&self) -> Result<Vec<(String, u64)>, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("remove_mints_to_sell").await;
 
         // Get the current timestamp
-        let _now = SystemTime::now()
+        let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_millis() as u64;
 
+        // Query all mint addresses with a score less than or equal to the current time
+        let mints_to_sell: Vec<(String, u64)> = conn.zrangebyscore_withscores(self.key(MINTS_TO_SELL), 0, now).await?;
+
         // Remove all obtained tokens using the ZREM command
         // Note: The redis-rs library might not have a direct zremrangebyscore method, use zrem instead
-        for mint in &mints_to_sell {
-            conn.zrem("mints_to_sell", mint).await?;
+        for (mint, _) in &mints_to_sell {
+            let _: () = conn.zrem(self.key(MINTS_TO_SELL), mint).await?;
         }
 
         Ok(mints_to_sell)
     }
+
+    // Open positions bought at least max_hold_ms ago
+    async fn expired_positions(&self, conn: &mut AsyncConnection, max_hold_ms: u64) -> Result<Vec<String>, RedisError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        let bought_at: HashMap<String, u64> = conn.hgetall(self.key(MINT_BOUGHT_AT)).await?;
+
+        Ok(bought_at
+            .into_iter()
+            .filter(|(_, bought_at)| now.saturating_sub(*bought_at) >= max_hold_ms)
+            .map(|(mint, _)| mint)
+            .collect())
+    }
+
+    // Get the open positions bought at least max_hold_ms ago, leaving them queued
+    pub async fn get_expired_positions(&self, max_hold_ms: u64) -> Result<Vec<String>, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("get_expired_positions").await;
+        self.expired_positions(&mut conn, max_hold_ms).await
+    }
+
+    // Get the open positions bought at least max_hold_ms ago and remove them from the sell queue,
+    // so they are sold now regardless of their scheduled sell time
+    pub async fn get_and_remove_expired_positions(&self, max_hold_ms: u64) -> Result<Vec<String>, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("get_and_remove_expired_positions").await;
+
+        let expired = self.expired_positions(&mut conn, max_hold_ms).await?;
+        for mint in &expired {
+            let _: () = conn.zrem(self.key(MINTS_TO_SELL), mint).await?;
+        }
+
+        Ok(expired)
+    }
+
+    // Queue an open position for immediate sale; returns false if there is no such position
+    pub async fn schedule_sell_now(&self, mint: &str) -> Result<bool, RedisError> {
+        let (mut conn, _timer) = self.lock_connection("schedule_sell_now").await;
+
+        let open: bool = conn.hexists(self.key(MINT_AMOUNTS), mint).await?;
+        if open {
+            // Score 0 is always due
+            let _: () = conn.zadd(self.key(MINTS_TO_SELL), mint, 0).await?;
+        }
+
+        Ok(open)
+    }
+
+    // Listen for messages on the command channel and pass each to the handler, in order.
+    // Pub/sub needs a dedicated connection, so this does not use the shared one.
+    pub async fn subscribe_commands<F>(&self, handler: F) -> Result<(), SniperError>
+    where
+        F: Fn(String, String) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync + 'static,
+    {
+        let mut pubsub = self.client.get_async_connection().await?.into_pubsub();
+        let channel = self.key(COMMAND_CHANNEL);
+        pubsub.subscribe(&channel).await?;
+        println!("Listening for commands on Redis channel {}", channel);
+
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            let channel = message.get_channel_name().to_string();
+            match message.get_payload::<String>() {
+                Ok(payload) => handler(channel, payload).await,
+                Err(e) => println!("Invalid command payload on {}: {:?}", channel, e),
+            }
+        }
+
+        Err(SniperError::Other("Command subscription closed".to_string()))
+    }
+
+    // Ping latency and sell queue state; connected is false if any request fails
+    pub async fn health_status(&self) -> HealthStatus {
+        let (mut conn, _timer) = self.lock_connection("health_status").await;
+
+        let started = Instant::now();
+        if redis::cmd("PING").query_async::<_, String>(&mut *conn).await.is_err() {
+            return HealthStatus::default();
+        }
+        let ping_latency_ms = started.elapsed().as_millis() as u64;
+
+        let queue_depth: usize = match conn.zcard(self.key(MINTS_TO_SELL)).await {
+            Ok(depth) => depth,
+            Err(_) => return HealthStatus::default(),
+        };
+
+        // The lowest score is the earliest scheduled sell time
+        let oldest: Vec<(String, u64)> = match conn.zrange_withscores(self.key(MINTS_TO_SELL), 0, 0).await {
+            Ok(oldest) => oldest,
+            Err(_) => return HealthStatus::default(),
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        HealthStatus {
+            connected: true,
+            ping_latency_ms,
+            queue_depth,
+            oldest_pending_sell_ms: oldest.first().map(|(_, sell_time)| now.saturating_sub(*sell_time)),
+        }
+    }
+}
+
+// Local wall-clock time of a millisecond Unix timestamp, for logs
+pub fn format_wall_clock(timestamp_ms: u64) -> String {
+    match Local.timestamp_millis_opt(timestamp_ms as i64).single() {
+        Some(time) => time.format("%H:%M:%S%.3f").to_string(),
+        None => timestamp_ms.to_string(),
+    }
 }
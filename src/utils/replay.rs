@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Minimum time between two dumps, so a burst of failures does not write the same frames repeatedly
+const CAPTURE_DUMP_COOLDOWN: Duration = Duration::from_secs(10);
+
+// One recorded Shredstream message
+#[derive(Clone)]
+pub struct RecordedFrame {
+    pub slot: u64,
+    pub entries: Vec<u8>, // Serialized Vec<Entry>, as received in the Shredstream message
+}
+
+// Read a recording of Shredstream messages. Each record is the slot (u64 LE), the payload
+// length (u32 LE) and the payload itself. A truncated final record is dropped.
+pub fn read_recorded_frames(path: &Path) -> Result<Vec<RecordedFrame>, Box<dyn Error>> {
+    let data = fs::read(path)?;
+    let mut frames = Vec::new();
+    let mut offset = 0;
+
+    while offset + 12 <= data.len() {
+        let slot = u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        let len = u32::from_le_bytes(data[offset + 8..offset + 12].try_into().unwrap()) as usize;
+        offset += 12;
+
+        if offset + len > data.len() {
+            println!("Recording {} ends with a truncated frame at slot {}", path.display(), slot);
+            break;
+        }
+
+        frames.push(RecordedFrame {
+            slot,
+            entries: data[offset..offset + len].to_vec(),
+        });
+        offset += len;
+    }
+
+    Ok(frames)
+}
+
+// Write frames in the recording format read by read_recorded_frames
+pub fn write_recorded_frames(path: &Path, frames: &[RecordedFrame]) -> io::Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    for frame in frames {
+        writer.write_all(&frame.slot.to_le_bytes())?;
+        writer.write_all(&(frame.entries.len() as u32).to_le_bytes())?;
+        writer.write_all(&frame.entries)?;
+    }
+    writer.flush()
+}
+
+struct CaptureState {
+    frames: VecDeque<RecordedFrame>,
+    last_dump: Option<Instant>,
+}
+
+// Ring buffer of the last raw frames, dumped to disk on an anomaly (parse error, failed snipe,
+// SIGUSR1) for offline inspection. Dumps use the recording format, so they can be fed back through
+// --dry-run-replay.
+#[derive(Clone)]
+pub struct FrameCapture {
+    capacity: usize,
+    dir: PathBuf,
+    state: Arc<Mutex<CaptureState>>,
+}
+
+impl FrameCapture {
+    pub fn new(capacity: usize, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            capacity,
+            dir: dir.into(),
+            state: Arc::new(Mutex::new(CaptureState { frames: VecDeque::with_capacity(capacity), last_dump: None })),
+        }
+    }
+
+    pub fn push(&self, slot: u64, entries: &[u8]) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.frames.len() >= self.capacity {
+            state.frames.pop_front();
+        }
+        state.frames.push_back(RecordedFrame { slot, entries: entries.to_vec() });
+    }
+
+    // Dump the buffered frames in the background. Dumps within the cooldown of the previous one
+    // are skipped, the frames around the first anomaly of a burst are the interesting ones.
+    pub fn dump(&self, reason: &str) {
+        let frames: Vec<RecordedFrame> = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.frames.is_empty() || state.last_dump.is_some_and(|last| last.elapsed() < CAPTURE_DUMP_COOLDOWN) {
+                return;
+            }
+            state.last_dump = Some(Instant::now());
+            state.frames.iter().cloned().collect()
+        };
+
+        let last_slot = frames.last().map(|frame| frame.slot).unwrap_or_default();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let path = self.dir.join(format!("frames-{}-{}.bin", last_slot, timestamp));
+        let reason = reason.to_string();
+        let dir = self.dir.clone();
+
+        tokio::task::spawn_blocking(move || {
+            match fs::create_dir_all(&dir).and_then(|_| write_recorded_frames(&path, &frames)) {
+                Ok(()) => println!("Captured {} frames up to slot {} ({}) to {}", frames.len(), last_slot, reason, path.display()),
+                Err(e) => println!("Failed to write frame capture {}: {}", path.display(), e),
+            }
+        });
+    }
+}
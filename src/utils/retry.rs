@@ -0,0 +1,72 @@
+use std::future::Future;
+use rand::Rng;
+use tokio::time::{sleep, Duration};
+
+// How a failed operation is retried: up to max_attempts tries in total, waiting
+// base_delay_ms * multiplier^(retry - 1) between them, capped at max_delay_ms and spread by up to
+// jitter_ms either way so several instances do not retry in lockstep
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub multiplier: f64,
+    pub jitter_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay_ms: 1_000,
+            multiplier: 2.0,
+            jitter_ms: 0,
+            max_delay_ms: 10_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Delay before the next try after `attempt` failed tries, None once the attempts are used up.
+    // For loops that cannot hand the operation to `run`, e.g. because it needs &mut self.
+    pub fn delay_after(&self, attempt: u32) -> Option<Duration> {
+        if attempt >= self.max_attempts.max(1) {
+            return None;
+        }
+
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay_ms = (self.base_delay_ms as f64 * self.multiplier.max(1.0).powi(exponent)).min(self.max_delay_ms as f64) as u64;
+        let delay_ms = if self.jitter_ms > 0 {
+            let offset = rand::thread_rng().gen_range(-(self.jitter_ms as i64)..=self.jitter_ms as i64);
+            (delay_ms as i64 + offset).max(0) as u64
+        } else {
+            delay_ms
+        };
+        Some(Duration::from_millis(delay_ms))
+    }
+
+    // Run `operation` until it succeeds or the attempts are used up, returning the last error.
+    // `operation` gets the number of the try, starting at 1; `label` names it in the retry log.
+    pub async fn run<T, E, F, Fut>(&self, label: &str, mut operation: F) -> Result<T, E>
+    where
+        E: std::fmt::Display,
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match operation(attempt).await {
+                Ok(value) => return Ok(value),
+                Err(e) => match self.delay_after(attempt) {
+                    Some(delay) => {
+                        println!("{} failed ({}), retrying in {}ms (attempt {}/{})...",
+                                 label, e, delay.as_millis(), attempt, self.max_attempts);
+                        sleep(delay).await;
+                    }
+                    None => return Err(e),
+                },
+            }
+        }
+    }
+}
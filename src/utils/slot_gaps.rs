@@ -0,0 +1,93 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::utils::redis::RedisClient;
+
+// Slots missed across (re)subscriptions, a measure of feed reliability
+#[derive(Default)]
+pub struct SlotGapStats {
+    gaps: AtomicU64,         // Subscriptions that started after the last processed slot
+    missed_slots: AtomicU64, // Slots between the last processed slot and the first slot of a new subscription
+    last_gap: AtomicU64,
+}
+
+impl SlotGapStats {
+    pub fn gaps(&self) -> u64 {
+        self.gaps.load(Ordering::Relaxed)
+    }
+
+    pub fn missed_slots(&self) -> u64 {
+        self.missed_slots.load(Ordering::Relaxed)
+    }
+
+    pub fn last_gap(&self) -> u64 {
+        self.last_gap.load(Ordering::Relaxed)
+    }
+}
+
+// Persists the last processed slots to Redis and, on the first batch of every subscription
+// (including the first after a restart), compares the new slot against the persisted one
+pub struct SlotGapTracker {
+    redis_client: Arc<RedisClient>,
+    keep: usize,
+    subscription: u64,
+    last_slot: u64,
+    stats: Arc<SlotGapStats>,
+}
+
+impl SlotGapTracker {
+    pub fn new(redis_client: Arc<RedisClient>, keep: usize) -> Self {
+        Self {
+            redis_client,
+            keep,
+            subscription: 0,
+            last_slot: 0,
+            stats: Arc::new(SlotGapStats::default()),
+        }
+    }
+
+    pub fn stats(&self) -> Arc<SlotGapStats> {
+        self.stats.clone()
+    }
+
+    // Called for every processed batch with the subscription it was received on
+    pub async fn on_batch(&mut self, slot: u64, subscription: u64) {
+        if subscription != self.subscription {
+            self.subscription = subscription;
+            self.check_gap(slot).await;
+        }
+
+        // One write per slot, off the processing path
+        if slot > self.last_slot {
+            self.last_slot = slot;
+            let redis_client = self.redis_client.clone();
+            let keep = self.keep;
+            tokio::spawn(async move {
+                if let Err(e) = redis_client.record_processed_slot(slot, keep).await {
+                    println!("Failed to persist processed slot {}: {:?}", slot, e);
+                }
+            });
+        }
+    }
+
+    async fn check_gap(&self, slot: u64) {
+        let last_slot = match self.redis_client.get_last_processed_slot().await {
+            Ok(Some(last_slot)) => last_slot,
+            Ok(None) => return,
+            Err(e) => {
+                println!("Failed to get last processed slot: {:?}", e);
+                return;
+            }
+        };
+
+        let gap = slot.saturating_sub(last_slot + 1);
+        self.stats.last_gap.store(gap, Ordering::Relaxed);
+        if gap > 0 {
+            let gaps = self.stats.gaps.fetch_add(1, Ordering::Relaxed) + 1;
+            let missed = self.stats.missed_slots.fetch_add(gap, Ordering::Relaxed) + gap;
+            println!("Feed gap: subscription started at slot {}, last processed slot was {} ({} slots missed; {} gaps, {} slots missed in total)",
+                     slot, last_slot, gap, gaps, missed);
+        } else {
+            println!("No feed gap: subscription started at slot {}, last processed slot was {}", slot, last_slot);
+        }
+    }
+}
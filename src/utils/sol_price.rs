@@ -0,0 +1,112 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+use crate::config::Config;
+use crate::utils::json_number_at;
+
+// Latest SOL/USD price, shared between the refresh task and the trading paths
+#[derive(Clone, Default)]
+pub struct SolUsdPrice(Arc<AtomicU64>); // f64 bits, 0 while unknown
+
+impl SolUsdPrice {
+    pub fn get(&self) -> Option<f64> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            bits => Some(f64::from_bits(bits)),
+        }
+    }
+
+    fn set(&self, price: f64) {
+        self.0.store(price.to_bits(), Ordering::Relaxed);
+    }
+}
+
+// Snipe range and buy amount in lamports. Values configured in USD are converted at the current
+// SOL/USD price; without a price yet, the SOL values of the config apply.
+#[derive(Clone)]
+pub struct TradeLimits {
+    config: Arc<Config>,
+    sol_usd_price: SolUsdPrice,
+}
+
+impl TradeLimits {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config, sol_usd_price: SolUsdPrice::default() }
+    }
+
+    pub fn sol_usd_price(&self) -> SolUsdPrice {
+        self.sol_usd_price.clone()
+    }
+
+    pub fn min_sol_price(&self) -> u64 {
+        self.lamports(self.config.min_usd_price, self.config.min_sol_price)
+    }
+
+    pub fn max_sol_price(&self) -> u64 {
+        self.lamports(self.config.max_usd_price, self.config.max_sol_price)
+    }
+
+    pub fn buy_sol_amount(&self) -> u64 {
+        self.lamports(self.config.buy_usd_amount, self.config.buy_sol_amount)
+    }
+
+    pub fn in_price_range(&self, sol_amount: u64) -> bool {
+        sol_amount >= self.min_sol_price() && sol_amount <= self.max_sol_price()
+    }
+
+    fn lamports(&self, usd: Option<f64>, sol_lamports: u64) -> u64 {
+        match (usd, self.sol_usd_price.get()) {
+            (Some(usd), Some(sol_usd)) => (usd / sol_usd * 1_000_000_000.0) as u64,
+            _ => sol_lamports,
+        }
+    }
+}
+
+// Fetch the SOL/USD price from the configured price API. `field` is the dot-separated path of
+// the price in the JSON response, e.g. "solana.usd" for CoinGecko's simple price endpoint.
+async fn fetch_sol_usd_price(http_client: &reqwest::Client, url: &str, field: &str) -> Result<f64, String> {
+    let response: serde_json::Value = http_client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let price = json_number_at(&response, field)?;
+
+    if price > 0.0 {
+        Ok(price)
+    } else {
+        Err(format!("invalid price {}", price))
+    }
+}
+
+// Fetch the price once now, then refresh it periodically in the background. On failure the last
+// known price is kept and an alert is logged.
+pub async fn spawn_sol_price_refresh(config: Arc<Config>, price: SolUsdPrice) -> JoinHandle<()> {
+    let http_client = reqwest::Client::new();
+    let refresh = move |http_client: reqwest::Client, config: Arc<Config>, price: SolUsdPrice| async move {
+        match fetch_sol_usd_price(&http_client, &config.sol_usd_price_url, &config.sol_usd_price_field).await {
+            Ok(sol_usd) => price.set(sol_usd),
+            Err(e) => match price.get() {
+                Some(last) => println!("ALERT: SOL/USD price refresh failed ({}), holding last price ${:.2}", e, last),
+                None => println!("ALERT: SOL/USD price unavailable ({}), using the SOL amounts of the config", e),
+            },
+        }
+    };
+
+    refresh(http_client.clone(), config.clone(), price.clone()).await;
+    if let Some(sol_usd) = price.get() {
+        println!("SOL/USD price: ${:.2}", sol_usd);
+    }
+
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(config.sol_usd_refresh_ms.max(1_000))).await;
+            refresh(http_client.clone(), config.clone(), price.clone()).await;
+        }
+    })
+}
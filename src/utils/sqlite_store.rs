@@ -0,0 +1,166 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use rusqlite::{params, Connection};
+use crate::error::SniperError;
+use crate::processor::TokenReserves;
+use crate::utils::redis::TradeEvent;
+use crate::utils::store::{PositionStore, StoreFuture};
+use crate::utils::unix_time_ms;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS positions (
+    mint TEXT PRIMARY KEY,
+    token_amount INTEGER NOT NULL,
+    buy_sol_amount INTEGER NOT NULL,
+    buy_signature TEXT NOT NULL,
+    bought_at INTEGER NOT NULL,
+    sell_at INTEGER NOT NULL,
+    sell_signature TEXT,
+    sold_at INTEGER
+);
+CREATE TABLE IF NOT EXISTS trades (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    side TEXT NOT NULL,
+    mint TEXT NOT NULL,
+    token_amount INTEGER NOT NULL,
+    sol_amount INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    slot INTEGER,
+    timestamp INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS reserve_snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    mint TEXT NOT NULL,
+    virtual_sol_reserves INTEGER NOT NULL,
+    virtual_token_reserves INTEGER NOT NULL,
+    slot INTEGER,
+    timestamp INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS trades_mint ON trades (mint);
+CREATE INDEX IF NOT EXISTS reserve_snapshots_mint ON reserve_snapshots (mint);
+";
+
+// Durable store for analysis with SQL. Closed positions are kept with their sell, not deleted.
+// Writes run on a blocking thread so a slow disk does not stall the tokio worker.
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    // Open (or create) the database and its tables
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    // A panic during a write leaves the connection usable, SQLite rolls back the open statement
+    async fn with_connection<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    ) -> Result<T, SniperError> {
+        let connection = self.connection.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().unwrap_or_else(|e| e.into_inner());
+            f(&mut connection)
+        })
+        .await
+        .map_err(|e| SniperError::Other(format!("SQLite write task failed: {}", e)))?;
+        Ok(result?)
+    }
+
+    fn insert_trade(connection: &Connection, trade_event: &TradeEvent, timestamp: i64) -> rusqlite::Result<usize> {
+        connection.execute(
+            "INSERT INTO trades (side, mint, token_amount, sol_amount, signature, slot, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                trade_event.side,
+                trade_event.mint,
+                trade_event.token_amount as i64,
+                trade_event.sol_amount as i64,
+                trade_event.signature,
+                trade_event.slot.map(|slot| slot as i64),
+                timestamp,
+            ],
+        )
+    }
+}
+
+impl PositionStore for SqliteStore {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn record_buy<'a>(&'a self, trade_event: &'a TradeEvent, delay_ms: u64) -> StoreFuture<'a> {
+        Box::pin(async move {
+            let now = unix_time_ms() as i64;
+            let trade_event = trade_event.clone();
+            self.with_connection(move |connection| {
+                // The position and its trade are written together or not at all
+                let transaction = connection.transaction()?;
+                // A re-entry buy adds to the open position, which keeps its first buy and sell time
+                transaction.execute(
+                    "INSERT INTO positions (mint, token_amount, buy_sol_amount, buy_signature, bought_at, sell_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                     ON CONFLICT(mint) DO UPDATE SET
+                         token_amount = token_amount + excluded.token_amount,
+                         buy_sol_amount = buy_sol_amount + excluded.buy_sol_amount",
+                    params![
+                        trade_event.mint,
+                        trade_event.token_amount as i64,
+                        trade_event.sol_amount as i64,
+                        trade_event.signature,
+                        now,
+                        now + delay_ms as i64,
+                    ],
+                )?;
+                Self::insert_trade(&transaction, &trade_event, now)?;
+                transaction.commit()
+            })
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn record_sell<'a>(&'a self, trade_event: &'a TradeEvent) -> StoreFuture<'a> {
+        Box::pin(async move {
+            let now = unix_time_ms() as i64;
+            let trade_event = trade_event.clone();
+            self.with_connection(move |connection| {
+                let transaction = connection.transaction()?;
+                transaction.execute(
+                    "UPDATE positions SET sell_signature = ?2, sold_at = ?3 WHERE mint = ?1",
+                    params![trade_event.mint, trade_event.signature, now],
+                )?;
+                Self::insert_trade(&transaction, &trade_event, now)?;
+                transaction.commit()
+            })
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn record_reserves<'a>(&'a self, mint: &'a str, reserves: TokenReserves, slot: Option<u64>) -> StoreFuture<'a> {
+        Box::pin(async move {
+            let mint = mint.to_string();
+            self.with_connection(move |connection| {
+                connection.execute(
+                    "INSERT INTO reserve_snapshots (mint, virtual_sol_reserves, virtual_token_reserves, slot, timestamp)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        mint,
+                        reserves.virtual_sol_reserves as i64,
+                        reserves.virtual_token_reserves as i64,
+                        slot.map(|slot| slot as i64),
+                        unix_time_ms() as i64,
+                    ],
+                )
+            })
+            .await?;
+            Ok(())
+        })
+    }
+}
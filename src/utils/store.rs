@@ -0,0 +1,47 @@
+use std::future::Future;
+use std::pin::Pin;
+use crate::error::SniperError;
+use crate::processor::TokenReserves;
+use crate::utils::redis::{RedisClient, TradeEvent};
+
+pub type StoreFuture<'a> = Pin<Box<dyn Future<Output = Result<(), SniperError>> + Send + 'a>>;
+
+// Persistence of positions, the trade journal and reserve snapshots
+pub trait PositionStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Open a position from a landed buy, to be sold after delay_ms
+    fn record_buy<'a>(&'a self, trade_event: &'a TradeEvent, delay_ms: u64) -> StoreFuture<'a>;
+
+    // Close the position of a submitted sell and journal the trade
+    fn record_sell<'a>(&'a self, trade_event: &'a TradeEvent) -> StoreFuture<'a>;
+
+    // Keep a snapshot of a token's virtual reserves
+    fn record_reserves<'a>(&'a self, mint: &'a str, reserves: TokenReserves, slot: Option<u64>) -> StoreFuture<'a>;
+}
+
+impl PositionStore for RedisClient {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    fn record_buy<'a>(&'a self, trade_event: &'a TradeEvent, delay_ms: u64) -> StoreFuture<'a> {
+        Box::pin(async move {
+            self.atomic_buy_record(&trade_event.mint, trade_event.token_amount, delay_ms, trade_event).await?;
+            Ok(())
+        })
+    }
+
+    fn record_sell<'a>(&'a self, trade_event: &'a TradeEvent) -> StoreFuture<'a> {
+        Box::pin(async move {
+            self.remove_sold_mint(&trade_event.mint).await?;
+            self.record_trade_event(trade_event).await?;
+            Ok(())
+        })
+    }
+
+    fn record_reserves<'a>(&'a self, _mint: &'a str, _reserves: TokenReserves, _slot: Option<u64>) -> StoreFuture<'a> {
+        // Redis only holds live state, reserve history is not kept
+        Box::pin(async { Ok(()) })
+    }
+}
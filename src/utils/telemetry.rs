@@ -0,0 +1,45 @@
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{trace, Resource};
+use tracing_subscriber::prelude::*;
+
+// Service name the spans are reported under
+const SERVICE_NAME: &str = "pumpfun-sniper";
+
+// Install the tracing subscriber. Events always go to stdout; with an OTLP endpoint the spans of
+// the hot path (entry_batch > deserialize/parse > snipe_decision > snipe > transaction_build/send/
+// confirmation) are also exported to the collector.
+pub fn init_tracing(otlp_endpoint: Option<&str>) {
+    let otlp_endpoint = match otlp_endpoint {
+        Some(endpoint) => endpoint,
+        None => {
+            tracing_subscriber::fmt::init();
+            return;
+        }
+    };
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(otlp_endpoint))
+        .with_trace_config(trace::config().with_resource(Resource::new(vec![KeyValue::new("service.name", SERVICE_NAME)])))
+        .install_batch(opentelemetry_sdk::runtime::Tokio);
+
+    match tracer {
+        Ok(tracer) => {
+            tracing_subscriber::registry()
+                .with(tracing_subscriber::fmt::layer())
+                .with(tracing_opentelemetry::layer().with_tracer(tracer))
+                .init();
+            println!("Exporting traces to {}", otlp_endpoint);
+        }
+        Err(e) => {
+            tracing_subscriber::fmt::init();
+            println!("Failed to set up trace export to {}, traces disabled: {}", otlp_endpoint, e);
+        }
+    }
+}
+
+// Flush the spans still buffered by the batch exporter
+pub fn shutdown_tracing() {
+    opentelemetry::global::shutdown_tracer_provider();
+}